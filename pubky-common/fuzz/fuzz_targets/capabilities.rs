@@ -0,0 +1,34 @@
+//! Fuzz [Capability] and [Capabilities] parsing with arbitrary strings.
+//!
+//! Run with `cargo +nightly fuzz run capabilities` from `pubky-common/`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pubky_common::capabilities::{Capabilities, Capability};
+
+fuzz_target!(|data: &[u8]| {
+    let input = String::from_utf8_lossy(data);
+
+    if let Ok(capability) = Capability::try_from(input.as_ref()) {
+        let string = capability.to_string();
+
+        assert_eq!(
+            Capability::try_from(string.as_str()),
+            Ok(capability.clone())
+        );
+
+        // A valid capability survives being serialized in a list.
+        assert_eq!(
+            Capabilities::try_from(string.as_str()),
+            Ok(Capabilities(vec![capability]))
+        );
+    }
+
+    let capabilities = Capabilities::try_from(input.as_ref()).expect("infallible");
+
+    assert_eq!(
+        Capabilities::try_from(capabilities.to_string().as_str()),
+        Ok(capabilities)
+    );
+});
@@ -18,6 +18,11 @@ const CURRENT_VERSION: u8 = 0;
 // 45 seconds in the past or the future
 const TIMESTAMP_WINDOW: i64 = 45 * 1_000_000;
 
+/// Maximum number of capabilities an [AuthToken] can have.
+pub const MAX_CAPABILITIES: usize = 64;
+/// Maximum size of a serialized [AuthToken] in bytes, including its capabilities.
+pub const MAX_AUTH_TOKEN_SIZE: usize = 8 * 1024;
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 /// Implementation of the [Pubky Auth spec](https://pubky.github.io/pubky-core/spec/auth.html).
 pub struct AuthToken {
@@ -30,7 +35,7 @@ pub struct AuthToken {
     ///
     /// Version 0:
     /// - Signer is implicitly the same as the root keypair for
-    ///     the [AuthToken::pubky], without any delegation.
+    ///   the [AuthToken::pubky], without any delegation.
     /// - Capabilities are only meant for resoucres on the homeserver.
     version: u8,
     /// Timestamp
@@ -77,13 +82,28 @@ impl AuthToken {
     // === Public Methods ===
 
     /// Parse and verify an AuthToken.
+    ///
+    /// Rejects tokens larger than [MAX_AUTH_TOKEN_SIZE] or with more
+    /// than [MAX_CAPABILITIES] capabilities.
     pub fn verify(bytes: &[u8]) -> Result<Self, Error> {
-        if bytes[75] > CURRENT_VERSION {
+        if bytes.len() > MAX_AUTH_TOKEN_SIZE {
+            return Err(Error::TooLarge);
+        }
+
+        // Shorter bytes fail to deserialize below, instead of panicking here.
+        if bytes
+            .get(75)
+            .is_some_and(|version| *version > CURRENT_VERSION)
+        {
             return Err(Error::UnknownVersion);
         }
 
         let token = AuthToken::deserialize(bytes)?;
 
+        if token.capabilities.0.len() > MAX_CAPABILITIES {
+            return Err(Error::TooManyCapabilities);
+        }
+
         match token.version {
             0 => {
                 let now = Timestamp::now();
@@ -201,6 +221,12 @@ pub enum Error {
     #[error("AuthToken already used")]
     /// AuthToken already used
     AlreadyUsed,
+    #[error("AuthToken is larger than {MAX_AUTH_TOKEN_SIZE} bytes")]
+    /// AuthToken is larger than [MAX_AUTH_TOKEN_SIZE] bytes
+    TooLarge,
+    #[error("AuthToken has more than {MAX_CAPABILITIES} capabilities")]
+    /// AuthToken has more than [MAX_CAPABILITIES] capabilities
+    TooManyCapabilities,
 }
 
 #[cfg(test)]
@@ -296,4 +322,35 @@ mod tests {
 
         assert_eq!(verifier.verify(serialized), Err(Error::AlreadyUsed));
     }
+
+    #[test]
+    fn too_large() {
+        let signer = Keypair::random();
+        let capabilities = (0..5000)
+            .map(|i| Capability::try_from(format!("/pub/{i}/:rw")).unwrap())
+            .collect::<Vec<_>>();
+
+        let token = AuthToken::sign(&signer, capabilities);
+
+        let serialized = &token.serialize();
+
+        assert_eq!(AuthToken::verify(serialized), Err(Error::TooLarge));
+    }
+
+    #[test]
+    fn too_many_capabilities() {
+        let signer = Keypair::random();
+        let capabilities = (0..MAX_CAPABILITIES + 1)
+            .map(|_| Capability::try_from("/:r").unwrap())
+            .collect::<Vec<_>>();
+
+        let token = AuthToken::sign(&signer, capabilities);
+
+        let serialized = &token.serialize();
+
+        assert_eq!(
+            AuthToken::verify(serialized),
+            Err(Error::TooManyCapabilities)
+        );
+    }
 }
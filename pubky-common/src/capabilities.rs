@@ -4,6 +4,9 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
+/// Maximum length of a [Capability::scope] in bytes.
+pub const MAX_SCOPE_LENGTH: usize = 1024;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// A Capability defines the scope of resources and the actions that the holder
 /// of this capability can access.
@@ -80,16 +83,15 @@ impl TryFrom<&str> for Capability {
     type Error = Error;
 
     fn try_from(value: &str) -> Result<Self, Error> {
-        if value.matches(':').count() != 1 {
-            return Err(Error::InvalidFormat);
-        }
+        let (scope, actions_str) = match value.split_once(':') {
+            Some((scope, actions_str)) if !actions_str.contains(':') => (scope, actions_str),
+            _ => return Err(Error::InvalidFormat),
+        };
 
-        if !value.starts_with('/') {
+        if !scope.starts_with('/') {
             return Err(Error::InvalidScope);
         }
 
-        let actions_str = value.rsplit(':').next().unwrap_or("");
-
         let mut actions = Vec::new();
 
         for char in actions_str.chars() {
@@ -103,10 +105,38 @@ impl TryFrom<&str> for Capability {
             }
         }
 
-        let scope = value[0..value.len() - actions_str.len() - 1].to_string();
+        validate_scope(scope)?;
+
+        Ok(Capability {
+            scope: scope.to_string(),
+            actions,
+        })
+    }
+}
+
+/// Validate that a scope is a reasonably sized, normalized path.
+fn validate_scope(scope: &str) -> Result<(), Error> {
+    if scope.len() > MAX_SCOPE_LENGTH {
+        return Err(Error::ScopeTooLong);
+    }
+
+    // `,` separates [Capabilities], so it would split this scope when serialized in a list.
+    if scope
+        .chars()
+        .any(|c| c.is_control() || matches!(c, '\\' | '?' | '#' | ','))
+    {
+        return Err(Error::InvalidScopeCharacters);
+    }
 
-        Ok(Capability { scope, actions })
+    // Relative segments could escape the scope, like `/pub/../priv/`.
+    if scope
+        .split('/')
+        .any(|segment| segment == "." || segment == "..")
+    {
+        return Err(Error::InvalidScopeCharacters);
     }
+
+    Ok(())
 }
 
 impl Serialize for Capability {
@@ -143,6 +173,14 @@ pub enum Error {
     #[error("Capability: Invalid Action")]
     /// Capability: Invalid Action
     InvalidAction,
+    #[error("Capability: Invalid scope: longer than {MAX_SCOPE_LENGTH} bytes")]
+    /// Capability: Invalid scope: longer than [MAX_SCOPE_LENGTH] bytes
+    ScopeTooLong,
+    #[error(
+        "Capability: Invalid scope: contains control characters, `\\`, `?`, `#`, `,`, or `.`/`..` segments"
+    )]
+    /// Capability: Invalid scope: contains control characters, `\`, `?`, `#`, `,`, or `.`/`..` segments
+    InvalidScopeCharacters,
     #[error("Capabilities: Invalid capabilities format")]
     /// Capabilities: Invalid capabilities format
     InvalidCapabilities,
@@ -154,10 +192,100 @@ pub enum Error {
 pub struct Capabilities(pub Vec<Capability>);
 
 impl Capabilities {
+    /// Read and write access to an app's public directory,
+    /// for example `app_readwrite("pubky.app")` is `/pub/pubky.app/:rw`.
+    pub fn app_readwrite(app: &str) -> Result<Self, Error> {
+        Self::with_actions(&format!("/pub/{}/", app.trim_matches('/')), "rw")
+    }
+
+    /// Read only access to a `prefix` (like `/pub/pubky.app/`),
+    /// for example `read_only("/pub/pubky.app/")` is `/pub/pubky.app/:r`.
+    pub fn read_only(prefix: &str) -> Result<Self, Error> {
+        Self::with_actions(prefix, "r")
+    }
+
+    fn with_actions(scope: &str, actions: &str) -> Result<Self, Error> {
+        Ok(Self(vec![Capability::try_from(
+            format!("{scope}:{actions}").as_str(),
+        )?]))
+    }
+
     /// Returns true if the list of capabilities contains a given capability.
     pub fn contains(&self, capability: &Capability) -> bool {
         self.0.contains(capability)
     }
+
+    /// Returns true if any of these capabilities grants `action` on `path`,
+    /// that is, its scope is a prefix of (or equal to) `path`.
+    pub fn allows(&self, path: &str, action: &Action) -> bool {
+        self.0.iter().any(|capability| {
+            path.starts_with(&capability.scope) && capability.actions.contains(action)
+        })
+    }
+
+    /// Returns true if these capabilities grant no more than `other`.
+    ///
+    /// Every action of every capability in `self` has to be granted by at least one
+    /// capability in `other`, whose scope is a prefix of (or equal to) its scope.
+    /// Actions may be granted by different capabilities, for example
+    /// `/pub/a/:rw` is a subset of `/pub/:r,/pub/a/:w`.
+    pub fn is_subset_of(&self, other: &Capabilities) -> bool {
+        self.uncovered_by(other).is_empty()
+    }
+
+    /// Compare these capabilities, granted to a session, to the `requested` ones,
+    /// for example to react to a user granting less than an app asked for.
+    ///
+    /// Uses the same rules as [Capabilities::is_subset_of], so `/pub/:rw` granted
+    /// for `/pub/app/:rw` requested grants more than requested, but nothing is missing.
+    pub fn diff(&self, requested: &Capabilities) -> CapabilitiesDiff {
+        CapabilitiesDiff {
+            not_requested: self.uncovered_by(requested),
+            not_granted: requested.uncovered_by(self),
+        }
+    }
+
+    /// Returns the actions of each of these capabilities not granted by `other`,
+    /// omitting capabilities fully granted.
+    fn uncovered_by(&self, other: &Capabilities) -> Vec<Capability> {
+        self.0
+            .iter()
+            .filter_map(|capability| {
+                let actions = capability
+                    .actions
+                    .iter()
+                    .filter(|action| {
+                        !other.0.iter().any(|granting| {
+                            capability.scope.starts_with(&granting.scope)
+                                && granting.actions.contains(action)
+                        })
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                (!actions.is_empty()).then(|| Capability {
+                    scope: capability.scope.clone(),
+                    actions,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+/// The differences between granted and requested [Capabilities], see [Capabilities::diff].
+pub struct CapabilitiesDiff {
+    /// Granted capabilities (with only their actions) that weren't requested.
+    pub not_requested: Vec<Capability>,
+    /// Requested capabilities (with only their actions) that weren't granted.
+    pub not_granted: Vec<Capability>,
+}
+
+impl CapabilitiesDiff {
+    /// Returns true if exactly the requested capabilities were granted.
+    pub fn is_empty(&self) -> bool {
+        self.not_requested.is_empty() && self.not_granted.is_empty()
+    }
 }
 
 impl From<Vec<Capability>> for Capabilities {
@@ -247,4 +375,187 @@ mod tests {
 
         assert_eq!(Capability::try_from(expected_string), Ok(cap))
     }
+
+    #[test]
+    fn templates() {
+        let caps = Capabilities::app_readwrite("pubky.app").unwrap();
+        assert_eq!(caps.to_string(), "/pub/pubky.app/:rw");
+        assert_eq!(Capabilities::try_from("/pub/pubky.app/:rw"), Ok(caps));
+
+        assert_eq!(
+            Capabilities::app_readwrite("/pubky.app/")
+                .unwrap()
+                .to_string(),
+            "/pub/pubky.app/:rw"
+        );
+
+        let caps = Capabilities::read_only("/pub/pubky.app/").unwrap();
+        assert_eq!(caps.to_string(), "/pub/pubky.app/:r");
+        assert_eq!(Capabilities::try_from("/pub/pubky.app/:r"), Ok(caps));
+
+        assert_eq!(
+            Capabilities::app_readwrite("../priv"),
+            Err(Error::InvalidScopeCharacters)
+        );
+        assert_eq!(
+            Capabilities::read_only("pub/pubky.app/"),
+            Err(Error::InvalidScope)
+        );
+    }
+
+    #[test]
+    fn is_subset_of() {
+        let caps = |s: &str| Capabilities::try_from(s).unwrap();
+
+        // Prefix
+        assert!(caps("/pub/a/:r").is_subset_of(&caps("/pub/:rw")));
+        assert!(caps("/pub/a/:rw").is_subset_of(&caps("/pub/a/:rw")));
+        assert!(caps("/pub/a/:rw,/pub/b/:r").is_subset_of(&caps("/:rw")));
+        assert!(!caps("/pub/:r").is_subset_of(&caps("/pub/a/:rw")));
+        assert!(!caps("/priv/:r").is_subset_of(&caps("/pub/:rw")));
+
+        // Actions
+        assert!(!caps("/pub/a/:rw").is_subset_of(&caps("/pub/:r")));
+        assert!(caps("/pub/a/:rw").is_subset_of(&caps("/pub/:r,/pub/a/:w")));
+        assert!(!caps("/pub/a/:rw").is_subset_of(&caps("/pub/:r,/pub/a/b/:w")));
+
+        // Empty
+        assert!(caps("").is_subset_of(&caps("")));
+        assert!(caps("").is_subset_of(&caps("/pub/:r")));
+        assert!(!caps("/pub/:r").is_subset_of(&caps("")));
+    }
+
+    #[test]
+    fn allows() {
+        let caps = Capabilities::try_from("/pub/pubky.app/:rw,/pub/foo.bar/file:r").unwrap();
+
+        assert!(caps.allows("/pub/pubky.app/foo", &Action::Write));
+        assert!(caps.allows("/pub/pubky.app/", &Action::Read));
+        assert!(!caps.allows("/pub/pubky.app", &Action::Write));
+        assert!(caps.allows("/pub/foo.bar/file", &Action::Read));
+        assert!(!caps.allows("/pub/foo.bar/file", &Action::Write));
+        assert!(!caps.allows("/priv/pubky.app/foo", &Action::Read));
+
+        assert!(!Capabilities::default().allows("/pub/", &Action::Read));
+    }
+
+    #[test]
+    fn diff() {
+        let caps = |s: &str| Capabilities::try_from(s).unwrap();
+        let cap = |s: &str| Capability::try_from(s).unwrap();
+
+        // Exactly as requested
+        let diff = caps("/pub/app/:rw").diff(&caps("/pub/app/:rw"));
+        assert!(diff.is_empty());
+
+        // Broader scope
+        assert_eq!(
+            caps("/pub/:rw").diff(&caps("/pub/app/:rw")),
+            CapabilitiesDiff {
+                not_requested: vec![cap("/pub/:rw")],
+                not_granted: vec![],
+            }
+        );
+
+        // Fewer actions
+        assert_eq!(
+            caps("/pub/app/:r").diff(&caps("/pub/app/:rw")),
+            CapabilitiesDiff {
+                not_requested: vec![],
+                not_granted: vec![cap("/pub/app/:w")],
+            }
+        );
+
+        // Different scopes
+        assert_eq!(
+            caps("/pub/other/:r,/pub/app/:r").diff(&caps("/pub/app/:rw,/priv/app/:r")),
+            CapabilitiesDiff {
+                not_requested: vec![cap("/pub/other/:r")],
+                not_granted: vec![cap("/pub/app/:w"), cap("/priv/app/:r")],
+            }
+        );
+
+        // Nothing granted
+        assert_eq!(
+            caps("").diff(&caps("/pub/app/:rw")),
+            CapabilitiesDiff {
+                not_requested: vec![],
+                not_granted: vec![cap("/pub/app/:rw")],
+            }
+        );
+    }
+
+    #[test]
+    fn scope_too_long() {
+        let scope = format!("/pub/{}/", "a".repeat(MAX_SCOPE_LENGTH));
+
+        assert_eq!(
+            Capability::try_from(format!("{scope}:rw")),
+            Err(Error::ScopeTooLong)
+        );
+
+        let scope = format!("/{}", "a".repeat(MAX_SCOPE_LENGTH - 1));
+        assert!(Capability::try_from(format!("{scope}:rw")).is_ok());
+    }
+
+    #[test]
+    fn invalid_scope_characters() {
+        for invalid in [
+            "/pub/foo\0bar/:r",
+            "/pub/foo\nbar/:r",
+            "/pub/foo\\bar/:r",
+            "/pub/foo?bar/:r",
+            "/pub/foo,bar/:r",
+            "/pub/../priv/:rw",
+            "/pub/./:rw",
+        ] {
+            assert_eq!(
+                Capability::try_from(invalid),
+                Err(Error::InvalidScopeCharacters),
+                "{invalid:?}"
+            );
+        }
+
+        assert!(Capability::try_from("/pub/pubky.app/..hidden/:r").is_ok());
+    }
+
+    #[test]
+    fn malformed() {
+        for (invalid, error) in [
+            ("", Error::InvalidFormat),
+            ("/pub/", Error::InvalidFormat),
+            ("/pub/:r:w", Error::InvalidFormat),
+            ("::", Error::InvalidFormat),
+            (":", Error::InvalidScope),
+            (":rw", Error::InvalidScope),
+            ("pub/:rw", Error::InvalidScope),
+            ("/pub/:x", Error::InvalidAction),
+            ("/pub/:r\u{0}", Error::InvalidAction),
+            ("/pub/:é", Error::InvalidAction),
+            ("/pub/\u{fffd}\u{0}:r", Error::InvalidScopeCharacters),
+        ] {
+            assert_eq!(Capability::try_from(invalid), Err(error), "{invalid:?}");
+        }
+
+        // Multi-byte characters around the separator.
+        assert_eq!(Capability::try_from("/pub/é:rw").unwrap().scope, "/pub/é");
+
+        // No actions, no capability granted, but a valid one.
+        assert_eq!(
+            Capability::try_from("/pub/:"),
+            Ok(Capability {
+                scope: "/pub/".to_string(),
+                actions: vec![]
+            })
+        );
+
+        // Invalid capabilities in a list are skipped.
+        assert_eq!(
+            Capabilities::try_from(",:,/pub/:r,/pub/,bar/:w,"),
+            Ok(Capabilities(vec![Capability {
+                scope: "/pub/".to_string(),
+                actions: vec![Action::Read]
+            }]))
+        );
+    }
 }
@@ -1,16 +1,23 @@
 //! Configuration for the server
 
 use anyhow::{anyhow, Context, Result};
-use pkarr::Keypair;
+use pkarr::{Keypair, PublicKey};
+use pubky_common::capabilities::Capability;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Debug,
     fs,
     net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use crate::{core::CoreConfig, io::IoConfig};
+use url::Url;
+
+use crate::{
+    core::{CoreConfig, RateLimiterConfig, ReadThrough},
+    io::IoConfig,
+};
 
 // === Core ==
 pub const DEFAULT_STORAGE_DIR: &str = "pubky";
@@ -19,6 +26,11 @@ pub const DEFAULT_MAP_SIZE: usize = 10995116277760; // 10TB (not = disk-space us
 pub const DEFAULT_LIST_LIMIT: u16 = 100;
 pub const DEFAULT_MAX_LIST_LIMIT: u16 = 1000;
 
+pub const DEFAULT_MAX_ENTRY_SIZE: usize = 100 * 1024 * 1024; // 100MB
+pub const DEFAULT_MAX_PATH_LENGTH: usize = 1024;
+
+pub const DEFAULT_USER_EVENT_LOG_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60); // 30 days
+
 // === IO ===
 pub const DEFAULT_HTTP_PORT: u16 = 6286;
 pub const DEFAULT_HTTPS_PORT: u16 = 6287;
@@ -48,12 +60,55 @@ struct IoToml {
     pub legacy_browsers: Option<LegacyBrowsersTompl>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct ReadThroughToml {
+    pub pubky: String,
+    pub prefix: String,
+    pub upstream: String,
+    pub url: String,
+    pub cache: Option<bool>,
+}
+
+/// Durations are in seconds.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+struct CoreToml {
+    pub default_list_limit: Option<u16>,
+    pub max_list_limit: Option<u16>,
+
+    pub max_entry_size: Option<usize>,
+    pub max_path_length: Option<usize>,
+
+    pub allowed_content_types: Option<Vec<String>>,
+    pub denied_content_types: Option<Vec<String>>,
+
+    pub user_storage_quota: Option<u64>,
+
+    pub session_ttl: Option<u64>,
+    pub max_session_lifetime: Option<u64>,
+
+    pub allowed_pubkys: Option<Vec<String>>,
+    pub max_capabilities: Option<Vec<Capability>>,
+
+    pub require_signed_writes: Option<bool>,
+
+    pub read_through: Option<Vec<ReadThroughToml>>,
+
+    pub public_cache_max_age: Option<u64>,
+
+    pub user_event_log_max_age: Option<u64>,
+
+    pub cors_allowed_origins: Option<Vec<String>>,
+
+    pub rate_limiter: Option<RateLimiterConfig>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 struct ConfigToml {
     secret_key: Option<String>,
 
     database: Option<DatabaseToml>,
     io: Option<IoToml>,
+    core: Option<CoreToml>,
 }
 
 /// Server configuration
@@ -69,7 +124,7 @@ pub struct Config {
 }
 
 impl Config {
-    fn try_from_str(value: &str) -> Result<Self> {
+    pub(crate) fn try_from_str(value: &str) -> Result<Self> {
         let config_toml: ConfigToml = toml::from_str(value)?;
 
         config_toml.try_into()
@@ -175,18 +230,77 @@ impl TryFrom<ConfigToml> for Config {
             }
         };
 
-        Ok(Config {
-            keypair,
+        let core = value.core.unwrap_or_default();
 
-            io,
-            core: CoreConfig {
-                storage,
-                ..Default::default()
-            },
+        let core = CoreConfig {
+            storage,
+            db_map_size: DEFAULT_MAP_SIZE,
+
+            default_list_limit: core.default_list_limit.unwrap_or(DEFAULT_LIST_LIMIT),
+            max_list_limit: core.max_list_limit.unwrap_or(DEFAULT_MAX_LIST_LIMIT),
+
+            max_entry_size: core.max_entry_size.unwrap_or(DEFAULT_MAX_ENTRY_SIZE),
+            max_path_length: core.max_path_length.unwrap_or(DEFAULT_MAX_PATH_LENGTH),
+
+            allowed_content_types: core.allowed_content_types,
+            denied_content_types: core.denied_content_types.unwrap_or_default(),
+
+            user_storage_quota: core.user_storage_quota,
+
+            session_ttl: core.session_ttl.map(Duration::from_secs),
+            max_session_lifetime: core.max_session_lifetime.map(Duration::from_secs),
+
+            allowed_pubkys: core
+                .allowed_pubkys
+                .map(|pubkys| pubkys.iter().map(|pubky| parse_pubky(pubky)).collect())
+                .transpose()?,
+            max_capabilities: core.max_capabilities,
+
+            require_signed_writes: core.require_signed_writes.unwrap_or(false),
+
+            read_through: core
+                .read_through
+                .unwrap_or_default()
+                .into_iter()
+                .map(ReadThrough::try_from)
+                .collect::<Result<_>>()?,
+
+            public_cache_max_age: core.public_cache_max_age.map(Duration::from_secs),
+
+            user_event_log_max_age: core
+                .user_event_log_max_age
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_USER_EVENT_LOG_MAX_AGE),
+
+            cors_allowed_origins: core.cors_allowed_origins,
+
+            rate_limiter: core.rate_limiter,
+        };
+
+        Ok(Config { keypair, io, core })
+    }
+}
+
+impl TryFrom<ReadThroughToml> for ReadThrough {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ReadThroughToml) -> std::result::Result<Self, Self::Error> {
+        Ok(ReadThrough {
+            pubky: parse_pubky(&value.pubky)?,
+            prefix: value.prefix,
+            upstream: parse_pubky(&value.upstream)?,
+            url: Url::parse(&value.url).with_context(|| {
+                format!("invalid read_through url in config.toml: {}", value.url)
+            })?,
+            cache: value.cache.unwrap_or(false),
         })
     }
 }
 
+fn parse_pubky(s: &str) -> anyhow::Result<PublicKey> {
+    PublicKey::try_from(s).with_context(|| format!("invalid pubky in config.toml: {s}"))
+}
+
 fn deserialize_secret_key(s: String) -> anyhow::Result<[u8; 32]> {
     let bytes =
         hex::decode(s).map_err(|_| anyhow!("secret_key in config.toml should hex encoded"))?;
@@ -317,4 +431,99 @@ domain = "example.com"
         );
         assert_eq!(config.io.domain, Some("example.com".to_string()));
     }
+
+    #[test]
+    fn parse_core() {
+        let pubky = Keypair::from_secret_key(&[0; 32]).public_key();
+        let upstream = Keypair::from_secret_key(&[1; 32]).public_key();
+
+        let config = Config::try_from_str(&format!(
+            r#"
+[core]
+default_list_limit = 10
+max_list_limit = 20
+max_entry_size = 1024
+max_path_length = 64
+allowed_content_types = ["image/*"]
+denied_content_types = ["application/x-executable"]
+user_storage_quota = 4096
+session_ttl = 60
+max_session_lifetime = 3600
+allowed_pubkys = ["{pubky}"]
+max_capabilities = ["/pub/:rw"]
+require_signed_writes = true
+public_cache_max_age = 30
+user_event_log_max_age = 86400
+cors_allowed_origins = ["https://example.com"]
+
+[[core.read_through]]
+pubky = "{pubky}"
+prefix = "/pub/shared/"
+upstream = "{upstream}"
+url = "https://homeserver.example.com"
+cache = true
+
+[core.rate_limiter]
+per_second = 1
+burst_size = 5
+        "#
+        ))
+        .unwrap();
+
+        assert_eq!(
+            config.core,
+            CoreConfig {
+                storage: config.core.storage.clone(),
+
+                default_list_limit: 10,
+                max_list_limit: 20,
+
+                max_entry_size: 1024,
+                max_path_length: 64,
+
+                allowed_content_types: Some(vec!["image/*".to_string()]),
+                denied_content_types: vec!["application/x-executable".to_string()],
+
+                user_storage_quota: Some(4096),
+
+                session_ttl: Some(Duration::from_secs(60)),
+                max_session_lifetime: Some(Duration::from_secs(3600)),
+
+                allowed_pubkys: Some(vec![pubky.clone()]),
+                max_capabilities: Some(vec![Capability::try_from("/pub/:rw").unwrap()]),
+
+                require_signed_writes: true,
+
+                read_through: vec![ReadThrough {
+                    pubky,
+                    prefix: "/pub/shared/".to_string(),
+                    upstream,
+                    url: Url::parse("https://homeserver.example.com").unwrap(),
+                    cache: true,
+                }],
+
+                public_cache_max_age: Some(Duration::from_secs(30)),
+
+                user_event_log_max_age: Duration::from_secs(86400),
+
+                cors_allowed_origins: Some(vec!["https://example.com".to_string()]),
+
+                rate_limiter: Some(RateLimiterConfig {
+                    behind_proxy: false,
+                    per_second: 1,
+                    burst_size: 5,
+                }),
+
+                ..Default::default()
+            }
+        );
+
+        assert!(Config::try_from_str(
+            r#"
+[core]
+allowed_pubkys = ["not a pubky"]
+            "#
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,137 @@
+//! Offline integrity checks of the database, for fsck-style maintenance.
+
+use std::collections::HashSet;
+
+use pubky_common::crypto::Hasher;
+
+use super::{tables::entries::Entry, DB};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// Discrepancies found by [DB::verify_full].
+pub struct IntegrityReport {
+    /// Number of entries checked.
+    pub entries_checked: usize,
+    /// Keys of entries that couldn't be deserialized.
+    pub invalid_entries: Vec<String>,
+    /// Keys of entries whose stored content hash doesn't match the hash of their blobs.
+    pub hash_mismatches: Vec<String>,
+    /// Keys of entries whose stored content length doesn't match the length of their blobs.
+    pub length_mismatches: Vec<String>,
+    /// Number of blob chunks not referenced by any entry.
+    pub orphan_chunks: usize,
+}
+
+impl IntegrityReport {
+    /// Returns `true` if no discrepancies were found.
+    ///
+    /// Orphan chunks waste space but don't affect any entry, so they are not counted.
+    pub fn is_ok(&self) -> bool {
+        self.invalid_entries.is_empty()
+            && self.hash_mismatches.is_empty()
+            && self.length_mismatches.is_empty()
+    }
+}
+
+impl DB {
+    /// Verify every entry against its content, recomputing each content hash
+    /// and length from the stored blobs, and count unreferenced blob chunks.
+    ///
+    /// Reads the whole database within a single read transaction.
+    pub fn verify_full(&self) -> anyhow::Result<IntegrityReport> {
+        let rtxn = self.env.read_txn()?;
+
+        let mut report = IntegrityReport::default();
+        let mut referenced = HashSet::new();
+
+        for result in self.tables.entries.iter(&rtxn)? {
+            let (key, bytes) = result?;
+
+            report.entries_checked += 1;
+
+            let entry = match Entry::deserialize(bytes) {
+                Ok(entry) => entry,
+                Err(_) => {
+                    report.invalid_entries.push(key.to_string());
+                    continue;
+                }
+            };
+
+            referenced.insert(entry.timestamp().to_bytes());
+
+            let mut hasher = Hasher::new();
+            let mut length = 0;
+
+            for chunk in entry.read_content(self, &rtxn)? {
+                let chunk = chunk?;
+
+                hasher.update(chunk);
+                length += chunk.len();
+            }
+
+            if &hasher.finalize() != entry.content_hash() {
+                report.hash_mismatches.push(key.to_string());
+            }
+            if length != entry.content_length() {
+                report.length_mismatches.push(key.to_string());
+            }
+        }
+
+        for result in self.tables.blobs.iter(&rtxn)? {
+            let (key, _) = result?;
+
+            if key.len() < 8 || !referenced.contains(&key[..8]) {
+                report.orphan_chunks += 1;
+            }
+        }
+
+        rtxn.commit()?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pkarr::Keypair;
+
+    use super::*;
+
+    #[test]
+    fn verify_full() -> anyhow::Result<()> {
+        let mut db = DB::test();
+
+        let public_key = Keypair::random().public_key();
+
+        let a = db
+            .write_entry(&public_key, "/pub/a")?
+            .update(&[1, 2, 3])?
+            .commit()?;
+        db.write_entry(&public_key, "/pub/b")?
+            .update(&[4, 5, 6])?
+            .commit()?;
+
+        let report = db.verify_full()?;
+
+        assert!(report.is_ok(), "{report:?}");
+        assert_eq!(report.entries_checked, 2);
+        assert_eq!(report.orphan_chunks, 0);
+
+        // Corrupt the content of `/pub/a`.
+        let mut chunk_key = [0; 12];
+        chunk_key[0..8].copy_from_slice(&a.timestamp().to_bytes());
+
+        let mut wtxn = db.env.write_txn()?;
+        db.tables.blobs.put(&mut wtxn, &chunk_key, &[1, 2, 4])?;
+        db.tables.blobs.put(&mut wtxn, &[0; 12], &[0])?;
+        wtxn.commit()?;
+
+        let report = db.verify_full()?;
+
+        assert!(!report.is_ok());
+        assert_eq!(report.hash_mismatches, vec![format!("{public_key}/pub/a")]);
+        assert!(report.length_mismatches.is_empty());
+        assert_eq!(report.orphan_chunks, 1);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,11 @@
 use heed::Env;
 
 mod m0;
+mod m1;
+mod m2;
+mod m3;
+mod m4;
+mod m5;
 
 use super::tables::Tables;
 
@@ -8,6 +13,11 @@ pub fn run(env: &Env) -> anyhow::Result<Tables> {
     let mut wtxn = env.write_txn()?;
 
     m0::run(env, &mut wtxn)?;
+    m1::run(env, &mut wtxn)?;
+    m2::run(env, &mut wtxn)?;
+    m3::run(env, &mut wtxn)?;
+    m4::run(env, &mut wtxn)?;
+    m5::run(env, &mut wtxn)?;
 
     let tables = Tables::new(env, &mut wtxn)?;
 
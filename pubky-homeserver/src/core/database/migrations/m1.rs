@@ -0,0 +1,10 @@
+use heed::{Env, RwTxn};
+
+use crate::core::database::tables::expirations;
+
+pub fn run(env: &Env, wtxn: &mut RwTxn) -> anyhow::Result<()> {
+    let _: expirations::ExpirationsTable =
+        env.create_database(wtxn, Some(expirations::EXPIRATIONS_TABLE))?;
+
+    Ok(())
+}
@@ -0,0 +1,10 @@
+use heed::{Env, RwTxn};
+
+use crate::core::database::tables::sessions;
+
+pub fn run(env: &Env, wtxn: &mut RwTxn) -> anyhow::Result<()> {
+    let _: sessions::SessionExpirationsTable =
+        env.create_database(wtxn, Some(sessions::SESSION_EXPIRATIONS_TABLE))?;
+
+    Ok(())
+}
@@ -0,0 +1,10 @@
+use heed::{Env, RwTxn};
+
+use crate::core::database::tables::content_encodings;
+
+pub fn run(env: &Env, wtxn: &mut RwTxn) -> anyhow::Result<()> {
+    let _: content_encodings::ContentEncodingsTable =
+        env.create_database(wtxn, Some(content_encodings::CONTENT_ENCODINGS_TABLE))?;
+
+    Ok(())
+}
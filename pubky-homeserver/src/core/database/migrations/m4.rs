@@ -0,0 +1,10 @@
+use heed::{Env, RwTxn};
+
+use crate::core::database::tables::user_events;
+
+pub fn run(env: &Env, wtxn: &mut RwTxn) -> anyhow::Result<()> {
+    let _: user_events::UserEventsTable =
+        env.create_database(wtxn, Some(user_events::USER_EVENTS_TABLE))?;
+
+    Ok(())
+}
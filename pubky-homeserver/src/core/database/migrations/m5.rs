@@ -0,0 +1,10 @@
+use heed::{Env, RwTxn};
+
+use crate::core::database::tables::immutable_entries;
+
+pub fn run(env: &Env, wtxn: &mut RwTxn) -> anyhow::Result<()> {
+    let _: immutable_entries::ImmutableEntriesTable =
+        env.create_database(wtxn, Some(immutable_entries::IMMUTABLE_ENTRIES_TABLE))?;
+
+    Ok(())
+}
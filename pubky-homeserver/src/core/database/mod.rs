@@ -4,6 +4,7 @@ use std::{fs, path::PathBuf};
 
 use heed::{Env, EnvOpenOptions};
 
+pub mod integrity;
 mod migrations;
 pub mod tables;
 
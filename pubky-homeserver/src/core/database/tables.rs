@@ -1,7 +1,11 @@
 pub mod blobs;
+pub mod content_encodings;
 pub mod entries;
 pub mod events;
+pub mod expirations;
+pub mod immutable_entries;
 pub mod sessions;
+pub mod user_events;
 pub mod users;
 
 use heed::{Env, RwTxn};
@@ -10,12 +14,16 @@ use blobs::{BlobsTable, BLOBS_TABLE};
 use entries::{EntriesTable, ENTRIES_TABLE};
 
 use self::{
+    content_encodings::{ContentEncodingsTable, CONTENT_ENCODINGS_TABLE},
     events::{EventsTable, EVENTS_TABLE},
-    sessions::{SessionsTable, SESSIONS_TABLE},
+    expirations::{ExpirationsTable, EXPIRATIONS_TABLE},
+    immutable_entries::{ImmutableEntriesTable, IMMUTABLE_ENTRIES_TABLE},
+    sessions::{SessionExpirationsTable, SessionsTable, SESSIONS_TABLE, SESSION_EXPIRATIONS_TABLE},
+    user_events::{UserEventsTable, USER_EVENTS_TABLE},
     users::{UsersTable, USERS_TABLE},
 };
 
-pub const TABLES_COUNT: u32 = 5;
+pub const TABLES_COUNT: u32 = 10;
 
 #[derive(Debug, Clone)]
 pub struct Tables {
@@ -24,6 +32,11 @@ pub struct Tables {
     pub blobs: BlobsTable,
     pub entries: EntriesTable,
     pub events: EventsTable,
+    pub expirations: ExpirationsTable,
+    pub session_expirations: SessionExpirationsTable,
+    pub content_encodings: ContentEncodingsTable,
+    pub user_events: UserEventsTable,
+    pub immutable_entries: ImmutableEntriesTable,
 }
 
 impl Tables {
@@ -44,6 +57,21 @@ impl Tables {
             events: env
                 .open_database(wtxn, Some(EVENTS_TABLE))?
                 .expect("Events table already created"),
+            expirations: env
+                .open_database(wtxn, Some(EXPIRATIONS_TABLE))?
+                .expect("Expirations table already created"),
+            session_expirations: env
+                .open_database(wtxn, Some(SESSION_EXPIRATIONS_TABLE))?
+                .expect("Session expirations table already created"),
+            content_encodings: env
+                .open_database(wtxn, Some(CONTENT_ENCODINGS_TABLE))?
+                .expect("Content encodings table already created"),
+            user_events: env
+                .open_database(wtxn, Some(USER_EVENTS_TABLE))?
+                .expect("User events table already created"),
+            immutable_entries: env
+                .open_database(wtxn, Some(IMMUTABLE_ENTRIES_TABLE))?
+                .expect("Immutable entries table already created"),
         })
     }
 }
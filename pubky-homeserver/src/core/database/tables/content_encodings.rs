@@ -0,0 +1,29 @@
+//! Content codings (like `br`) of entries stored pre-compressed.
+
+use heed::{types::Str, Database, RoTxn};
+use pkarr::PublicKey;
+
+use crate::core::database::DB;
+
+/// full_path(pubky/*path) => Content coding, like `br`.
+pub type ContentEncodingsTable = Database<Str, Str>;
+
+pub const CONTENT_ENCODINGS_TABLE: &str = "content_encodings";
+
+impl DB {
+    /// Returns the content coding the entry at this path is stored with, if any.
+    pub fn content_encoding(
+        &self,
+        txn: &RoTxn,
+        public_key: &PublicKey,
+        path: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let key = format!("{public_key}{path}");
+
+        Ok(self
+            .tables
+            .content_encodings
+            .get(txn, &key)?
+            .map(|encoding| encoding.to_string()))
+    }
+}
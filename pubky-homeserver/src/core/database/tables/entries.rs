@@ -2,19 +2,21 @@ use pkarr::PublicKey;
 use postcard::{from_bytes, to_allocvec};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{Read, Write},
+    ops::Bound,
     path::PathBuf,
 };
 use tracing::instrument;
 
 use heed::{
     types::{Bytes, Str},
-    Database, RoTxn,
+    Database, RoTxn, RwTxn,
 };
 
 use pubky_common::{
-    crypto::{Hash, Hasher},
+    crypto::{hash, Hash, Hasher},
     timestamp::Timestamp,
 };
 
@@ -35,14 +37,39 @@ impl DB {
         &mut self,
         public_key: &PublicKey,
         path: &str,
-    ) -> anyhow::Result<EntryWriter> {
+    ) -> anyhow::Result<EntryWriter<'_>> {
         EntryWriter::new(self, public_key, path)
     }
 
+    /// Returns the height of the entries B+tree, for diagnostics.
+    ///
+    /// It should grow logarithmically with the number of entries.
+    pub fn entries_height(&self, rtxn: &RoTxn) -> anyhow::Result<usize> {
+        Ok(self.tables.entries.stat(rtxn)?.depth as usize)
+    }
+
     /// Delete an entry by an author at a given path.
     ///
     /// The path has to start with a forward slash `/`
+    ///
+    /// Immutable entries are never deleted.
     pub fn delete_entry(&mut self, public_key: &PublicKey, path: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .delete_entry_if(public_key, path, |_| true)?
+            .unwrap_or(false))
+    }
+
+    /// Same as [Self::delete_entry], but only if the `condition` holds for the
+    /// current [Entry], checked within the same write transaction.
+    ///
+    /// Returns `None` without deleting anything if the `condition` doesn't hold,
+    /// or the entry is immutable, see [EntryWriter::set_immutable].
+    pub fn delete_entry_if(
+        &mut self,
+        public_key: &PublicKey,
+        path: &str,
+        condition: impl FnOnce(&Entry) -> bool,
+    ) -> anyhow::Result<Option<bool>> {
         let mut wtxn = self.env.write_txn()?;
 
         let key = format!("{public_key}{path}");
@@ -50,46 +77,293 @@ impl DB {
         let deleted = if let Some(bytes) = self.tables.entries.get(&wtxn, &key)? {
             let entry = Entry::deserialize(bytes)?;
 
-            let mut deleted_chunks = false;
+            if !condition(&entry) || self.tables.immutable_entries.get(&wtxn, &key)?.is_some() {
+                return Ok(None);
+            }
+
+            self.delete_entry_in(&mut wtxn, &key, path, &entry)?
+        } else {
+            false
+        };
+
+        wtxn.commit()?;
+
+        Ok(Some(deleted))
+    }
+
+    /// Delete the `entry` stored at `key` (`<public_key><path>`) within `wtxn`,
+    /// with its chunks, and log its deletion.
+    pub(crate) fn delete_entry_in(
+        &self,
+        wtxn: &mut RwTxn,
+        key: &str,
+        path: &str,
+        entry: &Entry,
+    ) -> anyhow::Result<bool> {
+        let mut deleted_chunks = false;
+
+        {
+            let mut iter = self
+                .tables
+                .blobs
+                .prefix_iter_mut(wtxn, &entry.timestamp.to_bytes())?;
+
+            while iter.next().is_some() {
+                unsafe {
+                    deleted_chunks = iter.del_current()?;
+                }
+            }
+        }
+
+        let deleted_entry = self.tables.entries.delete(wtxn, key)?;
+
+        self.tables.expirations.delete(wtxn, key)?;
+        self.tables.content_encodings.delete(wtxn, key)?;
+
+        self.log_user_event(wtxn, key, None)?;
+
+        // create DELETE event
+        if path.starts_with("/pub/") {
+            let url = format!("pubky://{key}");
+
+            let event = Event::delete(&url);
+            let value = event.serialize();
+
+            let key = Timestamp::now().to_string();
+
+            self.tables.events.put(wtxn, &key, &value)?;
+
+            // TODO: delete events older than a threshold.
+            // TODO: move to events.rs
+        }
+
+        Ok(deleted_entry && deleted_chunks)
+    }
+
+    /// Move all entries of an author from one path prefix to another,
+    /// within a single write transaction.
+    ///
+    /// Both prefixes have to start and end with a forward slash `/`, and not overlap.
+    /// Entries already at the destination are overwritten.
+    ///
+    /// Returns the number of moved entries, or `None` without moving anything
+    /// if any of the moved or overwritten entries is immutable.
+    pub fn move_prefix(
+        &mut self,
+        public_key: &PublicKey,
+        from: &str,
+        to: &str,
+    ) -> anyhow::Result<Option<u64>> {
+        let mut wtxn = self.env.write_txn()?;
 
+        let from_prefix = format!("{public_key}{from}");
+        let to_prefix = format!("{public_key}{to}");
+
+        let entries = self
+            .tables
+            .entries
+            .prefix_iter(&wtxn, &from_prefix)?
+            .map(|result| result.map(|(key, bytes)| (key.to_string(), bytes.to_vec())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (key, _) in &entries {
+            let new_key = format!("{to_prefix}{}", &key[from_prefix.len()..]);
+
+            if self.tables.immutable_entries.get(&wtxn, key)?.is_some()
+                || self
+                    .tables
+                    .immutable_entries
+                    .get(&wtxn, &new_key)?
+                    .is_some()
             {
+                return Ok(None);
+            }
+        }
+
+        for (key, bytes) in &entries {
+            let new_key = format!("{to_prefix}{}", &key[from_prefix.len()..]);
+
+            // Delete the chunks of an overwritten entry.
+            if let Some(existing) = self.tables.entries.get(&wtxn, &new_key)? {
+                let existing = Entry::deserialize(existing)?;
+
                 let mut iter = self
                     .tables
                     .blobs
-                    .prefix_iter_mut(&mut wtxn, &entry.timestamp.to_bytes())?;
+                    .prefix_iter_mut(&mut wtxn, &existing.timestamp.to_bytes())?;
 
                 while iter.next().is_some() {
                     unsafe {
-                        deleted_chunks = iter.del_current()?;
+                        iter.del_current()?;
                     }
                 }
             }
 
-            let deleted_entry = self.tables.entries.delete(&mut wtxn, &key)?;
+            self.tables.entries.delete(&mut wtxn, key)?;
+            self.tables.entries.put(&mut wtxn, &new_key, bytes)?;
 
-            // create DELETE event
-            if path.starts_with("/pub/") {
-                let url = format!("pubky://{key}");
+            match self.tables.expirations.get(&wtxn, key)? {
+                Some(expires_at) => {
+                    self.tables.expirations.delete(&mut wtxn, key)?;
+                    self.tables
+                        .expirations
+                        .put(&mut wtxn, &new_key, &expires_at)?;
+                }
+                None => {
+                    self.tables.expirations.delete(&mut wtxn, &new_key)?;
+                }
+            }
+
+            match self
+                .tables
+                .content_encodings
+                .get(&wtxn, key)?
+                .map(|encoding| encoding.to_string())
+            {
+                Some(encoding) => {
+                    self.tables.content_encodings.delete(&mut wtxn, key)?;
+                    self.tables
+                        .content_encodings
+                        .put(&mut wtxn, &new_key, &encoding)?;
+                }
+                None => {
+                    self.tables.content_encodings.delete(&mut wtxn, &new_key)?;
+                }
+            }
 
-                let event = Event::delete(&url);
-                let value = event.serialize();
+            self.log_user_event(&mut wtxn, key, None)?;
+            self.log_user_event(
+                &mut wtxn,
+                &new_key,
+                Some(*Entry::deserialize(bytes)?.content_hash()),
+            )?;
+
+            // create DELETE and PUT events
+            if from.starts_with("/pub/") {
+                let event = Event::delete(&format!("pubky://{key}"));
+
+                self.tables.events.put(
+                    &mut wtxn,
+                    &Timestamp::now().to_string(),
+                    &event.serialize(),
+                )?;
+            }
+            if to.starts_with("/pub/") {
+                let event = Event::put(&format!("pubky://{new_key}"));
+
+                self.tables.events.put(
+                    &mut wtxn,
+                    &Timestamp::now().to_string(),
+                    &event.serialize(),
+                )?;
+            }
+        }
 
-                let key = Timestamp::now().to_string();
+        wtxn.commit()?;
 
-                self.tables.events.put(&mut wtxn, &key, &value)?;
+        Ok(Some(entries.len() as u64))
+    }
 
-                // TODO: delete events older than a threshold.
-                // TODO: move to events.rs
+    /// Add `by` to the decimal integer stored in the entry at `path` (`0` if there
+    /// is none), and store the sum as a `text/plain` entry, within a single write
+    /// transaction, so concurrent increments are never lost.
+    ///
+    /// Returns the sum, or `None` without writing anything if the current entry
+    /// is immutable, isn't a decimal integer, or the sum would overflow.
+    pub fn increment_entry(
+        &mut self,
+        public_key: &PublicKey,
+        path: &str,
+        by: i64,
+    ) -> anyhow::Result<Option<i64>> {
+        let mut wtxn = self.env.write_txn()?;
+
+        let key = format!("{public_key}{path}");
+
+        if self.tables.immutable_entries.get(&wtxn, &key)?.is_some()
+            || self.tables.content_encodings.get(&wtxn, &key)?.is_some()
+        {
+            return Ok(None);
+        }
+
+        let current = match self.get_entry(&wtxn, public_key, path)? {
+            Some(entry) => {
+                let mut content = vec![];
+                for chunk in self.read_entry_content(&wtxn, &entry)? {
+                    content.extend_from_slice(chunk?);
+                }
+
+                match std::str::from_utf8(&content)
+                    .ok()
+                    .and_then(|content| content.trim().parse::<i64>().ok())
+                {
+                    Some(current) => current,
+                    None => return Ok(None),
+                }
             }
+            // Expired counters start over.
+            None => 0,
+        };
 
-            deleted_entry && deleted_chunks
-        } else {
-            false
+        let Some(value) = current.checked_add(by) else {
+            return Ok(None);
         };
 
+        // Delete the chunks of the current entry, even if expired.
+        if let Some(existing) = self.tables.entries.get(&wtxn, &key)? {
+            let existing = Entry::deserialize(existing)?;
+
+            let mut iter = self
+                .tables
+                .blobs
+                .prefix_iter_mut(&mut wtxn, &existing.timestamp.to_bytes())?;
+
+            while iter.next().is_some() {
+                unsafe {
+                    iter.del_current()?;
+                }
+            }
+        }
+
+        let content = value.to_string();
+        let content_hash = hash(content.as_bytes());
+        let timestamp = Timestamp::now();
+
+        let mut chunk_key = [0; 12];
+        chunk_key[0..8].copy_from_slice(&timestamp.to_bytes());
+
+        self.tables
+            .blobs
+            .put(&mut wtxn, &chunk_key, content.as_bytes())?;
+
+        let mut entry = Entry::new();
+        entry
+            .set_timestamp(&timestamp)
+            .set_content_hash(content_hash)
+            .set_content_length(content.len())
+            .set_content_type("text/plain");
+
+        self.tables
+            .entries
+            .put(&mut wtxn, &key, &entry.serialize())?;
+
+        if self.is_expired(&wtxn, public_key, path)? {
+            self.tables.expirations.delete(&mut wtxn, &key)?;
+        }
+
+        self.log_user_event(&mut wtxn, &key, Some(content_hash))?;
+
+        if path.starts_with("/pub/") {
+            let event = Event::put(&format!("pubky://{key}"));
+
+            self.tables
+                .events
+                .put(&mut wtxn, &timestamp.to_string(), &event.serialize())?;
+        }
+
         wtxn.commit()?;
 
-        Ok(deleted)
+        Ok(Some(value))
     }
 
     pub fn get_entry(
@@ -100,6 +374,11 @@ impl DB {
     ) -> anyhow::Result<Option<Entry>> {
         let key = format!("{public_key}{path}");
 
+        // Expired entries are absent, even before they are garbage collected.
+        if self.is_expired(txn, public_key, path)? {
+            return Ok(None);
+        }
+
         if let Some(bytes) = self.tables.entries.get(txn, &key)? {
             return Ok(Some(Entry::deserialize(bytes)?));
         }
@@ -107,6 +386,49 @@ impl DB {
         Ok(None)
     }
 
+    /// Returns the total content length of all entries of a user.
+    pub fn storage_used(&self, txn: &RoTxn, public_key: &PublicKey) -> anyhow::Result<u64> {
+        let prefix = format!("{public_key}/");
+
+        let mut used = 0;
+
+        for result in self.tables.entries.prefix_iter(txn, &prefix)? {
+            let (_, bytes) = result?;
+
+            used += Entry::deserialize(bytes)?.content_length() as u64;
+        }
+
+        Ok(used)
+    }
+
+    /// Returns the total content length of entries in each top-level
+    /// directory in `/pub/`, for example `/pub/pubky.app/`.
+    ///
+    /// Files directly in `/pub/` are counted under `/pub/` itself.
+    pub fn storage_breakdown(
+        &self,
+        txn: &RoTxn,
+        public_key: &PublicKey,
+    ) -> anyhow::Result<Vec<(String, u64)>> {
+        let prefix = format!("{public_key}/pub/");
+
+        let mut breakdown = BTreeMap::<String, u64>::new();
+
+        for result in self.tables.entries.prefix_iter(txn, &prefix)? {
+            let (key, bytes) = result?;
+            let entry = Entry::deserialize(bytes)?;
+
+            let directory = match key[prefix.len()..].split_once('/') {
+                Some((directory, _)) => format!("/pub/{directory}/"),
+                None => "/pub/".to_string(),
+            };
+
+            *breakdown.entry(directory).or_default() += entry.content_length() as u64;
+        }
+
+        Ok(breakdown.into_iter().collect())
+    }
+
     pub fn contains_directory(&self, txn: &RoTxn, path: &str) -> anyhow::Result<bool> {
         Ok(self.tables.entries.get_greater_than(txn, path)?.is_some())
     }
@@ -114,6 +436,21 @@ impl DB {
     /// Return a list of pubky urls.
     ///
     /// - limit defaults to [crate::config::DEFAULT_LIST_LIMIT] and capped by [crate::config::DEFAULT_MAX_LIST_LIMIT]
+    ///
+    /// In a flat listing, the cursor is a file path, and the listing continues
+    /// after that file.
+    ///
+    /// In a `shallow` listing, the cursor is the last returned item, either a
+    /// file or a directory prefix (ending with `/`). The listing continues
+    /// after that file or after _everything_ within that directory, so that
+    /// paginating never returns the same directory twice. A nested cursor, like
+    /// `dir/file`, continues within `dir/`, so `dir/` is returned again.
+    ///
+    /// Each page is read from its own transaction, so paginating doesn't reflect
+    /// one snapshot. Since the cursor is a key rather than an offset, entries
+    /// present for the whole pagination are listed exactly once, in order, while
+    /// entries written or deleted meanwhile are listed if, and only if, they sort
+    /// after the cursor at that time.
     pub fn list(
         &self,
         txn: &RoTxn,
@@ -152,39 +489,126 @@ impl DB {
             .unwrap_or(next_threshold(path, "", false, reverse, shallow));
 
         for _ in 0..limit {
-            if let Some((key, _)) = if reverse {
-                self.tables.entries.get_lower_than(txn, &threshold)?
-            } else {
-                self.tables.entries.get_greater_than(txn, &threshold)?
-            } {
-                if !key.starts_with(path) {
-                    break;
-                }
+            let next = match (reverse, threshold.inclusive) {
+                (false, false) => self.tables.entries.get_greater_than(txn, &threshold.key)?,
+                (false, true) => self
+                    .tables
+                    .entries
+                    .get_greater_than_or_equal_to(txn, &threshold.key)?,
+                (true, false) => self.tables.entries.get_lower_than(txn, &threshold.key)?,
+                (true, true) => self
+                    .tables
+                    .entries
+                    .get_lower_than_or_equal_to(txn, &threshold.key)?,
+            };
+
+            let Some((key, _)) = next else {
+                break;
+            };
+
+            if !key.starts_with(path) {
+                break;
+            }
 
-                if shallow {
-                    let mut split = key[path.len()..].split('/');
-                    let file_or_directory = split.next().expect("should not be reachable");
+            if shallow {
+                let mut split = key[path.len()..].split('/');
+                let file_or_directory = split.next().expect("should not be reachable");
 
-                    let is_directory = split.next().is_some();
+                let is_directory = split.next().is_some();
 
-                    threshold =
-                        next_threshold(path, file_or_directory, is_directory, reverse, shallow);
+                results.push(format!(
+                    "pubky://{path}{file_or_directory}{}",
+                    if is_directory { "/" } else { "" }
+                ));
 
-                    results.push(format!(
-                        "pubky://{path}{file_or_directory}{}",
-                        if is_directory { "/" } else { "" }
-                    ));
+                threshold = next_threshold(
+                    path,
+                    &format!("{file_or_directory}{}", if is_directory { "/" } else { "" }),
+                    is_directory,
+                    reverse,
+                    shallow,
+                );
+            } else {
+                threshold = Threshold {
+                    key: key.to_string(),
+                    inclusive: false,
+                };
+                results.push(format!("pubky://{}", key))
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Return the pubky urls of the entries within `path` modified after `since`,
+    /// with their modification [Timestamp]s, in a flat listing.
+    ///
+    /// - limit defaults to [crate::config::DEFAULT_LIST_LIMIT] and capped by [crate::config::DEFAULT_MAX_LIST_LIMIT]
+    ///
+    /// The cursor is a file path (or url), and the listing continues after that file.
+    pub fn list_since(
+        &self,
+        txn: &RoTxn,
+        path: &str,
+        since: &Timestamp,
+        limit: Option<u16>,
+        cursor: Option<String>,
+    ) -> anyhow::Result<Vec<(String, Timestamp)>> {
+        let limit = limit
+            .unwrap_or(self.config().default_list_limit)
+            .min(self.config().max_list_limit);
+
+        let threshold = match &cursor {
+            Some(cursor) => {
+                let file = if cursor.starts_with("pubky://") {
+                    cursor.split(path).last().expect("should not be reachable")
                 } else {
-                    threshold = key.to_string();
-                    results.push(format!("pubky://{}", key))
-                }
-            };
+                    cursor.trim_start_matches('/')
+                };
+
+                next_threshold(path, file, false, false, false)
+            }
+            None => next_threshold(path, "", false, false, false),
+        };
+
+        let start = if threshold.inclusive {
+            Bound::Included(threshold.key.as_str())
+        } else {
+            Bound::Excluded(threshold.key.as_str())
+        };
+
+        let mut results = Vec::new();
+
+        for result in self.tables.entries.range(txn, &(start, Bound::Unbounded))? {
+            if results.len() >= limit as usize {
+                break;
+            }
+
+            let (key, bytes) = result?;
+
+            if !key.starts_with(path) {
+                break;
+            }
+
+            let entry = Entry::deserialize(bytes)?;
+
+            if entry.timestamp().as_u64() > since.as_u64() {
+                results.push((format!("pubky://{key}"), *entry.timestamp()));
+            }
         }
 
         Ok(results)
     }
 }
 
+/// The key to continue a listing from.
+#[derive(Debug)]
+struct Threshold {
+    key: String,
+    /// Whether an entry at exactly [Threshold::key] should be listed.
+    inclusive: bool,
+}
+
 /// Calculate the next threshold
 #[instrument]
 fn next_threshold(
@@ -193,29 +617,53 @@ fn next_threshold(
     is_directory: bool,
     reverse: bool,
     shallow: bool,
-) -> String {
-    format!(
-        "{path}{file_or_directory}{}",
-        if file_or_directory.is_empty() {
-            // No file_or_directory, early return
-            if reverse {
-                // `path/to/dir/\x7f` to catch all paths than `path/to/dir/`
-                "\x7f"
-            } else {
-                ""
+) -> Threshold {
+    if file_or_directory.is_empty() {
+        return if reverse {
+            // Everything in `path/to/dir/` is lower than `path/to/dir0`
+            Threshold {
+                key: prefix_successor(path),
+                inclusive: false,
             }
-        } else if shallow & is_directory {
-            if reverse {
-                // threshold = `path/to/dir\x2e`, since `\x2e` is lower   than `/`
-                "\x2e"
-            } else {
-                //threshold = `path/to/dir\x7f`, since `\x7f` is greater than `/`
-                "\x7f"
+        } else {
+            Threshold {
+                key: path.to_string(),
+                inclusive: false,
+            }
+        };
+    }
+
+    let key = format!("{path}{file_or_directory}");
+
+    if shallow && is_directory {
+        if reverse {
+            // Everything lower than `path/to/dir/` is outside of that directory.
+            Threshold {
+                key,
+                inclusive: false,
             }
         } else {
-            ""
+            // `path/to/dir0` is the first key after everything in `path/to/dir/`
+            Threshold {
+                key: prefix_successor(&key),
+                inclusive: true,
+            }
         }
-    )
+    } else {
+        Threshold {
+            key,
+            inclusive: false,
+        }
+    }
+}
+
+/// The smallest key greater than all keys starting with a `prefix` ending with `/`.
+fn prefix_successor(prefix: &str) -> String {
+    match prefix.strip_suffix('/') {
+        // `0` is the character right after `/`.
+        Some(prefix) => format!("{prefix}0"),
+        None => format!("{prefix}\u{10FFFF}"),
+    }
 }
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -281,6 +729,11 @@ impl Entry {
         self
     }
 
+    pub fn set_content_type(&mut self, content_type: &str) -> &mut Self {
+        content_type.clone_into(&mut self.content_type);
+        self
+    }
+
     // === Getters ===
 
     pub fn timestamp(&self) -> &Timestamp {
@@ -291,6 +744,12 @@ impl Entry {
         &self.content_hash.0
     }
 
+    /// Quoted `ETag` of this entry, derived from its content hash,
+    /// shared by all responses (`GET`, `HEAD`, `PUT`) and conditional requests.
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.content_hash())
+    }
+
     pub fn content_length(&self) -> usize {
         self.content_length
     }
@@ -330,6 +789,10 @@ pub struct EntryWriter<'db> {
     entry_key: String,
     timestamp: Timestamp,
     is_public: bool,
+    content_type: String,
+    content_encoding: Option<String>,
+    expires_at: Option<Timestamp>,
+    immutable: bool,
 }
 
 impl<'db> EntryWriter<'db> {
@@ -352,9 +815,49 @@ impl<'db> EntryWriter<'db> {
             entry_key,
             timestamp,
             is_public: path.starts_with("/pub/"),
+            content_type: String::new(),
+            content_encoding: None,
+            expires_at: None,
+            immutable: false,
         })
     }
 
+    /// Set the content type of the [Entry] to be committed.
+    pub fn set_content_type(&mut self, content_type: &str) -> &mut Self {
+        content_type.clone_into(&mut self.content_type);
+
+        self
+    }
+
+    /// Set the content coding (like `br`) the content is written with,
+    /// to be served as is to clients accepting it.
+    pub fn set_content_encoding(&mut self, content_encoding: &str) -> &mut Self {
+        self.content_encoding = Some(content_encoding.to_string());
+
+        self
+    }
+
+    /// Set a time after which the [Entry] to be committed is treated as absent,
+    /// and eventually deleted by [DB::delete_expired_entries].
+    pub fn set_expires_at(&mut self, expires_at: Timestamp) -> &mut Self {
+        self.expires_at = Some(expires_at);
+
+        self
+    }
+
+    /// Make the [Entry] to be committed immutable, so it can't be overwritten,
+    /// deleted, or moved afterwards.
+    pub fn set_immutable(&mut self) -> &mut Self {
+        self.immutable = true;
+
+        self
+    }
+
+    /// Returns the hash of the content written so far.
+    pub fn content_hash(&self) -> Hash {
+        self.hasher.finalize()
+    }
+
     /// Same ase [EntryWriter::write_all] but returns a Result of a mutable reference of itself
     /// to enable chaining with [Self::commit].
     pub fn update(&mut self, chunk: &[u8]) -> Result<&mut Self, std::io::Error> {
@@ -365,12 +868,78 @@ impl<'db> EntryWriter<'db> {
 
     /// Commit blob from the filesystem buffer to LMDB,
     /// write the [Entry], and commit the write transaction.
+    ///
+    /// Fails if the current [Entry] at the same path is immutable.
     pub fn commit(&self) -> anyhow::Result<Entry> {
+        self.commit_if(|_| true)?.ok_or(anyhow::anyhow!(
+            "Can't overwrite immutable entry {}",
+            self.entry_key
+        ))
+    }
+
+    /// Same as [Self::commit], but only if the `condition` holds for the current
+    /// [Entry] at the same path (if any), checked within the same write transaction.
+    ///
+    /// Returns `None` without writing anything if the `condition` doesn't hold,
+    /// or the current [Entry] is immutable.
+    pub fn commit_if(
+        &self,
+        condition: impl FnOnce(Option<&Entry>) -> bool,
+    ) -> anyhow::Result<Option<Entry>> {
+        let mut wtxn = self.db.env.write_txn()?;
+
+        let entry = self.commit_in(&mut wtxn, condition)?;
+
+        match entry {
+            Some(_) => wtxn.commit()?,
+            None => wtxn.abort(),
+        }
+
+        Ok(entry)
+    }
+
+    /// Same as [Self::commit_if], but within `wtxn`, for example to write several
+    /// entries atomically, leaving committing it to the caller.
+    ///
+    /// The buffer is removed either way, so this writer can't be committed again.
+    pub fn commit_in(
+        &self,
+        wtxn: &mut RwTxn,
+        condition: impl FnOnce(Option<&Entry>) -> bool,
+    ) -> anyhow::Result<Option<Entry>> {
         let hash = self.hasher.finalize();
 
         let mut buffer = File::open(&self.buffer_path)?;
 
-        let mut wtxn = self.db.env.write_txn()?;
+        let expired = self
+            .db
+            .tables
+            .expirations
+            .get(wtxn, &self.entry_key)?
+            .is_some_and(|expires_at| expires_at <= Timestamp::now().as_u64());
+
+        let current = self
+            .db
+            .tables
+            .entries
+            .get(wtxn, &self.entry_key)?
+            .map(Entry::deserialize)
+            .transpose()?
+            .filter(|_| !expired);
+
+        let immutable = current.is_some()
+            && self
+                .db
+                .tables
+                .immutable_entries
+                .get(wtxn, &self.entry_key)?
+                .is_some();
+
+        if immutable || !condition(current.as_ref()) {
+            std::fs::remove_file(&self.buffer_path)?;
+
+            return Ok(None);
+        }
 
         let mut chunk_key = [0; 12];
         chunk_key[0..8].copy_from_slice(&self.timestamp.to_bytes());
@@ -391,7 +960,7 @@ impl<'db> EntryWriter<'db> {
             self.db
                 .tables
                 .blobs
-                .put(&mut wtxn, &chunk_key, &chunk[..bytes_read])?;
+                .put(wtxn, &chunk_key, &chunk[..bytes_read])?;
 
             chunk_index += 1;
         }
@@ -404,10 +973,53 @@ impl<'db> EntryWriter<'db> {
         let length = buffer.metadata()?.len();
         entry.set_content_length(length as usize);
 
+        entry.set_content_type(&self.content_type);
+
         self.db
             .tables
             .entries
-            .put(&mut wtxn, &self.entry_key, &entry.serialize())?;
+            .put(wtxn, &self.entry_key, &entry.serialize())?;
+
+        match self.expires_at {
+            Some(expires_at) => {
+                self.db
+                    .tables
+                    .expirations
+                    .put(wtxn, &self.entry_key, &expires_at.as_u64())?
+            }
+            None => {
+                self.db.tables.expirations.delete(wtxn, &self.entry_key)?;
+            }
+        }
+
+        match &self.content_encoding {
+            Some(content_encoding) => {
+                self.db
+                    .tables
+                    .content_encodings
+                    .put(wtxn, &self.entry_key, content_encoding)?
+            }
+            None => {
+                self.db
+                    .tables
+                    .content_encodings
+                    .delete(wtxn, &self.entry_key)?;
+            }
+        }
+
+        if self.immutable {
+            self.db
+                .tables
+                .immutable_entries
+                .put(wtxn, &self.entry_key, &())?;
+        } else {
+            self.db
+                .tables
+                .immutable_entries
+                .delete(wtxn, &self.entry_key)?;
+        }
+
+        self.db.log_user_event(wtxn, &self.entry_key, Some(hash))?;
 
         // Write a public [Event].
         if self.is_public {
@@ -417,17 +1029,15 @@ impl<'db> EntryWriter<'db> {
 
             let key = entry.timestamp.to_string();
 
-            self.db.tables.events.put(&mut wtxn, &key, &value)?;
+            self.db.tables.events.put(wtxn, &key, &value)?;
 
             // TODO: delete events older than a threshold.
             // TODO: move to events.rs
         }
 
-        wtxn.commit()?;
-
         std::fs::remove_file(&self.buffer_path)?;
 
-        Ok(entry)
+        Ok(Some(entry))
     }
 }
 
@@ -450,10 +1060,13 @@ impl<'db> std::io::Write for EntryWriter<'db> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
     use bytes::Bytes;
     use pkarr::Keypair;
+    use pubky_common::timestamp::Timestamp;
 
-    use super::DB;
+    use super::{Entry, DB};
 
     #[tokio::test]
     async fn entries() -> anyhow::Result<()> {
@@ -541,4 +1154,264 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn entries_height() -> anyhow::Result<()> {
+        let db = DB::test();
+
+        let public_key = Keypair::random().public_key();
+
+        assert_eq!(db.entries_height(&db.env.read_txn()?)?, 0);
+
+        let mut wtxn = db.env.write_txn()?;
+        for i in 0..10_000 {
+            let key = format!("{public_key}/pub/example.com/{i:05}");
+            db.tables
+                .entries
+                .put(&mut wtxn, &key, &Entry::new().serialize())?;
+        }
+        wtxn.commit()?;
+
+        let height = db.entries_height(&db.env.read_txn()?)?;
+
+        // ~10k small entries fit in a few hundred leaf pages.
+        assert!((2..=4).contains(&height), "height: {height}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn shallow_list_pagination() -> anyhow::Result<()> {
+        let db = DB::test();
+
+        let public_key = Keypair::random().public_key();
+        let path = format!("{public_key}/pub/");
+
+        let mut expected = BTreeSet::new();
+
+        let mut wtxn = db.env.write_txn()?;
+        let mut put = |name: &str| {
+            db.tables.entries.put(
+                &mut wtxn,
+                &format!("{path}{name}"),
+                &Entry::new().serialize(),
+            )
+        };
+
+        for i in 0..50 {
+            for file in ["a", "b", "sub/c"] {
+                put(&format!("d{i:02}/{file}"))?;
+            }
+            expected.insert(format!("pubky://{path}d{i:02}/"));
+        }
+        // Sorted right before and right after everything in `d05/`.
+        for name in ["d05", "d05-x", "d05.x/a", "d050", "d050/a"] {
+            put(name)?;
+        }
+        for item in ["d05", "d05-x", "d05.x/", "d050", "d050/"] {
+            expected.insert(format!("pubky://{path}{item}"));
+        }
+        wtxn.commit()?;
+
+        let rtxn = db.env.read_txn()?;
+
+        for reverse in [false, true] {
+            let mut listed = vec![];
+            let mut cursor = None;
+
+            loop {
+                let page = db.list(&rtxn, &path, reverse, Some(7), cursor, true)?;
+
+                let Some(last) = page.last() else {
+                    break;
+                };
+                cursor = Some(last.clone());
+
+                listed.extend(page);
+            }
+
+            let mut sorted = expected.iter().cloned().collect::<Vec<_>>();
+            if reverse {
+                sorted.reverse();
+            }
+
+            assert_eq!(listed, sorted, "reverse: {reverse}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_pagination_with_concurrent_writes() -> anyhow::Result<()> {
+        let db = DB::test();
+
+        let public_key = Keypair::random().public_key();
+
+        for reverse in [false, true] {
+            let path = format!("{public_key}/pub/{reverse}/");
+
+            let put = |i: u32| -> anyhow::Result<()> {
+                let mut wtxn = db.env.write_txn()?;
+                db.tables.entries.put(
+                    &mut wtxn,
+                    &format!("{path}{i:02}"),
+                    &Entry::new().serialize(),
+                )?;
+                Ok(wtxn.commit()?)
+            };
+
+            let initial = (0..20).map(|i| i * 2).collect::<BTreeSet<_>>();
+            for i in &initial {
+                put(*i)?;
+            }
+
+            let mut listed = vec![];
+            let mut behind = BTreeSet::new();
+            let mut ahead = BTreeSet::new();
+            let mut cursor = None;
+
+            loop {
+                let rtxn = db.env.read_txn()?;
+                let page = db.list(&rtxn, &path, reverse, Some(3), cursor, false)?;
+                drop(rtxn);
+
+                let Some(last) = page.last().cloned() else {
+                    break;
+                };
+                listed.extend(page);
+
+                // Between pages, write a new entry that was already passed, and
+                // one that is still ahead of the cursor.
+                let last_index = last.rsplit('/').next().unwrap().parse::<u32>()?;
+                let (passed, next) = if reverse {
+                    (last_index.checked_add(1), last_index.checked_sub(1))
+                } else {
+                    (last_index.checked_sub(1), last_index.checked_add(1))
+                };
+                for (i, is_ahead) in [(passed, false), (next, true)] {
+                    if let Some(i) = i.filter(|i| *i < 40 && i % 2 == 1) {
+                        if !behind.contains(&i) && !ahead.contains(&i) {
+                            put(i)?;
+                            if is_ahead {
+                                ahead.insert(i);
+                            } else {
+                                behind.insert(i);
+                            }
+                        }
+                    }
+                }
+
+                cursor = Some(last);
+            }
+
+            let listed = listed
+                .iter()
+                .map(|url| url.rsplit('/').next().unwrap().parse::<u32>())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut expected = initial.union(&ahead).copied().collect::<Vec<_>>();
+            if reverse {
+                expected.reverse();
+            }
+
+            assert!(!behind.is_empty() && !ahead.is_empty());
+            // In order, without duplicates, including everything present from
+            // the start and written ahead of the cursor, but nothing written
+            // behind it.
+            assert_eq!(listed, expected, "reverse: {reverse}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn expired_entries() -> anyhow::Result<()> {
+        let mut db = DB::test();
+
+        let public_key = Keypair::random().public_key();
+
+        db.write_entry(&public_key, "/pub/expired")?
+            .set_expires_at(Timestamp::now())
+            .update(&[1, 2, 3])?
+            .commit()?;
+        db.write_entry(&public_key, "/pub/fresh")?
+            .set_expires_at(Timestamp::now() + 60_000_000)
+            .update(&[4, 5, 6])?
+            .commit()?;
+
+        // Left over for an entry that no longer exists.
+        {
+            let mut wtxn = db.env.write_txn()?;
+            db.tables.expirations.put(
+                &mut wtxn,
+                &format!("{public_key}/pub/missing"),
+                &(Timestamp::now() + 60_000_000).as_u64(),
+            )?;
+            wtxn.commit()?;
+        }
+
+        {
+            let rtxn = db.env.read_txn()?;
+
+            assert!(db.get_entry(&rtxn, &public_key, "/pub/expired")?.is_none());
+            assert!(db.get_entry(&rtxn, &public_key, "/pub/fresh")?.is_some());
+        }
+
+        assert_eq!(db.delete_expired_entries()?, 1);
+
+        let rtxn = db.env.read_txn()?;
+
+        assert!(db
+            .tables
+            .entries
+            .get(&rtxn, &format!("{public_key}/pub/expired"))?
+            .is_none());
+        assert!(db
+            .tables
+            .expirations
+            .get(&rtxn, &format!("{public_key}/pub/missing"))?
+            .is_none());
+        assert_eq!(db.tables.expirations.len(&rtxn)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn increment_entry() -> anyhow::Result<()> {
+        let mut db = DB::test();
+
+        let public_key = Keypair::random().public_key();
+
+        assert_eq!(db.increment_entry(&public_key, "/pub/counter", 5)?, Some(5));
+        assert_eq!(
+            db.increment_entry(&public_key, "/pub/counter", -7)?,
+            Some(-2)
+        );
+
+        {
+            let rtxn = db.env.read_txn()?;
+
+            let entry = db.get_entry(&rtxn, &public_key, "/pub/counter")?.unwrap();
+            let content = entry.read_content(&db, &rtxn)?.next().unwrap()?;
+
+            assert_eq!(content, b"-2");
+            assert_eq!(entry.content_type(), "text/plain");
+        }
+
+        db.write_entry(&public_key, "/pub/text")?
+            .update(b"hello")?
+            .commit()?;
+        assert_eq!(db.increment_entry(&public_key, "/pub/text", 1)?, None);
+
+        db.write_entry(&public_key, "/pub/immutable")?
+            .set_immutable()
+            .update(b"1")?
+            .commit()?;
+        assert_eq!(db.increment_entry(&public_key, "/pub/immutable", 1)?, None);
+
+        db.increment_entry(&public_key, "/pub/max", i64::MAX)?;
+        assert_eq!(db.increment_entry(&public_key, "/pub/max", 1)?, None);
+
+        Ok(())
+    }
 }
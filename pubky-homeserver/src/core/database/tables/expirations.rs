@@ -0,0 +1,83 @@
+//! Expiry times of entries written with a TTL.
+
+use heed::{
+    byteorder::BigEndian,
+    types::{Str, U64},
+    Database, RoTxn,
+};
+use pkarr::PublicKey;
+use pubky_common::timestamp::Timestamp;
+
+use crate::core::database::DB;
+
+use super::entries::Entry;
+
+/// full_path(pubky/*path) => Expiry [Timestamp] in microseconds.
+pub type ExpirationsTable = Database<Str, U64<BigEndian>>;
+
+pub const EXPIRATIONS_TABLE: &str = "expirations";
+
+impl DB {
+    /// Returns `true` if the entry at this path was written with a TTL that already passed.
+    pub fn is_expired(
+        &self,
+        txn: &RoTxn,
+        public_key: &PublicKey,
+        path: &str,
+    ) -> anyhow::Result<bool> {
+        let key = format!("{public_key}{path}");
+
+        Ok(self
+            .tables
+            .expirations
+            .get(txn, &key)?
+            .is_some_and(|expires_at| expires_at <= Timestamp::now().as_u64()))
+    }
+
+    /// Delete all expired entries, and the expiry times left for entries that no longer
+    /// exist, returning the number of deleted entries.
+    ///
+    /// Expiry times are checked within the same write transaction as the deletions,
+    /// so entries rewritten meanwhile are kept.
+    pub fn delete_expired_entries(&mut self) -> anyhow::Result<usize> {
+        let now = Timestamp::now().as_u64();
+
+        let mut wtxn = self.env.write_txn()?;
+
+        let expirations = self
+            .tables
+            .expirations
+            .iter(&wtxn)?
+            .map(|result| result.map(|(key, expires_at)| (key.to_string(), expires_at)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut deleted = 0;
+
+        for (key, expires_at) in expirations {
+            let Some(bytes) = self.tables.entries.get(&wtxn, &key)? else {
+                self.tables.expirations.delete(&mut wtxn, &key)?;
+
+                continue;
+            };
+
+            if expires_at > now || self.tables.immutable_entries.get(&wtxn, &key)?.is_some() {
+                continue;
+            }
+
+            let entry = Entry::deserialize(bytes)?;
+
+            // Keys are `<public_key>/<path>`, and public keys never contain a `/`.
+            let Some(index) = key.find('/') else {
+                continue;
+            };
+
+            if self.delete_entry_in(&mut wtxn, &key, &key[index..], &entry)? {
+                deleted += 1;
+            }
+        }
+
+        wtxn.commit()?;
+
+        Ok(deleted)
+    }
+}
@@ -0,0 +1,28 @@
+//! Entries written as immutable, that can't be overwritten, deleted or moved.
+
+use heed::{
+    types::{Str, Unit},
+    Database, RoTxn,
+};
+use pkarr::PublicKey;
+
+use crate::core::database::DB;
+
+/// full_path(pubky/*path) => ().
+pub type ImmutableEntriesTable = Database<Str, Unit>;
+
+pub const IMMUTABLE_ENTRIES_TABLE: &str = "immutable_entries";
+
+impl DB {
+    /// Returns `true` if the entry at this path was written as immutable.
+    pub fn is_immutable(
+        &self,
+        txn: &RoTxn,
+        public_key: &PublicKey,
+        path: &str,
+    ) -> anyhow::Result<bool> {
+        let key = format!("{public_key}{path}");
+
+        Ok(self.tables.immutable_entries.get(txn, &key)?.is_some())
+    }
+}
@@ -2,7 +2,9 @@ use heed::{
     types::{Bytes, Str},
     Database,
 };
-use pubky_common::session::Session;
+use postcard::{from_bytes, to_allocvec};
+use pubky_common::{capabilities::Capability, session::Session, timestamp::Timestamp};
+use serde::{Deserialize, Serialize};
 
 use crate::core::database::DB;
 
@@ -11,8 +13,54 @@ pub type SessionsTable = Database<Str, Bytes>;
 
 pub const SESSIONS_TABLE: &str = "sessions";
 
+/// session secret => SessionExpiry.
+pub type SessionExpirationsTable = Database<Str, Bytes>;
+
+pub const SESSION_EXPIRATIONS_TABLE: &str = "session_expirations";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+/// Expiry of a session, only tracked if [crate::core::CoreConfig::session_ttl] is set.
+pub struct SessionExpiry {
+    /// Timestamp in microseconds of when the session was created.
+    pub created_at: u64,
+    /// Timestamp in microseconds after which the session is no longer valid.
+    pub expires_at: u64,
+}
+
+/// Outcome of [DB::revoke_capability].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revocation {
+    /// The capabilities within the scope were removed, returning the updated session.
+    Revoked(Box<Session>),
+    /// This broader capability grants access to the scope, so the session was left
+    /// unchanged, as removing the narrower ones wouldn't revoke anything.
+    Covered(Capability),
+}
+
+impl SessionExpiry {
+    pub fn serialize(&self) -> Vec<u8> {
+        to_allocvec(self).expect("SessionExpiry::serialize")
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> core::result::Result<Self, postcard::Error> {
+        from_bytes(bytes)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Timestamp::now().as_u64()
+    }
+}
+
 impl DB {
+    /// Returns the session for this secret, or `None` if it doesn't exist or it expired.
     pub fn get_session(&self, session_secret: &str) -> anyhow::Result<Option<Session>> {
+        if self
+            .session_expiry(session_secret)?
+            .is_some_and(|expiry| expiry.is_expired())
+        {
+            return Ok(None);
+        }
+
         let rtxn = self.env.read_txn()?;
 
         let session = self
@@ -30,11 +78,144 @@ impl DB {
         Ok(None)
     }
 
+    /// Returns the expiry of a session, or `None` if it never expires.
+    pub fn session_expiry(&self, session_secret: &str) -> anyhow::Result<Option<SessionExpiry>> {
+        let rtxn = self.env.read_txn()?;
+
+        let expiry = self
+            .tables
+            .session_expirations
+            .get(&rtxn, session_secret)?
+            .map(SessionExpiry::deserialize)
+            .transpose()?;
+
+        rtxn.commit()?;
+
+        Ok(expiry)
+    }
+
+    /// Extend the expiry of a valid session by [crate::core::CoreConfig::session_ttl] from now,
+    /// capped by [crate::core::CoreConfig::max_session_lifetime] since its creation.
+    ///
+    /// Returns the new expiry, or `None` if the session never expires.
+    pub fn refresh_session(
+        &mut self,
+        session_secret: &str,
+    ) -> anyhow::Result<Option<SessionExpiry>> {
+        let ttl = match self.config().session_ttl {
+            Some(ttl) => ttl.as_micros() as u64,
+            None => return Ok(None),
+        };
+        let max_lifetime = self
+            .config()
+            .max_session_lifetime
+            .map(|lifetime| lifetime.as_micros() as u64);
+
+        let mut wtxn = self.env.write_txn()?;
+
+        let expiry = match self
+            .tables
+            .session_expirations
+            .get(&wtxn, session_secret)?
+            .map(SessionExpiry::deserialize)
+            .transpose()?
+        {
+            Some(expiry) => expiry,
+            None => {
+                wtxn.abort();
+
+                return Ok(None);
+            }
+        };
+
+        let mut expires_at = Timestamp::now().as_u64().saturating_add(ttl);
+
+        if let Some(max_lifetime) = max_lifetime {
+            expires_at = expires_at.min(expiry.created_at.saturating_add(max_lifetime));
+        }
+
+        let refreshed = SessionExpiry {
+            expires_at: expires_at.max(expiry.expires_at),
+            ..expiry
+        };
+
+        self.tables
+            .session_expirations
+            .put(&mut wtxn, session_secret, &refreshed.serialize())?;
+
+        wtxn.commit()?;
+
+        Ok(Some(refreshed))
+    }
+
+    /// Remove the capabilities within this `scope` (the ones whose scope starts with it)
+    /// from a valid session, unless a broader one still grants access to it.
+    ///
+    /// Returns `None` if the session doesn't exist or it expired.
+    pub fn revoke_capability(
+        &mut self,
+        session_secret: &str,
+        scope: &str,
+    ) -> anyhow::Result<Option<Revocation>> {
+        if self
+            .session_expiry(session_secret)?
+            .is_some_and(|expiry| expiry.is_expired())
+        {
+            return Ok(None);
+        }
+
+        let mut wtxn = self.env.write_txn()?;
+
+        let mut session = match self
+            .tables
+            .sessions
+            .get(&wtxn, session_secret)?
+            .map(Session::deserialize)
+            .transpose()?
+        {
+            Some(session) => session,
+            None => {
+                wtxn.abort();
+
+                return Ok(None);
+            }
+        };
+
+        let (revoked, capabilities): (Vec<_>, Vec<_>) = session
+            .capabilities()
+            .iter()
+            .cloned()
+            .partition(|capability| capability.scope.starts_with(scope));
+
+        if let Some(covering) = capabilities
+            .iter()
+            .find(|capability| scope.starts_with(&capability.scope))
+        {
+            wtxn.abort();
+
+            return Ok(Some(Revocation::Covered(covering.clone())));
+        }
+
+        if !revoked.is_empty() {
+            session.set_capabilities(capabilities);
+
+            self.tables
+                .sessions
+                .put(&mut wtxn, session_secret, &session.serialize())?;
+        }
+
+        wtxn.commit()?;
+
+        Ok(Some(Revocation::Revoked(Box::new(session))))
+    }
+
     pub fn delete_session(&mut self, secret: &str) -> anyhow::Result<bool> {
         let mut wtxn = self.env.write_txn()?;
 
         let deleted = self.tables.sessions.delete(&mut wtxn, secret)?;
 
+        self.tables.session_expirations.delete(&mut wtxn, secret)?;
+
         wtxn.commit()?;
 
         Ok(deleted)
@@ -0,0 +1,189 @@
+//! Per user log of written and deleted entries (public and private),
+//! readable only by the user, for auditing and syncing their own data.
+
+use std::ops::Bound;
+
+use heed::{
+    types::{Bytes, Str},
+    Database, RoTxn, RwTxn,
+};
+use pkarr::PublicKey;
+use postcard::{from_bytes, to_allocvec};
+use pubky_common::{crypto::Hash, timestamp::Timestamp};
+use serde::{Deserialize, Serialize};
+
+use crate::core::database::DB;
+
+/// `<pubky>:<timestamp base32>` => Encoded user event.
+pub type UserEventsTable = Database<Str, Bytes>;
+
+pub const USER_EVENTS_TABLE: &str = "user_events";
+
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum UserEvent {
+    /// An entry was written at a path, with the hash of its content.
+    Put(String, [u8; 32]),
+    /// An entry was deleted at a path.
+    Delete(String),
+}
+
+impl UserEvent {
+    pub fn serialize(&self) -> Vec<u8> {
+        to_allocvec(self).expect("UserEvent::serialize")
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> core::result::Result<Self, postcard::Error> {
+        from_bytes(bytes)
+    }
+
+    /// Formats the event as `PUT <content hash> <path>` or `DEL <path>`.
+    pub fn line(&self) -> String {
+        match self {
+            UserEvent::Put(path, content_hash) => {
+                format!("PUT {} {path}", Hash::from_bytes(*content_hash))
+            }
+            UserEvent::Delete(path) => format!("DEL {path}"),
+        }
+    }
+}
+
+impl DB {
+    /// Append an event to the log of the user owning `entry_key` (`<pubky>/<path>`),
+    /// writing an entry with `content_hash`, or deleting it if `None`.
+    ///
+    /// Also deletes the user's events older than
+    /// [crate::core::CoreConfig::user_event_log_max_age].
+    pub fn log_user_event(
+        &self,
+        wtxn: &mut RwTxn,
+        entry_key: &str,
+        content_hash: Option<Hash>,
+    ) -> anyhow::Result<()> {
+        // Public keys never contain a `/`.
+        let Some(index) = entry_key.find('/') else {
+            return Ok(());
+        };
+        let (public_key, path) = entry_key.split_at(index);
+
+        let event = match content_hash {
+            Some(content_hash) => UserEvent::Put(path.to_string(), *content_hash.as_bytes()),
+            None => UserEvent::Delete(path.to_string()),
+        };
+
+        let now = Timestamp::now();
+
+        self.tables
+            .user_events
+            .put(wtxn, &format!("{public_key}:{now}"), &event.serialize())?;
+
+        let max_age = self.config().user_event_log_max_age.as_micros() as u64;
+        let oldest = Timestamp::from(now.as_u64().saturating_sub(max_age));
+
+        let start = format!("{public_key}:");
+        let end = format!("{public_key}:{oldest}");
+
+        self.tables.user_events.delete_range(
+            wtxn,
+            &(
+                Bound::Included(start.as_str()),
+                Bound::Excluded(end.as_str()),
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the events of a user after the `since` timestamp, oldest first.
+    ///
+    /// - limit defaults to [crate::config::DEFAULT_LIST_LIMIT] and capped by [crate::config::DEFAULT_MAX_LIST_LIMIT]
+    pub fn list_user_events(
+        &self,
+        txn: &RoTxn,
+        public_key: &PublicKey,
+        since: Option<&Timestamp>,
+        limit: Option<u16>,
+    ) -> anyhow::Result<Vec<(Timestamp, UserEvent)>> {
+        let limit = limit
+            .unwrap_or(self.config().default_list_limit)
+            .min(self.config().max_list_limit);
+
+        let start = match since {
+            Some(since) => Bound::Excluded(format!("{public_key}:{since}")),
+            None => Bound::Included(format!("{public_key}:")),
+        };
+        // `;` follows `:`, so this ends the user's events.
+        let end = format!("{public_key};");
+
+        self.tables
+            .user_events
+            .range(
+                txn,
+                &(
+                    start.as_ref().map(|start| start.as_str()),
+                    Bound::Excluded(end.as_str()),
+                ),
+            )?
+            .take(limit as usize)
+            .map(|result| {
+                let (key, bytes) = result?;
+
+                let timestamp = key.rsplit(':').next().unwrap_or_default();
+                let timestamp = Timestamp::try_from(timestamp.to_string())
+                    .map_err(|_| anyhow::anyhow!("Invalid user event key {key}"))?;
+
+                Ok((timestamp, UserEvent::deserialize(bytes)?))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pkarr::Keypair;
+    use pubky_common::crypto::hash;
+
+    use crate::core::{database::DB, CoreConfig};
+
+    use super::UserEvent;
+
+    #[test]
+    fn prune_old_events() -> anyhow::Result<()> {
+        let db = unsafe {
+            DB::open(CoreConfig {
+                // Every event is older than the next one written.
+                user_event_log_max_age: Duration::ZERO,
+                ..CoreConfig::test()
+            })?
+        };
+
+        let public_key = Keypair::random().public_key();
+        let other = Keypair::random().public_key();
+
+        let mut wtxn = db.env.write_txn()?;
+        db.log_user_event(&mut wtxn, &format!("{public_key}/pub/a"), Some(hash(&[0])))?;
+        db.log_user_event(&mut wtxn, &format!("{other}/pub/a"), None)?;
+        wtxn.commit()?;
+
+        let mut wtxn = db.env.write_txn()?;
+        db.log_user_event(&mut wtxn, &format!("{public_key}/priv/b"), None)?;
+        wtxn.commit()?;
+
+        let rtxn = db.env.read_txn()?;
+
+        let events = db.list_user_events(&rtxn, &public_key, None, None)?;
+        assert_eq!(
+            events
+                .into_iter()
+                .map(|(_, event)| event)
+                .collect::<Vec<_>>(),
+            vec![UserEvent::Delete("/priv/b".to_string())]
+        );
+
+        // Only the user's own old events are pruned when they write.
+        assert_eq!(db.list_user_events(&rtxn, &other, None, None)?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -22,7 +22,7 @@ pub struct User {
 impl<'a> BytesEncode<'a> for User {
     type EItem = Self;
 
-    fn bytes_encode(user: &Self::EItem) -> Result<Cow<[u8]>, BoxedError> {
+    fn bytes_encode(user: &Self::EItem) -> Result<Cow<'_, [u8]>, BoxedError> {
         let vec = to_allocvec(user).unwrap();
 
         Ok(Cow::Owned(vec))
@@ -44,7 +44,7 @@ pub struct PublicKeyCodec {}
 impl<'a> BytesEncode<'a> for PublicKeyCodec {
     type EItem = PublicKey;
 
-    fn bytes_encode(pubky: &Self::EItem) -> Result<Cow<[u8]>, BoxedError> {
+    fn bytes_encode(pubky: &Self::EItem) -> Result<Cow<'_, [u8]>, BoxedError> {
         Ok(Cow::Borrowed(pubky.as_bytes()))
     }
 }
@@ -140,6 +140,20 @@ impl<T> From<flume::SendError<T>> for Error {
     }
 }
 
+impl From<axum::extract::multipart::MultipartError> for Error {
+    fn from(error: axum::extract::multipart::MultipartError) -> Self {
+        debug!(?error);
+        Self::new(error.status(), Some(error.body_text()))
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        debug!(?error);
+        Self::new(StatusCode::BAD_GATEWAY, error.into())
+    }
+}
+
 impl From<flume::RecvError> for Error {
     fn from(error: flume::RecvError) -> Self {
         debug!(?error);
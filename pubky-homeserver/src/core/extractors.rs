@@ -53,6 +53,8 @@ pub struct ListQueryParams {
     pub cursor: Option<String>,
     pub reverse: bool,
     pub shallow: bool,
+    /// Only list entries modified after this [pubky_common::timestamp::Timestamp].
+    pub since: Option<String>,
 }
 
 impl<S> FromRequestParts<S> for ListQueryParams
@@ -83,12 +85,49 @@ where
                     Some(c.to_string())
                 }
             });
+        let since = params
+            .get("since")
+            // Treat `since=` as None
+            .filter(|s| !s.is_empty())
+            .cloned();
 
         Ok(ListQueryParams {
             reverse,
             shallow,
             limit,
             cursor,
+            since,
         })
     }
 }
+
+/// Whether the request asked to omit the response body with `Prefer: return=minimal`,
+/// in which case successful writes respond with `204 No Content`.
+#[derive(Debug, Clone, Copy)]
+pub struct PreferMinimal(pub bool);
+
+impl<S> FromRequestParts<S> for PreferMinimal
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let minimal = parts
+            .headers
+            .get_all("prefer")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|preference| {
+                preference
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .eq_ignore_ascii_case("return=minimal")
+            });
+
+        Ok(PreferMinimal(minimal))
+    }
+}
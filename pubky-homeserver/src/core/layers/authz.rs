@@ -6,6 +6,7 @@ use axum::{
 };
 use futures_util::future::BoxFuture;
 use pkarr::PublicKey;
+use pubky_common::capabilities::{Action, Capabilities, Capability};
 use std::{convert::Infallible, task::Poll};
 use tower::{Layer, Service};
 use tower_cookies::Cookies;
@@ -82,7 +83,25 @@ where
             let cookies = req.extensions().get::<Cookies>();
 
             // Authorize the request
-            if let Err(e) = authorize(&state, req.method(), cookies, pubky.public_key(), path) {
+            let authorized = if path == "/move" {
+                // Moving a prefix deletes entries from one and writes them to the other.
+                match move_prefixes(req.uri().query()) {
+                    Some((from, to)) => {
+                        authorize(&state, &Method::DELETE, cookies, pubky.public_key(), &from)
+                            .and_then(|_| {
+                                authorize(&state, &Method::PUT, cookies, pubky.public_key(), &to)
+                            })
+                    }
+                    None => Err(Error::new(
+                        StatusCode::BAD_REQUEST,
+                        Some("Missing `from` or `to` query parameters"),
+                    )),
+                }
+            } else {
+                authorize(&state, req.method(), cookies, pubky.public_key(), path)
+            };
+
+            if let Err(e) = authorized {
                 return Ok(e.into_response());
             }
 
@@ -92,7 +111,7 @@ where
     }
 }
 
-/// Authorize write (PUT or DELETE) for Public paths.
+/// Authorize write (PUT or DELETE) for Public paths, and any access to Private paths.
 fn authorize(
     state: &AppState,
     method: &Method,
@@ -100,48 +119,359 @@ fn authorize(
     public_key: &PublicKey,
     path: &str,
 ) -> Result<()> {
-    if path == "/session" {
-        // Checking (or deleting) one's session is ok for everyone
+    if path == "/session" || path == "/session/capabilities" {
+        // Checking (deleting or narrowing) one's session is ok for everyone
         return Ok(());
     } else if path.starts_with("/pub/") {
-        if method == Method::GET {
+        if method == Method::GET || method == Method::HEAD {
             return Ok(());
         }
+    } else if path.starts_with("/priv/") {
+        // Private paths are never publicly readable.
+    } else if path == "/storage" || path == "/event-log" {
+        // Storage usage and event logs are only visible to sessions with root capabilities.
     } else {
-        return Err(Error::new(
-            StatusCode::FORBIDDEN,
-            "Writing to directories other than '/pub/' is forbidden".into(),
-        ));
+        let reason = "Writing to directories other than '/pub/' or '/priv/' is forbidden";
+        denied(method, public_key, path, Action::Write, None, reason);
+
+        return Err(Error::new(StatusCode::FORBIDDEN, reason.into()));
     }
 
+    let action = if path.starts_with("/priv/") && (method == Method::GET || method == Method::HEAD)
+    {
+        Action::Read
+    } else {
+        Action::Write
+    };
+
     if let Some(cookies) = cookies {
-        let session_secret = session_secret_from_cookies(cookies, public_key)
-            .ok_or(Error::with_status(StatusCode::UNAUTHORIZED))?;
+        let session_secret = match session_secret_from_cookies(cookies, public_key) {
+            Some(session_secret) => session_secret,
+            None => {
+                denied(
+                    method,
+                    public_key,
+                    path,
+                    action,
+                    None,
+                    "Missing session cookie",
+                );
+
+                return Err(Error::with_status(StatusCode::UNAUTHORIZED));
+            }
+        };
+
+        let session = match state.db.get_session(&session_secret)? {
+            Some(session) => session,
+            None => {
+                denied(method, public_key, path, action, None, "Unknown session");
 
-        let session = state
-            .db
-            .get_session(&session_secret)?
-            .ok_or(Error::with_status(StatusCode::UNAUTHORIZED))?;
+                return Err(Error::with_status(StatusCode::UNAUTHORIZED));
+            }
+        };
 
         if session.pubky() == public_key
-            && session.capabilities().iter().any(|cap| {
-                path.starts_with(&cap.scope)
-                    && cap
-                        .actions
-                        .contains(&pubky_common::capabilities::Action::Write)
-            })
+            && session
+                .capabilities()
+                .iter()
+                .any(|cap| path.starts_with(&cap.scope) && cap.actions.contains(&action))
         {
             return Ok(());
         }
 
+        let reason = if session.pubky() != public_key {
+            "Session belongs to a different pubky"
+        } else {
+            "Missing capability"
+        };
+        denied(
+            method,
+            public_key,
+            path,
+            action,
+            Some(session.capabilities()),
+            reason,
+        );
+
         return Err(Error::with_status(StatusCode::FORBIDDEN));
     }
 
+    denied(
+        method,
+        public_key,
+        path,
+        action,
+        None,
+        "Missing session cookie",
+    );
+
     Err(Error::with_status(StatusCode::UNAUTHORIZED))
 }
 
+/// The `from` and `to` query parameters of a `/move` request.
+fn move_prefixes(query: Option<&str>) -> Option<(String, String)> {
+    let mut from = None;
+    let mut to = None;
+
+    for (key, value) in url::form_urlencoded::parse(query?.as_bytes()) {
+        match key.as_ref() {
+            "from" => from = Some(value.into_owned()),
+            "to" => to = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+
+    Some((from?, to?))
+}
+
+/// Log a denied request, with the capability it required and the ones its session had.
+///
+/// Never log the session secret.
+fn denied(
+    method: &Method,
+    public_key: &PublicKey,
+    path: &str,
+    action: Action,
+    present: Option<&[Capability]>,
+    reason: &str,
+) {
+    let required = Capability {
+        scope: path.to_string(),
+        actions: vec![action],
+    };
+    let present = Capabilities(present.map(|caps| caps.to_vec()).unwrap_or_default());
+
+    tracing::warn!(
+        pubky = %public_key,
+        %path,
+        %method,
+        %required,
+        %present,
+        reason,
+        "Denied request"
+    );
+}
+
 pub fn session_secret_from_cookies(cookies: &Cookies, public_key: &PublicKey) -> Option<String> {
     cookies
         .get(&public_key.to_string())
         .map(|c| c.value().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use pkarr::Keypair;
+    use pubky_common::{auth::AuthToken, capabilities::Capabilities};
+
+    use crate::core::HomeserverCore;
+
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn signup(server: &HomeserverCore, keypair: &Keypair, capabilities: &str) -> String {
+        let capabilities: Capabilities = capabilities.try_into().unwrap();
+        let auth_token = AuthToken::sign(keypair, capabilities);
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/signup")
+                    .header("host", keypair.public_key().to_string())
+                    .method(Method::POST)
+                    .body(Body::from(auth_token.serialize()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        response
+            .headers()
+            .get(header::SET_COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn log_denied_request() {
+        let logs = Logs::default();
+
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let cookie = signup(&server, &keypair, "/pub/pubky.app/:rw").await;
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/foo.bar/file")
+                    .header("host", public_key.to_string())
+                    .method(Method::PUT)
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::from(vec![0]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+
+        assert!(logs.contains("Denied request"));
+        assert!(logs.contains(&format!("pubky={public_key}")));
+        assert!(logs.contains("path=/pub/foo.bar/file"));
+        assert!(logs.contains("method=PUT"));
+        assert!(logs.contains("required=/pub/foo.bar/file:w"));
+        assert!(logs.contains("present=/pub/pubky.app/:rw"));
+        assert!(logs.contains("reason=\"Missing capability\""));
+
+        let session_secret = cookie
+            .split(';')
+            .next()
+            .and_then(|c| c.split_once('='))
+            .unwrap()
+            .1;
+
+        assert!(!logs.contains(session_secret));
+    }
+
+    #[tokio::test]
+    async fn private_paths() {
+        let server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let cookie = signup(&server, &keypair, "/priv/example.com/:rw").await;
+
+        let request = |method: Method, path: &str, cookie: Option<&str>| {
+            let mut builder = Request::builder()
+                .uri(path)
+                .header("host", public_key.to_string())
+                .method(method);
+
+            if let Some(cookie) = cookie {
+                builder = builder.header(header::COOKIE, cookie);
+            }
+
+            builder.body(Body::from(vec![0])).unwrap()
+        };
+
+        // Write granted by capability
+        let response = server
+            .call(request(Method::PUT, "/priv/example.com/foo", Some(&cookie)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // Read granted by capability
+        let response = server
+            .call(request(Method::GET, "/priv/example.com/foo", Some(&cookie)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Outside the granted scope
+        let response = server
+            .call(request(Method::PUT, "/priv/other.com/foo", Some(&cookie)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Public reads are denied
+        let response = server
+            .call(request(Method::GET, "/priv/example.com/foo", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = server
+            .call(request(Method::GET, "/priv/example.com/", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn public_head() {
+        let server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let cookie = signup(&server, &keypair, "/pub/example.com/:rw").await;
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/example.com/foo")
+                    .header("host", public_key.to_string())
+                    .method(Method::PUT)
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::from(vec![0]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/example.com/foo")
+                    .header("host", public_key.to_string())
+                    .method(Method::HEAD)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/priv/example.com/foo")
+                    .header("host", public_key.to_string())
+                    .method(Method::HEAD)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
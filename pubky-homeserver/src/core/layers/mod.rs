@@ -1,3 +1,4 @@
 pub mod authz;
 pub mod pubky_host;
+pub mod rate_limiter;
 pub mod trace;
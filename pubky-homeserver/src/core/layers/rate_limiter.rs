@@ -0,0 +1,121 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use axum::Router;
+use governor::middleware::StateInformationMiddleware;
+use serde::{Deserialize, Serialize};
+use tower_governor::{
+    governor::{GovernorConfig, GovernorConfigBuilder},
+    key_extractor::{KeyExtractor, PeerIpKeyExtractor, SmartIpKeyExtractor},
+    GovernorLayer,
+};
+
+/// How often expired records are cleared from the rate limiter's storage.
+const GC_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Configurations for rate limiting requests by IP address.
+pub struct RateLimiterConfig {
+    /// Enable rate limit based on headers commonly used by reverse proxies.
+    ///
+    /// Uses headers commonly used by reverse proxies to extract the original IP address,
+    /// falling back to the connection's peer IP address.
+    /// <https://docs.rs/tower_governor/latest/tower_governor/key_extractor/struct.SmartIpKeyExtractor.html>
+    #[serde(default)]
+    pub behind_proxy: bool,
+    /// How many seconds it takes to replenish one element of the quota, so that
+    /// an IP address can keep sending one request every `per_second` seconds.
+    ///
+    /// **Must not be zero.**
+    pub per_second: u64,
+    /// Set quota size that defines how many requests can occur
+    /// before the governor middleware starts blocking requests from an IP address and
+    /// clients have to wait until the elements of the quota are replenished.
+    ///
+    /// **The burst_size must not be zero.**
+    pub burst_size: u32,
+}
+
+impl Default for RateLimiterConfig {
+    fn default() -> Self {
+        Self {
+            behind_proxy: false,
+            per_second: 2,
+            burst_size: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A rate limiter that works for direct connections (Peer) or behind reverse-proxy (Proxy)
+pub enum IpRateLimiter {
+    Peer(Arc<GovernorConfig<PeerIpKeyExtractor, StateInformationMiddleware>>),
+    Proxy(Arc<GovernorConfig<SmartIpKeyExtractor, StateInformationMiddleware>>),
+}
+
+impl IpRateLimiter {
+    /// Create an [IpRateLimiter]
+    ///
+    /// Expired records are only cleared from its storage by [Self::run_gc].
+    pub fn new(config: &RateLimiterConfig) -> Result<Self> {
+        Ok(match config.behind_proxy {
+            true => Self::Proxy(governor_config(config, SmartIpKeyExtractor)?),
+            false => Self::Peer(governor_config(config, PeerIpKeyExtractor)?),
+        })
+    }
+
+    /// Add a [GovernorLayer] on the provided [Router]
+    pub fn layer(&self, router: Router) -> Router {
+        match self {
+            IpRateLimiter::Peer(config) => router.layer(GovernorLayer {
+                config: config.clone(),
+            }),
+            IpRateLimiter::Proxy(config) => router.layer(GovernorLayer {
+                config: config.clone(),
+            }),
+        }
+    }
+
+    /// Clear expired records from the rate limiter's storage every [GC_INTERVAL], until dropped.
+    pub async fn run_gc(self) {
+        let mut interval = tokio::time::interval(GC_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let size = match &self {
+                IpRateLimiter::Peer(config) => {
+                    config.limiter().retain_recent();
+                    config.limiter().len()
+                }
+                IpRateLimiter::Proxy(config) => {
+                    config.limiter().retain_recent();
+                    config.limiter().len()
+                }
+            };
+
+            tracing::debug!("rate limiting storage size: {size}");
+        }
+    }
+}
+
+fn governor_config<K>(
+    config: &RateLimiterConfig,
+    key_extractor: K,
+) -> Result<Arc<GovernorConfig<K, StateInformationMiddleware>>>
+where
+    K: KeyExtractor + Send + Sync + 'static,
+    K::Key: Send + Sync,
+{
+    Ok(Arc::new(
+        GovernorConfigBuilder::default()
+            .use_headers()
+            .per_second(config.per_second)
+            .burst_size(config.burst_size)
+            .key_extractor(key_extractor)
+            .finish()
+            .ok_or(anyhow!(
+                "rate limiter per_second and burst_size must not be zero"
+            ))?,
+    ))
+}
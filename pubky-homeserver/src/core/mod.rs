@@ -1,8 +1,10 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use anyhow::Result;
 use axum::Router;
-use pubky_common::auth::AuthVerifier;
+use pkarr::PublicKey;
+use pubky_common::{auth::AuthVerifier, capabilities::Capability};
+use url::Url;
 
 pub mod database;
 mod error;
@@ -10,22 +12,31 @@ mod extractors;
 mod layers;
 mod routes;
 
+pub use layers::rate_limiter::RateLimiterConfig;
+
 use crate::config::{
-    DEFAULT_LIST_LIMIT, DEFAULT_MAP_SIZE, DEFAULT_MAX_LIST_LIMIT, DEFAULT_STORAGE_DIR,
+    DEFAULT_LIST_LIMIT, DEFAULT_MAP_SIZE, DEFAULT_MAX_ENTRY_SIZE, DEFAULT_MAX_LIST_LIMIT,
+    DEFAULT_MAX_PATH_LENGTH, DEFAULT_STORAGE_DIR, DEFAULT_USER_EVENT_LOG_MAX_AGE,
 };
 
 use database::DB;
+use layers::rate_limiter::IpRateLimiter;
 
 #[derive(Clone, Debug)]
 pub(crate) struct AppState {
     pub(crate) verifier: AuthVerifier,
     pub(crate) db: DB,
+    /// Client for fetching entries from [ReadThrough] upstreams.
+    pub(crate) http_client: reqwest::Client,
 }
 
 #[derive(Debug, Clone)]
 /// A side-effect-free Core of the [crate::Homeserver].
 pub struct HomeserverCore {
     pub(crate) router: Router,
+    pub(crate) db: DB,
+    /// Rate limiter of the [Self::router], whose storage is cleared by [crate::Homeserver].
+    pub(crate) rate_limiter: Option<IpRateLimiter>,
 }
 
 impl HomeserverCore {
@@ -40,11 +51,29 @@ impl HomeserverCore {
         let state = AppState {
             verifier: AuthVerifier::default(),
             db,
+            http_client: reqwest::Client::new(),
         };
 
-        let router = routes::create_app(state.clone());
+        let rate_limiter = config
+            .rate_limiter
+            .as_ref()
+            .map(IpRateLimiter::new)
+            .transpose()?;
+
+        let router = routes::create_app(state.clone(), rate_limiter.as_ref())?;
+
+        Ok(Self {
+            router,
+            db: state.db,
+            rate_limiter,
+        })
+    }
+
+    /// Flush the database to disk and close this core.
+    pub fn shutdown(self) -> Result<()> {
+        self.db.env.force_sync()?;
 
-        Ok(Self { router })
+        Ok(())
     }
 }
 
@@ -55,7 +84,7 @@ mod tests {
     use axum::{
         body::Body,
         extract::Request,
-        http::{header, Method},
+        http::{header, Method, StatusCode},
         response::Response,
     };
     use pkarr::Keypair;
@@ -100,6 +129,100 @@ mod tests {
             Ok(self.router.clone().oneshot(request).await?)
         }
     }
+
+    #[tokio::test]
+    async fn restart_from_same_storage() {
+        let config = CoreConfig::test();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        {
+            let mut server = unsafe { HomeserverCore::new(config.clone()) }.unwrap();
+            let cookie = server.create_root_user(&keypair).await.unwrap();
+
+            let response = server
+                .call(
+                    Request::builder()
+                        .header("host", public_key.to_string())
+                        .uri("/pub/foo")
+                        .method(Method::PUT)
+                        .header(header::COOKIE, cookie)
+                        .body(Body::from(vec![1_u8, 2, 3, 4, 5]))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            server.shutdown().unwrap();
+        }
+
+        let server = unsafe { HomeserverCore::new(config) }.unwrap();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/pub/foo")
+                    .method(Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert_eq!(body.as_ref(), &[1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn session_survives_restart() {
+        let config = CoreConfig::test();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let cookie = {
+            let mut server = unsafe { HomeserverCore::new(config.clone()) }.unwrap();
+            let cookie = server.create_root_user(&keypair).await.unwrap();
+
+            server.shutdown().unwrap();
+
+            cookie
+        };
+
+        let server = unsafe { HomeserverCore::new(config) }.unwrap();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/session")
+                    .method(Method::GET)
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let session = pubky_common::session::Session::deserialize(&body).unwrap();
+
+        assert_eq!(session.pubky(), &public_key);
+        assert_eq!(session.capabilities(), &vec![Capability::root()]);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -119,6 +242,104 @@ pub struct CoreConfig {
     ///
     /// Defaults to `1000`
     pub max_list_limit: u16,
+
+    /// The maximum size of an entry's content in bytes.
+    ///
+    /// Defaults to `100MB`
+    pub max_entry_size: usize,
+    /// The maximum length of an entry's path (like `/pub/example.com/foo`) in bytes,
+    /// as percent-encoded in the request url.
+    ///
+    /// Defaults to `1024`
+    pub max_path_length: usize,
+
+    /// Content types (like `image/png` or `image/*`) allowed to be stored, if set.
+    ///
+    /// Defaults to `None`, allowing any content type.
+    pub allowed_content_types: Option<Vec<String>>,
+    /// Content types (like `application/x-executable`) never allowed to be stored.
+    ///
+    /// Defaults to an empty list.
+    pub denied_content_types: Vec<String>,
+
+    /// The maximum total size of entries stored by each user, in bytes.
+    ///
+    /// Defaults to `None` (unlimited).
+    pub user_storage_quota: Option<u64>,
+
+    /// How long sessions stay valid without being refreshed.
+    ///
+    /// Defaults to `None` (sessions never expire).
+    pub session_ttl: Option<Duration>,
+    /// The maximum lifetime of a session since its creation,
+    /// no matter how many times it is refreshed.
+    ///
+    /// Defaults to `None` (unlimited).
+    pub max_session_lifetime: Option<Duration>,
+
+    /// Pubkys allowed to signup and signin, if set.
+    ///
+    /// Defaults to `None`, allowing any Pubky.
+    pub allowed_pubkys: Option<Vec<PublicKey>>,
+    /// The most capabilities granted to sessions on signup and signin, if set.
+    ///
+    /// Requested capabilities are intersected with these (narrowing scopes and
+    /// dropping actions), and the session holds only the result.
+    ///
+    /// Defaults to `None`, granting whatever the auth token requests.
+    pub max_capabilities: Option<Vec<Capability>>,
+
+    /// Reject `PUT` and `DELETE` requests to entries without a valid
+    /// `x-pubky-signature` header, signed by the tenant's keypair.
+    ///
+    /// Signatures are always verified when present.
+    ///
+    /// Defaults to `false`.
+    pub require_signed_writes: bool,
+
+    /// Serve entries absent locally from the homeservers of other Pubkys.
+    ///
+    /// Defaults to an empty list.
+    pub read_through: Vec<ReadThrough>,
+
+    /// How long shared caches (like CDNs) may serve `/pub/` entries without
+    /// revalidating them, which they can do cheaply using their `ETag`.
+    ///
+    /// Defaults to `None`, requiring revalidation on every request (`Cache-Control: no-cache`).
+    pub public_cache_max_age: Option<Duration>,
+
+    /// How long events stay in each user's event log, readable by the user at `/event-log`.
+    ///
+    /// Defaults to 30 days.
+    pub user_event_log_max_age: Duration,
+
+    /// Origins (like `https://example.com`) allowed to make cross-origin requests, if set.
+    ///
+    /// Defaults to `None`, allowing any origin.
+    pub cors_allowed_origins: Option<Vec<String>>,
+
+    /// Rate limit requests by IP address, if set.
+    ///
+    /// Defaults to `None` (no rate limiting).
+    pub rate_limiter: Option<RateLimiterConfig>,
+}
+
+/// Serve a tenant's entries under a prefix from another Pubky's homeserver,
+/// when they are absent locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadThrough {
+    /// The local tenant whose reads are forwarded.
+    pub pubky: PublicKey,
+    /// The path prefix (like `/pub/shared/`) of forwarded reads,
+    /// read from the same path of the upstream.
+    pub prefix: String,
+    /// The Pubky whose entries are served.
+    pub upstream: PublicKey,
+    /// The url of the upstream's homeserver over ICANN http(s),
+    /// like `https://homeserver.example.com`.
+    pub url: Url,
+    /// Store entries fetched from the upstream, so later reads are served locally.
+    pub cache: bool,
 }
 
 impl Default for CoreConfig {
@@ -130,6 +351,32 @@ impl Default for CoreConfig {
 
             default_list_limit: DEFAULT_LIST_LIMIT,
             max_list_limit: DEFAULT_MAX_LIST_LIMIT,
+
+            max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+            max_path_length: DEFAULT_MAX_PATH_LENGTH,
+
+            allowed_content_types: None,
+            denied_content_types: vec![],
+
+            user_storage_quota: None,
+
+            session_ttl: None,
+            max_session_lifetime: None,
+
+            allowed_pubkys: None,
+            max_capabilities: None,
+
+            require_signed_writes: false,
+
+            read_through: vec![],
+
+            public_cache_max_age: None,
+
+            user_event_log_max_age: DEFAULT_USER_EVENT_LOG_MAX_AGE,
+
+            cors_allowed_origins: None,
+
+            rate_limiter: None,
         }
     }
 }
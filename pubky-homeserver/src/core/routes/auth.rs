@@ -1,22 +1,38 @@
-use axum::{extract::State, response::IntoResponse};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use axum_extra::{extract::Host, headers::UserAgent, TypedHeader};
 use bytes::Bytes;
-use tower_cookies::{cookie::SameSite, Cookie, Cookies};
+use pkarr::PublicKey;
+use tower_cookies::{
+    cookie::{self, SameSite},
+    Cookie, Cookies,
+};
 
-use pubky_common::{crypto::random_bytes, session::Session, timestamp::Timestamp};
+use pubky_common::{
+    capabilities::Capability, crypto::random_bytes, session::Session, timestamp::Timestamp,
+};
 
-use crate::core::{database::tables::users::User, error::Result, AppState};
+use crate::core::{
+    database::tables::{sessions::SessionExpiry, users::User},
+    error::{Error, Result},
+    extractors::PreferMinimal,
+    AppState,
+};
 
 pub async fn signup(
     State(state): State<AppState>,
     user_agent: Option<TypedHeader<UserAgent>>,
     cookies: Cookies,
     host: Host,
+    minimal: PreferMinimal,
     body: Bytes,
-) -> Result<impl IntoResponse> {
+) -> Result<Response> {
     // TODO: Verify invitation link.
     // TODO: add errors in case of already axisting user.
-    signin(State(state), user_agent, cookies, host, body).await
+    signin(State(state), user_agent, cookies, host, minimal, body).await
 }
 
 pub async fn signin(
@@ -24,16 +40,29 @@ pub async fn signin(
     user_agent: Option<TypedHeader<UserAgent>>,
     cookies: Cookies,
     Host(host): Host,
+    PreferMinimal(minimal): PreferMinimal,
     body: Bytes,
-) -> Result<impl IntoResponse> {
+) -> Result<Response> {
     let token = state.verifier.verify(&body)?;
 
     let public_key = token.pubky();
 
+    if let Some(allowed) = &state.db.config().allowed_pubkys {
+        if !allowed.contains(public_key) {
+            return Err(Error::new(
+                StatusCode::FORBIDDEN,
+                Some("Pubky is not allowed on this homeserver"),
+            ));
+        }
+    }
+
     let mut wtxn = state.db.env.write_txn()?;
 
     let users = state.db.tables.users;
-    if let Some(existing) = users.get(&wtxn, public_key)? {
+    let existing = users.get(&wtxn, public_key)?;
+    let created = existing.is_none();
+
+    if let Some(existing) = existing {
         // TODO: why do we need this?
         users.put(&mut wtxn, public_key, &existing)?;
     } else {
@@ -48,9 +77,14 @@ pub async fn signin(
 
     let session_secret = base32::encode(base32::Alphabet::Crockford, &random_bytes::<16>());
 
+    let capabilities = match &state.db.config().max_capabilities {
+        Some(max) => intersect_capabilities(token.capabilities(), max),
+        None => token.capabilities().to_vec(),
+    };
+
     let session = Session::new(
         token.pubky(),
-        token.capabilities(),
+        &capabilities,
         user_agent.map(|ua| ua.to_string()),
     )
     .serialize();
@@ -61,22 +95,100 @@ pub async fn signin(
         .sessions
         .put(&mut wtxn, &session_secret, &session)?;
 
+    let session_ttl = state.db.config().session_ttl;
+
+    if let Some(ttl) = session_ttl {
+        let created_at = Timestamp::now().as_u64();
+
+        let expiry = SessionExpiry {
+            created_at,
+            expires_at: created_at.saturating_add(ttl.as_micros() as u64),
+        };
+
+        state
+            .db
+            .tables
+            .session_expirations
+            .put(&mut wtxn, &session_secret, &expiry.serialize())?;
+    }
+
     wtxn.commit()?;
 
+    let mut cookie = session_cookie(public_key, session_secret, &host);
+
+    if let Some(ttl) = session_ttl {
+        cookie.set_max_age(cookie::time::Duration::seconds(ttl.as_secs() as i64));
+    }
+
+    cookies.add(cookie);
+
+    // The session is established by the cookie alone.
+    if minimal {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    // `201 Created` tells clients this was a new account.
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+
+    Ok((status, session).into_response())
+}
+
+/// Capabilities both `requested` and within `max`, with the narrower scope
+/// and the common actions of each overlapping pair.
+fn intersect_capabilities(requested: &[Capability], max: &[Capability]) -> Vec<Capability> {
+    let mut capabilities = Vec::new();
+
+    for requested in requested {
+        for max in max {
+            let scope = if requested.scope.starts_with(&max.scope) {
+                &requested.scope
+            } else if max.scope.starts_with(&requested.scope) {
+                &max.scope
+            } else {
+                continue;
+            };
+
+            let actions = requested
+                .actions
+                .iter()
+                .filter(|action| max.actions.contains(action))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !actions.is_empty() {
+                capabilities.push(Capability {
+                    scope: scope.clone(),
+                    actions,
+                });
+            }
+        }
+    }
+
+    capabilities
+}
+
+/// Create a session cookie for this Pubky.
+pub(crate) fn session_cookie(
+    public_key: &PublicKey,
+    session_secret: String,
+    host: &str,
+) -> Cookie<'static> {
     let mut cookie = Cookie::new(public_key.to_string(), session_secret);
 
     cookie.set_path("/");
 
     // TODO: do we even have insecure anymore?
-    if is_secure(&host) {
+    if is_secure(host) {
         cookie.set_secure(true);
         cookie.set_same_site(SameSite::None);
     }
     cookie.set_http_only(true);
 
-    cookies.add(cookie);
-
-    Ok(session)
+    cookie
 }
 
 /// Assuming that if the server is addressed by anything other than
@@ -93,7 +205,14 @@ fn is_secure(host: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Method, Request},
+    };
     use pkarr::Keypair;
+    use pubky_common::auth::AuthToken;
+
+    use crate::core::{CoreConfig, HomeserverCore};
 
     use super::*;
 
@@ -108,4 +227,206 @@ mod tests {
         assert!(is_secure(&Keypair::random().public_key().to_string()));
         assert!(is_secure("example.com"));
     }
+
+    #[tokio::test]
+    async fn allowed_pubkys() {
+        let allowed = Keypair::random();
+        let other = Keypair::random();
+
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                allowed_pubkys: Some(vec![allowed.public_key()]),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        server.create_root_user(&allowed).await.unwrap();
+
+        for path in ["/signup", "/session"] {
+            let response = server
+                .call(
+                    Request::builder()
+                        .uri(path)
+                        .header("host", other.public_key().to_string())
+                        .method(Method::POST)
+                        .body(Body::from(
+                            AuthToken::sign(&other, vec![Capability::root()]).serialize(),
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            assert!(response.headers().get(header::SET_COOKIE).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn max_capabilities() {
+        let server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                max_capabilities: Some(vec!["/:r".try_into().unwrap()]),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/signup")
+                    .header("host", public_key.to_string())
+                    .method(Method::POST)
+                    .body(Body::from(
+                        AuthToken::sign(&keypair, vec![Capability::root()]).serialize(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let session = Session::deserialize(&body).unwrap();
+        assert_eq!(
+            session.capabilities(),
+            &vec![Capability::try_from("/:r").unwrap()]
+        );
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/foo")
+                    .header("host", public_key.to_string())
+                    .header(header::COOKIE, cookie)
+                    .method(Method::PUT)
+                    .body(Body::from(vec![0]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn intersect_capabilities() {
+        let capabilities = |list: &str| {
+            list.split(',')
+                .map(|capability| Capability::try_from(capability).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            super::intersect_capabilities(
+                &capabilities("/pub/foo/:rw,/priv/:rw"),
+                &capabilities("/pub/:r,/pub/foo/bar/:w")
+            ),
+            capabilities("/pub/foo/:r,/pub/foo/bar/:w")
+        );
+    }
+
+    #[tokio::test]
+    async fn signup_created() {
+        let server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+
+        let signup = || {
+            Request::builder()
+                .uri("/signup")
+                .header("host", keypair.public_key().to_string())
+                .method(Method::POST)
+                .body(Body::from(
+                    AuthToken::sign(&keypair, vec![Capability::root()]).serialize(),
+                ))
+                .unwrap()
+        };
+
+        let response = server.call(signup()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server.call(signup()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn prefer_minimal() {
+        let server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/signup")
+                    .header("host", keypair.public_key().to_string())
+                    .header("prefer", "return=minimal")
+                    .method(Method::POST)
+                    .body(Body::from(
+                        AuthToken::sign(&keypair, vec![Capability::root()]).serialize(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let put = |prefer: Option<&str>| {
+            let mut builder = Request::builder()
+                .uri("/pub/foo.txt")
+                .header("host", keypair.public_key().to_string())
+                .header(header::COOKIE, &cookie)
+                .method(Method::PUT);
+
+            if let Some(prefer) = prefer {
+                builder = builder.header("prefer", prefer);
+            }
+
+            builder.body(Body::from("foo")).unwrap()
+        };
+
+        let response = server.call(put(Some("return=minimal"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server.call(put(Some("return=minimal"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = server
+            .call(put(Some("return=representation")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server.call(put(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
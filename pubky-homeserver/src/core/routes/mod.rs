@@ -1,21 +1,25 @@
 //! The controller part of the [super::HomeserverCore]
 
+use std::ops::RangeInclusive;
+
 use axum::{
     body::Body,
     extract::Request,
-    http::{header, HeaderValue},
+    http::{header, HeaderValue, StatusCode},
     middleware::{self, Next},
-    response::Response,
+    response::{IntoResponse, Response},
     routing::{get, post},
     Router,
 };
 use tower::ServiceBuilder;
 use tower_cookies::CookieManagerLayer;
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
-use crate::core::AppState;
+use crate::core::{error::Error, AppState};
 
-use super::layers::{pubky_host::PubkyHostLayer, trace::with_trace_layer};
+use super::layers::{
+    pubky_host::PubkyHostLayer, rate_limiter::IpRateLimiter, trace::with_trace_layer,
+};
 
 mod auth;
 mod feed;
@@ -25,6 +29,13 @@ mod tenants;
 static HOMESERVER_VERSION: &str = concat!("pubky.org", "@", env!("CARGO_PKG_VERSION"),);
 const TRACING_EXCLUDED_PATHS: [&str; 1] = ["/events/"];
 
+/// Header carrying the wire protocol version, sent by clients and echoed by the homeserver.
+const PUBKY_VERSION_HEADER: &str = "x-pubky-version";
+/// The current wire protocol version.
+const PUBKY_VERSION: u8 = 0;
+/// Wire protocol versions that clients can use with this homeserver.
+const SUPPORTED_PUBKY_VERSIONS: RangeInclusive<u8> = 0..=PUBKY_VERSION;
+
 fn base() -> Router<AppState> {
     Router::new()
         .route("/", get(root::handler))
@@ -37,15 +48,45 @@ fn base() -> Router<AppState> {
     // TODO: maybe add to a separate router (drive router?).
 }
 
-pub fn create_app(state: AppState) -> Router {
+pub fn create_app(state: AppState, rate_limiter: Option<&IpRateLimiter>) -> anyhow::Result<Router> {
+    let config = state.db.config();
+
+    let cors = cors_layer(config.cors_allowed_origins.as_deref())?;
+
     let app = base()
         .merge(tenants::router(state.clone()))
         .layer(CookieManagerLayer::new())
-        .layer(CorsLayer::very_permissive())
+        .layer(ServiceBuilder::new().layer(middleware::from_fn(negotiate_version)))
+        .layer(cors)
         .layer(ServiceBuilder::new().layer(middleware::from_fn(add_server_header)))
         .with_state(state);
 
-    with_trace_layer(app, &TRACING_EXCLUDED_PATHS).layer(PubkyHostLayer)
+    let mut app = with_trace_layer(app, &TRACING_EXCLUDED_PATHS);
+
+    if let Some(rate_limiter) = rate_limiter {
+        app = rate_limiter.layer(app);
+    }
+
+    Ok(app.layer(PubkyHostLayer))
+}
+
+/// Allow cross-origin requests from `allowed_origins` if set, or from any origin.
+fn cors_layer(allowed_origins: Option<&[String]>) -> anyhow::Result<CorsLayer> {
+    let cors = CorsLayer::very_permissive();
+
+    let Some(allowed_origins) = allowed_origins else {
+        return Ok(cors);
+    };
+
+    let origins = allowed_origins
+        .iter()
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .map_err(|_| anyhow::anyhow!("Invalid CORS allowed origin: {origin}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(cors.allow_origin(AllowOrigin::list(origins)))
 }
 
 // Middleware to add a `Server` header to all responses
@@ -59,3 +100,211 @@ async fn add_server_header(request: Request<Body>, next: Next) -> Response {
 
     response
 }
+
+// Middleware to validate the client's `X-Pubky-Version` and echo the negotiated version.
+//
+// Requests without the header are assumed to speak the current version.
+async fn negotiate_version(request: Request<Body>, next: Next) -> Response {
+    let version = match request.headers().get(PUBKY_VERSION_HEADER) {
+        Some(value) => match value
+            .to_str()
+            .ok()
+            .and_then(|s| s.trim().parse::<u8>().ok())
+        {
+            Some(version) => version,
+            None => {
+                return Error::new(
+                    StatusCode::BAD_REQUEST,
+                    Some(format!("Invalid {PUBKY_VERSION_HEADER} header")),
+                )
+                .into_response();
+            }
+        },
+        None => PUBKY_VERSION,
+    };
+
+    if !SUPPORTED_PUBKY_VERSIONS.contains(&version) {
+        let mut response = Error::new(
+            StatusCode::UPGRADE_REQUIRED,
+            Some(format!(
+                "Unsupported Pubky version {version}, supported versions are {}..={}",
+                SUPPORTED_PUBKY_VERSIONS.start(),
+                SUPPORTED_PUBKY_VERSIONS.end()
+            )),
+        )
+        .into_response();
+
+        response.headers_mut().insert(
+            PUBKY_VERSION_HEADER,
+            HeaderValue::from(u16::from(PUBKY_VERSION)),
+        );
+
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+
+    response
+        .headers_mut()
+        .insert(PUBKY_VERSION_HEADER, HeaderValue::from(u16::from(version)));
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+
+    use crate::core::{CoreConfig, HomeserverCore, RateLimiterConfig};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn version_negotiation() {
+        let server = HomeserverCore::test().unwrap();
+
+        let request = |version: Option<&str>| {
+            let mut request = Request::builder()
+                .uri("/")
+                .header(header::ORIGIN, "https://example.com");
+
+            if let Some(version) = version {
+                request = request.header(PUBKY_VERSION_HEADER, version);
+            }
+
+            request.body(Body::empty()).unwrap()
+        };
+
+        let negotiated = |response: &Response| {
+            response
+                .headers()
+                .get(PUBKY_VERSION_HEADER)
+                .map(|v| v.to_str().unwrap().to_string())
+        };
+
+        // Missing header is the current version.
+        let response = server.call(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(negotiated(&response), Some(PUBKY_VERSION.to_string()));
+
+        // Matching version.
+        let response = server
+            .call(request(Some(&PUBKY_VERSION.to_string())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(negotiated(&response), Some(PUBKY_VERSION.to_string()));
+
+        // Unsupported version, responding with the version to use instead.
+        let response = server
+            .call(request(Some(&(PUBKY_VERSION + 1).to_string())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(negotiated(&response), Some(PUBKY_VERSION.to_string()));
+        // Readable by browsers.
+        assert!(response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        // Malformed version.
+        let response = server.call(request(Some("v1"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn cors_allowed_origins() {
+        let server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                cors_allowed_origins: Some(vec!["https://example.com".to_string()]),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let request = |origin: &str| {
+            Request::builder()
+                .uri("/")
+                .header(header::ORIGIN, origin)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = server.call(request("https://example.com")).await.unwrap();
+        assert_eq!(
+            response
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://example.com"
+        );
+
+        let response = server.call(request("https://other.com")).await.unwrap();
+        assert!(!response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn rate_limiter() {
+        let server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                rate_limiter: Some(RateLimiterConfig {
+                    behind_proxy: false,
+                    per_second: 60,
+                    burst_size: 2,
+                }),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let request = |ip: [u8; 4]| {
+            let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+            request.extensions_mut().insert(axum::extract::ConnectInfo(
+                std::net::SocketAddr::from((ip, 1234)),
+            ));
+            request
+        };
+
+        for _ in 0..2 {
+            let response = server.call(request([127, 0, 0, 1])).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = server.call(request([127, 0, 0, 1])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Other IP addresses have their own quota.
+        let response = server.call(request([127, 0, 0, 2])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn invalid_configs() {
+        assert!(unsafe {
+            HomeserverCore::new(CoreConfig {
+                cors_allowed_origins: Some(vec!["https://example.com\n".to_string()]),
+                ..CoreConfig::test()
+            })
+        }
+        .is_err());
+
+        assert!(unsafe {
+            HomeserverCore::new(CoreConfig {
+                rate_limiter: Some(RateLimiterConfig {
+                    burst_size: 0,
+                    ..Default::default()
+                }),
+                ..CoreConfig::test()
+            })
+        }
+        .is_err());
+    }
+}
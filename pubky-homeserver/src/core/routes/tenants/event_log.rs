@@ -0,0 +1,49 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, Response, StatusCode},
+    response::IntoResponse,
+};
+use pubky_common::timestamp::Timestamp;
+use serde::Deserialize;
+
+use crate::core::{
+    error::{Error, Result},
+    extractors::PubkyHost,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct EventLogParams {
+    since: Option<String>,
+    limit: Option<u16>,
+}
+
+/// Returns the tenant's events after the `since` timestamp (if any), oldest first,
+/// formatted as `<timestamp> PUT <content hash> <path>` or `<timestamp> DEL <path>` lines.
+pub async fn event_log(
+    State(state): State<AppState>,
+    pubky: PubkyHost,
+    Query(EventLogParams { since, limit }): Query<EventLogParams>,
+) -> Result<impl IntoResponse> {
+    let since = since
+        .map(Timestamp::try_from)
+        .transpose()
+        .map_err(|_| Error::new(StatusCode::BAD_REQUEST, Some("Invalid since timestamp")))?;
+
+    let txn = state.db.env.read_txn()?;
+
+    let events = state
+        .db
+        .list_user_events(&txn, pubky.public_key(), since.as_ref(), limit)?;
+
+    let lines = events
+        .iter()
+        .map(|(timestamp, event)| format!("{timestamp} {}", event.line()))
+        .collect::<Vec<_>>();
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Body::from(lines.join("\n")))?)
+}
@@ -5,14 +5,16 @@
 
 use axum::{
     extract::DefaultBodyLimit,
-    routing::{delete, get, head, put},
+    routing::{delete, get, head, patch, post, put},
     Router,
 };
 
 use crate::core::{layers::authz::AuthorizationLayer, AppState};
 
+pub mod event_log;
 pub mod read;
 pub mod session;
+pub mod storage;
 pub mod write;
 
 pub fn router(state: AppState) -> Router<AppState> {
@@ -22,12 +24,94 @@ pub fn router(state: AppState) -> Router<AppState> {
         .route("/pub/{*path}", get(read::get))
         .route("/pub/{*path}", head(read::head))
         .route("/pub/{*path}", put(write::put))
+        .route("/pub/{*path}", post(write::post_multipart))
         .route("/pub/{*path}", delete(write::delete))
+        .route("/pub/{*path}", patch(write::increment))
+        .route("/priv/", get(read::get))
+        .route("/priv/{*path}", get(read::get))
+        .route("/priv/{*path}", head(read::head))
+        .route("/priv/{*path}", put(write::put))
+        .route("/priv/{*path}", post(write::post_multipart))
+        .route("/priv/{*path}", delete(write::delete))
+        .route("/priv/{*path}", patch(write::increment))
+        .route("/move", post(write::move_prefix))
         // - Session routes
         .route("/session", get(session::session))
         .route("/session", delete(session::signout))
+        .route("/session", patch(session::refresh))
+        .route("/session/capabilities", delete(session::revoke_capability))
+        // - Storage routes
+        .route("/storage", get(storage::breakdown))
+        // - Event log routes
+        .route("/event-log", get(event_log::event_log))
         // Layers
         // TODO: different max size for sessions and other routes?
-        .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
+        .layer(DefaultBodyLimit::max(state.db.config().max_entry_size))
         .layer(AuthorizationLayer::new(state.clone()))
 }
+
+/// Methods the [router] accepts on an entry or directory `path` under `/pub/` or `/priv/`,
+/// for the `Allow` header of `405 Method Not Allowed` responses.
+pub fn allowed_methods(path: &str) -> &'static str {
+    if path.ends_with('/') {
+        "DELETE, GET, HEAD, PATCH, POST, PUT"
+    } else {
+        "DELETE, GET, HEAD, PATCH, PUT"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use pkarr::Keypair;
+
+    use crate::core::HomeserverCore;
+
+    #[tokio::test]
+    async fn method_not_allowed() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/foo")
+                    .header("host", public_key.to_string())
+                    .method(Method::POST)
+                    .header(header::COOKIE, cookie)
+                    .body(Body::from(vec![0]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let mut allow = response
+            .headers()
+            .get(header::ALLOW)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .split(',')
+            .map(|method| method.trim().to_string())
+            .collect::<Vec<_>>();
+        allow.sort();
+
+        assert_eq!(allow, vec!["DELETE", "GET", "HEAD", "PATCH", "PUT"]);
+        assert_eq!(
+            super::allowed_methods("/pub/foo"),
+            "DELETE, GET, HEAD, PATCH, PUT"
+        );
+        assert_eq!(
+            super::allowed_methods("/pub/foo/"),
+            "DELETE, GET, HEAD, PATCH, POST, PUT"
+        );
+    }
+}
@@ -4,32 +4,69 @@ use axum::{
     http::{header, HeaderMap, HeaderValue, Response, StatusCode},
     response::IntoResponse,
 };
+use heed::RoTxn;
 use httpdate::HttpDate;
 use pkarr::PublicKey;
-use std::str::FromStr;
+use pubky_common::{crypto::hash, timestamp::Timestamp};
+use std::{io::Write, ops::Range, str::FromStr};
 
 use crate::core::{
-    database::tables::entries::Entry,
+    database::{tables::entries::Entry, DB},
     error::{Error, Result},
     extractors::{ListQueryParams, PubkyHost},
-    AppState,
+    AppState, ReadThrough,
 };
 
+use super::write::REDIRECT_CONTENT_TYPE;
+
 pub async fn head(
     State(state): State<AppState>,
     pubky: PubkyHost,
     headers: HeaderMap,
     path: OriginalUri,
 ) -> Result<impl IntoResponse> {
-    let rtxn = state.db.env.read_txn()?;
+    let public_key = pubky.public_key();
+    let path = path.0.path();
 
-    get_entry(
-        headers,
-        state
-            .db
-            .get_entry(&rtxn, pubky.public_key(), path.0.path())?,
-        None,
-    )
+    let entry = {
+        let rtxn = state.db.env.read_txn()?;
+
+        let mut entry = state.db.get_entry(&rtxn, public_key, path)?;
+        let content_encoding = state.db.content_encoding(&rtxn, public_key, path)?;
+
+        if let Some(entry) = entry.as_mut() {
+            sniff_content_type(&state.db, &rtxn, entry, content_encoding.as_deref())?;
+        }
+
+        if let Some(entry) = entry
+            .as_ref()
+            .filter(|entry| entry.content_type() == REDIRECT_CONTENT_TYPE)
+        {
+            let mut target = Vec::new();
+            for chunk in entry.read_content(&state.db, &rtxn)? {
+                target.extend_from_slice(chunk?);
+            }
+
+            return redirect(entry, &target);
+        }
+
+        entry.map(|entry| (entry, content_encoding))
+    };
+
+    let mut response = match (entry, matching_read_through(&state, public_key, path)) {
+        // Fetched (and cached) the same way as GET, to respond with the same headers.
+        (None, Some(read_through)) => {
+            let (parts, _) = read_through_entry(state.clone(), headers, &read_through, path)
+                .await?
+                .into_parts();
+
+            Response::from_parts(parts, Body::empty())
+        }
+        (entry, _) => get_entry(headers, entry, None, None)?,
+    };
+    response.headers_mut().extend(cache_headers(&state, path));
+
+    Ok(response)
 }
 
 pub async fn get(
@@ -43,10 +80,17 @@ pub async fn get(
     let path = path.0.path().to_string();
 
     if path.ends_with('/') {
-        return list(state, &public_key, &path, params);
+        return list(state, &headers, &public_key, &path, params);
     }
 
-    let (entry_tx, entry_rx) = flume::bounded::<Option<Entry>>(1);
+    let range = ByteRange::from_headers(&headers);
+    let accepts_brotli = accepts_brotli(&headers);
+    let cache_headers = cache_headers(&state, &path);
+
+    let read_through = matching_read_through(&state, &public_key, &path)
+        .map(|read_through| (read_through, state.clone(), path.clone()));
+
+    let (entry_tx, entry_rx) = flume::bounded::<Option<(Entry, Option<String>)>>(1);
     let (chunks_tx, chunks_rx) = flume::unbounded::<std::result::Result<Vec<u8>, heed::Error>>();
 
     tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
@@ -54,13 +98,83 @@ pub async fn get(
 
         let option = state.db.get_entry(&rtxn, &public_key, &path)?;
 
-        if let Some(entry) = option {
+        if let Some(mut entry) = option {
             let iter = entry.read_content(&state.db, &rtxn)?;
 
-            entry_tx.send(Some(entry))?;
+            // Redirects are served from their whole target, ignoring ranges.
+            if entry.content_type() == REDIRECT_CONTENT_TYPE {
+                entry_tx.send(Some((entry, None)))?;
+
+                for next in iter {
+                    chunks_tx.send(next.map(|chunk| chunk.to_vec()))?;
+                }
+
+                return Ok(());
+            }
+
+            let content_encoding = state.db.content_encoding(&rtxn, &public_key, &path)?;
+            sniff_content_type(&state.db, &rtxn, &mut entry, content_encoding.as_deref())?;
+
+            // Decompress for clients that don't accept the stored encoding.
+            if content_encoding.is_some() && !accepts_brotli {
+                entry_tx.send(Some((entry, content_encoding)))?;
+
+                let mut decompressor =
+                    brotli::DecompressorWriter::new(ChunksWriter(chunks_tx.clone()), 4096);
+
+                for next in iter {
+                    match next {
+                        Ok(chunk) => decompressor.write_all(chunk)?,
+                        Err(error) => chunks_tx.send(Err(error))?,
+                    }
+                }
+
+                decompressor.close()?;
+
+                return Ok(());
+            }
+
+            let range = match range.map(|range| range.resolve(entry.content_length())) {
+                // Unsatisfiable range, no content to send.
+                Some(None) => {
+                    entry_tx.send(Some((entry, content_encoding)))?;
+                    return Ok(());
+                }
+                range => range.flatten(),
+            };
+
+            entry_tx.send(Some((entry, content_encoding)))?;
+
+            let mut offset = 0;
 
             for next in iter {
-                chunks_tx.send(next.map(|b| b.to_vec()))?;
+                let chunk = match next {
+                    Ok(chunk) => chunk,
+                    Err(error) => {
+                        chunks_tx.send(Err(error))?;
+                        continue;
+                    }
+                };
+
+                let chunk_start = offset;
+                offset += chunk.len();
+
+                let chunk = match &range {
+                    Some(range) => {
+                        if offset <= range.start {
+                            continue;
+                        }
+                        if chunk_start >= range.end {
+                            break;
+                        }
+
+                        &chunk[range.start.saturating_sub(chunk_start)
+                            ..(range.end - chunk_start).min(chunk.len())]
+                    }
+                    None => chunk,
+                };
+
+                chunks_tx.send(Ok(chunk.to_vec()))?;
             }
         };
 
@@ -69,15 +183,195 @@ pub async fn get(
         Ok(())
     });
 
-    get_entry(
-        headers,
-        entry_rx.recv_async().await?,
-        Some(Body::from_stream(chunks_rx.into_stream())),
-    )
+    let entry = entry_rx.recv_async().await?;
+
+    if let Some((entry, _)) = entry
+        .as_ref()
+        .filter(|(entry, _)| entry.content_type() == REDIRECT_CONTENT_TYPE)
+    {
+        let mut target = Vec::new();
+        while let Ok(chunk) = chunks_rx.recv_async().await {
+            target.extend_from_slice(&chunk?);
+        }
+
+        return redirect(entry, &target);
+    }
+
+    let mut response = match (entry, read_through) {
+        (None, Some((read_through, state, path))) => {
+            read_through_entry(state, headers, &read_through, &path).await?
+        }
+        (entry, _) => get_entry(
+            headers,
+            entry,
+            Some(Body::from_stream(chunks_rx.into_stream())),
+            range,
+        )?,
+    };
+
+    response.headers_mut().extend(cache_headers);
+
+    Ok(response)
+}
+
+/// Respond with `302 Found` to the `target` of a redirect entry,
+/// see [REDIRECT_CONTENT_TYPE].
+fn redirect(entry: &Entry, target: &[u8]) -> Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(StatusCode::FOUND)
+        .header(
+            header::LOCATION,
+            HeaderValue::from_bytes(target).map_err(|_| {
+                Error::new(StatusCode::INTERNAL_SERVER_ERROR, Some("Invalid redirect"))
+            })?,
+        )
+        .header(header::ETAG, entry.etag())
+        .header(header::CONTENT_TYPE, REDIRECT_CONTENT_TYPE)
+        .body(Body::empty())?)
+}
+
+/// Sniff the content type of entries stored without one, from their leading bytes,
+/// falling back to `application/octet-stream` for unknown or compressed content.
+fn sniff_content_type(
+    db: &DB,
+    rtxn: &RoTxn,
+    entry: &mut Entry,
+    content_encoding: Option<&str>,
+) -> anyhow::Result<()> {
+    if !entry.content_type().is_empty() {
+        return Ok(());
+    }
+
+    let sniffed = match content_encoding {
+        Some(_) => None,
+        None => match entry.read_content(db, rtxn)?.next() {
+            Some(chunk) => infer::get(chunk?).map(|t| t.mime_type()),
+            None => None,
+        },
+    };
+
+    entry.set_content_type(sniffed.unwrap_or("application/octet-stream"));
+
+    Ok(())
+}
+
+/// Let shared caches store `/pub/` entries, revalidating them with their `ETag`
+/// once [crate::core::CoreConfig::public_cache_max_age] passes.
+fn cache_headers(state: &AppState, path: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if !path.starts_with("/pub/") {
+        return headers;
+    }
+
+    let cache_control = match state.db.config().public_cache_max_age {
+        Some(max_age) => format!("public, max-age={}", max_age.as_secs()),
+        None => "public, no-cache".to_string(),
+    };
+
+    headers.insert(
+        header::CACHE_CONTROL,
+        cache_control
+            .try_into()
+            .expect("cache control is a valid header value"),
+    );
+    headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+    headers
+}
+
+/// Returns the [ReadThrough] forwarding reads of this `path`, if any.
+fn matching_read_through(
+    state: &AppState,
+    public_key: &PublicKey,
+    path: &str,
+) -> Option<ReadThrough> {
+    state
+        .db
+        .config()
+        .read_through
+        .iter()
+        .find(|read_through| {
+            &read_through.pubky == public_key && path.starts_with(&read_through.prefix)
+        })
+        .cloned()
+}
+
+/// Fetch an entry absent locally from the upstream of a [ReadThrough],
+/// and store it if configured to.
+async fn read_through_entry(
+    state: AppState,
+    headers: HeaderMap,
+    read_through: &ReadThrough,
+    path: &str,
+) -> Result<Response<Body>> {
+    let url = read_through
+        .url
+        .join(path)
+        .map_err(|error| Error::new(StatusCode::INTERNAL_SERVER_ERROR, Some(error)))?;
+
+    let response = state
+        .http_client
+        .get(url)
+        .header("pubky-host", read_through.upstream.to_string())
+        .send()
+        .await?;
+
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => return Err(Error::with_status(StatusCode::NOT_FOUND)),
+        status => {
+            return Err(Error::new(
+                StatusCode::BAD_GATEWAY,
+                Some(format!("Upstream responded with {status}")),
+            ))
+        }
+    }
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+
+    let bytes = response.bytes().await?;
+
+    if bytes.len() > state.db.config().max_entry_size {
+        return Err(Error::new(
+            StatusCode::BAD_GATEWAY,
+            Some("Upstream entry is larger than the maximum entry size"),
+        ));
+    }
+
+    let entry = if read_through.cache {
+        let public_key = read_through.pubky.clone();
+        let path = path.to_string();
+        let bytes = bytes.clone();
+        let mut db = state.db;
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Entry> {
+            let mut entry_writer = db.write_entry(&public_key, &path)?;
+            entry_writer.set_content_type(&content_type);
+            entry_writer.update(&bytes)?.commit()
+        })
+        .await??
+    } else {
+        let mut entry = Entry::new();
+        entry
+            .set_content_hash(hash(&bytes))
+            .set_timestamp(&Timestamp::now())
+            .set_content_length(bytes.len())
+            .set_content_type(&content_type);
+        entry
+    };
+
+    get_entry(headers, Some((entry, None)), Some(Body::from(bytes)), None)
 }
 
 pub fn list(
     state: AppState,
+    headers: &HeaderMap,
     public_key: &PublicKey,
     path: &str,
     params: ListQueryParams,
@@ -93,6 +387,29 @@ pub fn list(
         ));
     }
 
+    // Entries modified after `since`, as `<timestamp> <url>` lines.
+    if let Some(since) = params.since {
+        if params.shallow || params.reverse {
+            return Err(Error::new(
+                StatusCode::BAD_REQUEST,
+                Some("`since` can't be combined with `shallow` or `reverse`"),
+            ));
+        }
+
+        let since = Timestamp::try_from(since)
+            .map_err(|_| Error::new(StatusCode::BAD_REQUEST, Some("Invalid since timestamp")))?;
+
+        let lines = state
+            .db
+            .list_since(&txn, &path, &since, params.limit, params.cursor)?
+            .into_iter()
+            .map(|(url, timestamp)| format!("{timestamp} {url}"))
+            .collect::<Vec<_>>();
+
+        // Lines already change with the timestamps of rewritten entries.
+        return listing(headers, lines, &[]);
+    }
+
     // Handle listing
     let vec = state.db.list(
         &txn,
@@ -103,23 +420,85 @@ pub fn list(
         params.shallow,
     )?;
 
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "text/plain")
-        .body(Body::from(vec.join("\n")))?)
+    // Content hashes of the listed entries, so that overwriting one changes the `ETag`.
+    let prefix = format!("pubky://{public_key}");
+    let mut content_hashes = vec![];
+    for url in &vec {
+        if let Some(entry) = url
+            .strip_prefix(&prefix)
+            .map(|path| state.db.get_entry(&txn, public_key, path))
+            .transpose()?
+            .flatten()
+        {
+            content_hashes.push(entry.content_hash().to_string());
+        }
+    }
+
+    listing(headers, vec, &content_hashes)
+}
+
+/// Respond with the `lines` of a listing, tagged with their hash and the `versions`
+/// of the listed entries as an `ETag`, or with `304 Not Modified` if that `ETag`
+/// matches `If-None-Match`.
+fn listing(headers: &HeaderMap, lines: Vec<String>, versions: &[String]) -> Result<Response<Body>> {
+    let body = lines.join("\n");
+    let etag = format!(
+        "\"{}\"",
+        hash(format!("{body}\n{}", versions.join("\n")).as_bytes())
+    );
+
+    let response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CONTENT_TYPE, "text/plain");
+
+    if if_none_match(headers, &etag) {
+        return Ok(response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())?);
+    }
+
+    Ok(response.status(StatusCode::OK).body(Body::from(body))?)
+}
+
+/// Returns true if the `If-None-Match` header lists the `etag`.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|str| str.trim().split(',').any(|tag| tag.trim() == etag))
 }
 
+/// Respond with an entry and the content coding it is stored with, if any.
 pub fn get_entry(
     headers: HeaderMap,
-    entry: Option<Entry>,
+    entry: Option<(Entry, Option<String>)>,
     body: Option<Body>,
+    range: Option<ByteRange>,
 ) -> Result<Response<Body>> {
-    if let Some(entry) = entry {
-        // TODO: Enable seek API (range requests)
-        // TODO: Gzip? or brotli?
-
+    if let Some((entry, content_encoding)) = entry {
         let mut response = HeaderMap::from(&entry).into_response();
 
+        // Served decompressed if the client doesn't accept brotli, ignoring ranges.
+        let mut range = range;
+
+        if let Some(content_encoding) = content_encoding {
+            let response_headers = response.headers_mut();
+            response_headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+            if accepts_brotli(&headers) {
+                response_headers.insert(
+                    header::CONTENT_ENCODING,
+                    content_encoding
+                        .try_into()
+                        .expect("content encoding is a valid header value"),
+                );
+            } else {
+                response_headers.remove(header::CONTENT_LENGTH);
+                response_headers.remove(header::ACCEPT_RANGES);
+                range = None;
+            }
+        }
+
         // Handle IF_MODIFIED_SINCE
         if let Some(condition_http_date) = headers
             .get(header::IF_MODIFIED_SINCE)
@@ -134,19 +513,40 @@ pub fn get_entry(
         };
 
         // Handle IF_NONE_MATCH
-        if let Some(str) = headers
-            .get(header::IF_NONE_MATCH)
-            .and_then(|h| h.to_str().ok())
-        {
-            let etag = format!("\"{}\"", entry.content_hash());
-            if str
-                .trim()
-                .split(',')
-                .collect::<Vec<_>>()
-                .contains(&etag.as_str())
-            {
-                *response.status_mut() = StatusCode::NOT_MODIFIED;
-            };
+        if if_none_match(&headers, &entry.etag()) {
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+        }
+
+        // Handle RANGE
+        if let (Some(range), StatusCode::OK) = (range, response.status()) {
+            let length = entry.content_length();
+
+            match range.resolve(length) {
+                Some(range) => {
+                    *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_LENGTH, range.len().into());
+                    response.headers_mut().insert(
+                        header::CONTENT_RANGE,
+                        format!("bytes {}-{}/{length}", range.start, range.end - 1)
+                            .try_into()
+                            .expect("valid header value"),
+                    );
+                }
+                None => {
+                    *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_LENGTH, 0.into());
+                    response.headers_mut().insert(
+                        header::CONTENT_RANGE,
+                        format!("bytes */{length}")
+                            .try_into()
+                            .expect("valid header value"),
+                    );
+                }
+            }
         }
 
         if let Some(body) = body {
@@ -159,6 +559,44 @@ pub fn get_entry(
     }
 }
 
+/// Returns true if the `Accept-Encoding` header accepts brotli (`br`).
+fn accepts_brotli(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default();
+
+            (name.eq_ignore_ascii_case("br") || name == "*")
+                && !params.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q == 0.0)
+                })
+        })
+}
+
+/// Sends written bytes as chunks of a streamed body.
+struct ChunksWriter(flume::Sender<std::result::Result<Vec<u8>, heed::Error>>);
+
+impl Write for ChunksWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .send(Ok(buf.to_vec()))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 impl From<&Entry> for HeaderMap {
     fn from(entry: &Entry) -> Self {
         let mut headers = HeaderMap::new();
@@ -179,15 +617,59 @@ impl From<&Entry> for HeaderMap {
         );
         headers.insert(
             header::ETAG,
-            format!("\"{}\"", entry.content_hash())
-                .try_into()
-                .expect("hex string is valid"),
+            entry.etag().try_into().expect("hex string is valid"),
         );
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
 
         headers
     }
 }
 
+/// A single byte range requested in a `Range: bytes=...` header.
+///
+/// Multiple ranges are not supported, and are ignored like any
+/// other invalid `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=start-end`, inclusive.
+    Bounded(usize, usize),
+    /// `bytes=start-`.
+    From(usize),
+    /// `bytes=-length`, the last `length` bytes.
+    Suffix(usize),
+}
+
+impl ByteRange {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(header::RANGE)?.to_str().ok()?;
+        let (start, end) = value.trim().strip_prefix("bytes=")?.split_once('-')?;
+
+        match (start.trim(), end.trim()) {
+            ("", "") => None,
+            ("", length) => length.parse().ok().map(Self::Suffix),
+            (start, "") => start.parse().ok().map(Self::From),
+            (start, end) => {
+                let start = start.parse().ok()?;
+                let end = end.parse().ok()?;
+
+                (start <= end).then_some(Self::Bounded(start, end))
+            }
+        }
+    }
+
+    /// Resolve this range against an entry of `length` bytes, returning
+    /// `None` if the range is not satisfiable.
+    pub fn resolve(&self, length: usize) -> Option<Range<usize>> {
+        let range = match *self {
+            Self::Bounded(start, end) => start..end.saturating_add(1).min(length),
+            Self::From(start) => start..length,
+            Self::Suffix(suffix) => length.saturating_sub(suffix)..length,
+        };
+
+        (range.start < range.end).then_some(range)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use axum::{
@@ -195,8 +677,10 @@ mod tests {
         http::{header, Method, Request, StatusCode},
     };
     use pkarr::Keypair;
+    use std::time::Duration;
+    use url::Url;
 
-    use crate::core::HomeserverCore;
+    use crate::core::{CoreConfig, HomeserverCore, ReadThrough};
 
     #[tokio::test]
     async fn if_last_modified() {
@@ -221,7 +705,7 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.status(), StatusCode::CREATED);
 
         let response = server
             .call(
@@ -278,7 +762,7 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.status(), StatusCode::CREATED);
 
         let response = server
             .call(
@@ -310,4 +794,507 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
     }
+
+    #[tokio::test]
+    async fn list_if_none_match() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let put = |path: &str, body: Vec<u8>| {
+            Request::builder()
+                .uri(path)
+                .header("host", public_key.to_string())
+                .method(Method::PUT)
+                .header(header::COOKIE, &cookie)
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let list = |etag: Option<&str>| {
+            let mut request = Request::builder()
+                .uri("/pub/")
+                .header("host", public_key.to_string())
+                .method(Method::GET);
+
+            if let Some(etag) = etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = server.call(put("/pub/foo", vec![1, 2, 3])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server.call(list(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+        let response = server
+            .call(list(Some(etag.to_str().unwrap())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // Changed once a listed entry is overwritten.
+        let response = server.call(put("/pub/foo", vec![4, 5, 6])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server
+            .call(list(Some(etag.to_str().unwrap())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let overwritten = response.headers().get(header::ETAG).unwrap().clone();
+        assert_ne!(overwritten, etag);
+
+        // Changed once a new entry is listed.
+        let response = server.call(put("/pub/bar", vec![1, 2, 3])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server
+            .call(list(Some(overwritten.to_str().unwrap())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers().get(header::ETAG).unwrap(), &overwritten);
+    }
+
+    #[tokio::test]
+    async fn range() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let data = (0..=255_u8).collect::<Vec<_>>();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/foo")
+                    .header("host", public_key.to_string())
+                    .method(Method::PUT)
+                    .header(header::COOKIE, cookie)
+                    .body(Body::from(data.clone()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        for (range, expected, content_range) in [
+            ("bytes=0-9", &data[0..10], "bytes 0-9/256"),
+            ("bytes=250-", &data[250..], "bytes 250-255/256"),
+            ("bytes=-6", &data[250..], "bytes 250-255/256"),
+            ("bytes=100-1000", &data[100..], "bytes 100-255/256"),
+        ] {
+            let response = server
+                .call(
+                    Request::builder()
+                        .uri("/pub/foo")
+                        .header("host", public_key.to_string())
+                        .method(Method::GET)
+                        .header(header::RANGE, range)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+            assert_eq!(
+                response.headers().get(header::CONTENT_RANGE).unwrap(),
+                content_range
+            );
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+
+            assert_eq!(body.as_ref(), expected);
+        }
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/foo")
+                    .header("host", public_key.to_string())
+                    .method(Method::GET)
+                    .header(header::RANGE, "bytes=256-")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            response.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes */256"
+        );
+    }
+
+    #[tokio::test]
+    async fn brotli_content_encoding() {
+        use std::io::Write;
+
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let asset = "body { color: red; }\n".repeat(100);
+
+        let compressed = {
+            let mut compressor = brotli::CompressorWriter::new(vec![], 4096, 11, 22);
+            compressor.write_all(asset.as_bytes()).unwrap();
+            compressor.into_inner()
+        };
+
+        let response = server
+            .call(
+                Request::builder()
+                    .uri("/pub/style.css")
+                    .header("host", public_key.to_string())
+                    .method(Method::PUT)
+                    .header(header::COOKIE, cookie)
+                    .header(header::CONTENT_TYPE, "text/css")
+                    .header(header::CONTENT_ENCODING, "br")
+                    .body(Body::from(compressed.clone()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let get = |accept_encoding: Option<&str>| {
+            let mut request = Request::builder()
+                .uri("/pub/style.css")
+                .header("host", public_key.to_string())
+                .method(Method::GET);
+
+            if let Some(accept_encoding) = accept_encoding {
+                request = request.header(header::ACCEPT_ENCODING, accept_encoding);
+            }
+
+            request.body(Body::empty()).unwrap()
+        };
+
+        // Served compressed to clients accepting brotli.
+        let response = server.call(get(Some("gzip, br"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "br"
+        );
+        assert_eq!(
+            response.headers().get(header::VARY).unwrap(),
+            "accept-encoding"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), compressed);
+
+        // Decompressed for others.
+        for accept_encoding in [None, Some("gzip"), Some("br;q=0")] {
+            let response = server.call(get(accept_encoding)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+            assert_eq!(
+                response.headers().get(header::CONTENT_TYPE).unwrap(),
+                "text/css"
+            );
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body.as_ref(), asset.as_bytes(), "{accept_encoding:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn read_through() {
+        let upstream_keypair = Keypair::random();
+        let upstream_key = upstream_keypair.public_key();
+
+        let mut upstream = HomeserverCore::test().unwrap();
+        let cookie = upstream.create_root_user(&upstream_keypair).await.unwrap();
+
+        for path in ["/pub/shared/foo.txt", "/pub/shared/bar.txt"] {
+            let response = upstream
+                .call(
+                    Request::builder()
+                        .header("host", upstream_key.to_string())
+                        .uri(path)
+                        .method(Method::PUT)
+                        .header(header::CONTENT_TYPE, "text/plain")
+                        .header(header::COOKIE, cookie.to_string())
+                        .body(Body::from("federated"))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let router = upstream.router.clone();
+        let upstream_task = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                read_through: vec![ReadThrough {
+                    pubky: public_key.clone(),
+                    prefix: "/pub/shared/".to_string(),
+                    upstream: upstream_key,
+                    url,
+                    cache: true,
+                }],
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let request = |method: Method, path: &str| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri(path)
+                .method(method)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let get = |path: &str| request(Method::GET, path);
+
+        let response = server.call(get("/pub/shared/foo.txt")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), b"federated");
+
+        // HEAD reads through too, with the same headers as GET.
+        let head = server
+            .call(request(Method::HEAD, "/pub/shared/bar.txt"))
+            .await
+            .unwrap();
+        let response = server.call(get("/pub/shared/bar.txt")).await.unwrap();
+        assert_eq!(head.status(), StatusCode::OK);
+        for name in [header::CONTENT_TYPE, header::CONTENT_LENGTH, header::ETAG] {
+            assert_eq!(head.headers().get(&name), response.headers().get(&name));
+        }
+
+        let body = axum::body::to_bytes(head.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        // Absent on the upstream too.
+        for method in [Method::GET, Method::HEAD] {
+            let response = server
+                .call(request(method, "/pub/shared/baz.txt"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        // Outside the forwarded prefix.
+        let response = server.call(get("/pub/foo.txt")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Cached locally, served without the upstream.
+        upstream_task.abort();
+
+        let response = server.call(get("/pub/shared/foo.txt")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), b"federated");
+    }
+
+    #[tokio::test]
+    async fn cache_control() {
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                public_cache_max_age: Some(Duration::from_secs(60)),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let mut etags = vec![];
+
+        for _ in 0..2 {
+            let response = server
+                .call(
+                    Request::builder()
+                        .header("host", public_key.to_string())
+                        .uri("/pub/foo")
+                        .method(Method::PUT)
+                        .header(header::COOKIE, &cookie)
+                        .body(Body::from(vec![1_u8, 2, 3]))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(response.status().is_success());
+
+            for method in [Method::GET, Method::HEAD] {
+                let response = server
+                    .call(
+                        Request::builder()
+                            .header("host", public_key.to_string())
+                            .uri("/pub/foo")
+                            .method(method)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(
+                    response.headers().get(header::CACHE_CONTROL).unwrap(),
+                    "public, max-age=60"
+                );
+                assert_eq!(
+                    response.headers().get(header::VARY).unwrap(),
+                    "accept-encoding"
+                );
+
+                etags.push(response.headers().get(header::ETAG).unwrap().clone());
+            }
+        }
+
+        // Rewriting the same content keeps the same ETag.
+        assert!(etags.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    #[tokio::test]
+    async fn redirect() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let target = format!("pubky://{public_key}/pub/target.txt");
+
+        let put = |body: String| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/link")
+                .method(Method::PUT)
+                .header(header::COOKIE, &cookie)
+                .header(header::CONTENT_TYPE, "application/x-pubky-redirect")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        for invalid in [
+            "https://example.com/".to_string(),
+            format!("{target}\n"),
+            format!("pubky://{public_key}/{}", "a".repeat(2048)),
+        ] {
+            let response = server.call(put(invalid)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let response = server.call(put(target.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        for method in [Method::GET, Method::HEAD] {
+            let response = server
+                .call(
+                    Request::builder()
+                        .header("host", public_key.to_string())
+                        .uri("/pub/link")
+                        .method(method)
+                        // Ignored for redirects.
+                        .header(header::RANGE, "bytes=0-1")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::FOUND);
+            assert_eq!(
+                response.headers().get(header::LOCATION).unwrap(),
+                target.as_str()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn sniff_content_type() {
+        let server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0];
+
+        // Entries stored without a content type.
+        let mut db = server.db.clone();
+        for (path, content) in [("/pub/image", &png[..]), ("/pub/data", &[1_u8, 2, 3][..])] {
+            let entry = db
+                .write_entry(&public_key, path)
+                .unwrap()
+                .update(content)
+                .unwrap()
+                .commit()
+                .unwrap();
+            assert_eq!(entry.content_type(), "");
+        }
+
+        for (path, expected) in [
+            ("/pub/image", "image/png"),
+            ("/pub/data", "application/octet-stream"),
+        ] {
+            for method in [Method::GET, Method::HEAD] {
+                let response = server
+                    .call(
+                        Request::builder()
+                            .header("host", public_key.to_string())
+                            .uri(path)
+                            .method(method)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(
+                    response.headers().get(header::CONTENT_TYPE).unwrap(),
+                    expected
+                );
+            }
+        }
+    }
 }
@@ -1,10 +1,19 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use tower_cookies::Cookies;
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+};
+use axum_extra::extract::Host;
+use pubky_common::timestamp::Timestamp;
+use serde::Deserialize;
+use tower_cookies::{cookie, Cookies};
 
 use crate::core::{
+    database::tables::sessions::{Revocation, SessionExpiry},
     error::{Error, Result},
     extractors::PubkyHost,
     layers::authz::session_secret_from_cookies,
+    routes::auth::session_cookie,
     AppState,
 };
 
@@ -15,13 +24,82 @@ pub async fn session(
 ) -> Result<impl IntoResponse> {
     if let Some(secret) = session_secret_from_cookies(&cookies, pubky.public_key()) {
         if let Some(session) = state.db.get_session(&secret)? {
+            let expiry = state.db.session_expiry(&secret)?;
+
             // TODO: add content-type
-            return Ok(session.serialize());
+            return Ok((expires_header(expiry), session.serialize()));
+        };
+    }
+
+    Err(Error::with_status(StatusCode::NOT_FOUND))
+}
+
+/// Extend the expiry of the current session, see [crate::core::CoreConfig::session_ttl].
+pub async fn refresh(
+    State(mut state): State<AppState>,
+    cookies: Cookies,
+    pubky: PubkyHost,
+    Host(host): Host,
+) -> Result<impl IntoResponse> {
+    if let Some(secret) = session_secret_from_cookies(&cookies, pubky.public_key()) {
+        if let Some(session) = state.db.get_session(&secret)? {
+            let expiry = state.db.refresh_session(&secret)?;
+
+            if let Some(expiry) = expiry {
+                let remaining =
+                    expiry.expires_at.saturating_sub(Timestamp::now().as_u64()) / 1_000_000;
+
+                let mut cookie = session_cookie(pubky.public_key(), secret, &host);
+                cookie.set_max_age(cookie::time::Duration::seconds(remaining as i64));
+
+                cookies.add(cookie);
+            }
+
+            return Ok((expires_header(expiry), session.serialize()));
         };
     }
 
     Err(Error::with_status(StatusCode::NOT_FOUND))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeCapabilityParams {
+    scope: String,
+}
+
+/// Remove the capabilities within the `scope` query parameter from the current session,
+/// responding with the updated session.
+///
+/// Responds with `409 Conflict` if a broader capability of the session grants access to
+/// `scope`, since removing the narrower ones wouldn't revoke anything.
+pub async fn revoke_capability(
+    State(mut state): State<AppState>,
+    cookies: Cookies,
+    pubky: PubkyHost,
+    Query(RevokeCapabilityParams { scope }): Query<RevokeCapabilityParams>,
+) -> Result<impl IntoResponse> {
+    if let Some(secret) = session_secret_from_cookies(&cookies, pubky.public_key()) {
+        match state.db.revoke_capability(&secret, &scope)? {
+            Some(Revocation::Revoked(session)) => {
+                let expiry = state.db.session_expiry(&secret)?;
+
+                return Ok((expires_header(expiry), session.serialize()));
+            }
+            Some(Revocation::Covered(capability)) => {
+                return Err(Error::new(
+                    StatusCode::CONFLICT,
+                    Some(format!(
+                        "{scope} is covered by the broader capability {capability}, revoke it instead"
+                    )),
+                ));
+            }
+            None => {}
+        }
+    }
+
+    Err(Error::with_status(StatusCode::NOT_FOUND))
+}
+
 pub async fn signout(
     State(mut state): State<AppState>,
     cookies: Cookies,
@@ -36,3 +114,266 @@ pub async fn signout(
     // Idempotent Success Response (200 OK)
     Ok(())
 }
+
+/// `Expires` header with the session's expiry, if it expires.
+fn expires_header(expiry: Option<SessionExpiry>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Some(expiry) = expiry {
+        headers.insert(
+            header::EXPIRES,
+            HeaderValue::from_str(&Timestamp::from(expiry.expires_at).format_http_date())
+                .expect("http date is a valid header value"),
+        );
+    }
+
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, time::Duration};
+
+    use axum::{body::Body, http::Method, http::Request, response::Response};
+    use httpdate::HttpDate;
+    use pkarr::Keypair;
+    use pubky_common::{
+        auth::AuthToken,
+        capabilities::{Capabilities, Capability},
+    };
+
+    use crate::core::{database::tables::sessions::SessionExpiry, CoreConfig, HomeserverCore};
+
+    use super::*;
+
+    fn expires(response: &Response) -> HttpDate {
+        HttpDate::from_str(
+            response
+                .headers()
+                .get(header::EXPIRES)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// Move the expiry of the session in `cookie` back by `elapsed`, as if that much time passed.
+    fn age_session(server: &mut HomeserverCore, cookie: &str, elapsed: Duration) {
+        let session_secret = cookie
+            .split(';')
+            .next()
+            .and_then(|c| c.split_once('='))
+            .unwrap()
+            .1;
+
+        let elapsed = elapsed.as_micros() as u64;
+
+        let expiry = server.db.session_expiry(session_secret).unwrap().unwrap();
+        let aged = SessionExpiry {
+            created_at: expiry.created_at - elapsed,
+            expires_at: expiry.expires_at - elapsed,
+        };
+
+        let mut wtxn = server.db.env.write_txn().unwrap();
+        server
+            .db
+            .tables
+            .session_expirations
+            .put(&mut wtxn, session_secret, &aged.serialize())
+            .unwrap();
+        wtxn.commit().unwrap();
+    }
+
+    #[tokio::test]
+    async fn refresh_session() {
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                session_ttl: Some(Duration::from_secs(60)),
+                max_session_lifetime: Some(Duration::from_secs(600)),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let request = |method: Method| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/session")
+                .method(method)
+                .header(header::COOKIE, &cookie)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        age_session(&mut server, &cookie, Duration::from_secs(30));
+
+        let response = server.call(request(Method::GET)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let before = expires(&response);
+
+        let response = server.call(request(Method::PATCH)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let refreshed = expires(&response);
+
+        assert!(refreshed > before);
+
+        // Still valid past the original expiry.
+        age_session(&mut server, &cookie, Duration::from_secs(45));
+
+        let response = server.call(request(Method::GET)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Expires without refreshing.
+        age_session(&mut server, &cookie, Duration::from_secs(30));
+
+        let response = server.call(request(Method::GET)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = server.call(request(Method::PATCH)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn max_session_lifetime() {
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                session_ttl: Some(Duration::from_secs(60)),
+                max_session_lifetime: Some(Duration::from_secs(60)),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let request = |method: Method| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/session")
+                .method(method)
+                .header(header::COOKIE, &cookie)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        age_session(&mut server, &cookie, Duration::from_secs(30));
+
+        let response = server.call(request(Method::GET)).await.unwrap();
+        let before = expires(&response);
+
+        // Can't be extended past the maximum lifetime.
+        let response = server.call(request(Method::PATCH)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(expires(&response), before);
+    }
+
+    #[tokio::test]
+    async fn revoke_capability() {
+        let server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+
+        let capabilities: Capabilities =
+            "/pub/example.com/:rw,/pub/other.com/:rw,/pub/other.com/nested/:rw"
+                .try_into()
+                .unwrap();
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/signup")
+                    .method(Method::POST)
+                    .body(Body::from(
+                        AuthToken::sign(&keypair, capabilities).serialize(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let request = |method: Method, uri: &str| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri(uri)
+                .method(method)
+                .header(header::COOKIE, &cookie)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for path in [
+            "/pub/example.com/foo",
+            "/pub/other.com/foo",
+            "/pub/other.com/nested/foo",
+        ] {
+            let response = server.call(request(Method::PUT, path)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED, "{path}");
+        }
+
+        let response = server
+            .call(request(
+                Method::DELETE,
+                "/session/capabilities?scope=/pub/other.com/",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let session = pubky_common::session::Session::deserialize(&body).unwrap();
+        assert_eq!(
+            session.capabilities(),
+            &vec![Capability::try_from("/pub/example.com/:rw").unwrap()]
+        );
+
+        // The remaining capability still authorizes writes.
+        let response = server
+            .call(request(Method::PUT, "/pub/example.com/foo"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The revoked ones, including nested scopes, are forbidden.
+        for path in ["/pub/other.com/foo", "/pub/other.com/nested/foo"] {
+            let response = server.call(request(Method::PUT, path)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{path}");
+        }
+
+        // A narrower scope of a remaining capability can't be revoked alone.
+        let response = server
+            .call(request(
+                Method::DELETE,
+                "/session/capabilities?scope=/pub/example.com/foo/",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = server.call(request(Method::GET, "/session")).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let session = pubky_common::session::Session::deserialize(&body).unwrap();
+        assert_eq!(
+            session.capabilities(),
+            &vec![Capability::try_from("/pub/example.com/:rw").unwrap()]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, Response, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::core::{error::Result, extractors::PubkyHost, AppState};
+
+/// Returns the storage used by each top-level directory in `/pub/`,
+/// formatted as `<directory> <bytes>` lines.
+pub async fn breakdown(
+    State(state): State<AppState>,
+    pubky: PubkyHost,
+) -> Result<impl IntoResponse> {
+    let txn = state.db.env.read_txn()?;
+
+    let breakdown = state.db.storage_breakdown(&txn, pubky.public_key())?;
+
+    let lines = breakdown
+        .iter()
+        .map(|(directory, size)| format!("{directory} {size}"))
+        .collect::<Vec<_>>();
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Body::from(lines.join("\n")))?)
+}
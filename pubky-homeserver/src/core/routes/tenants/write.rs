@@ -1,29 +1,80 @@
-use std::io::Write;
+use std::{io::Write, str::FromStr};
 
+use bytes::Bytes;
 use futures_util::stream::StreamExt;
+use httpdate::HttpDate;
+use pkarr::PublicKey;
+use pubky_common::{
+    crypto::{hash, Hash, Signature},
+    timestamp::Timestamp,
+};
+use serde::Deserialize;
 
 use axum::{
     body::Body,
-    extract::{OriginalUri, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{multipart::MultipartRejection, Multipart, OriginalUri, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
 };
 
 use crate::core::{
+    database::tables::entries::{Entry, EntryWriter},
     error::{Error, Result},
-    extractors::PubkyHost,
-    AppState,
+    extractors::{PreferMinimal, PubkyHost},
+    AppState, CoreConfig,
 };
 
 pub async fn delete(
     State(mut state): State<AppState>,
     pubky: PubkyHost,
+    headers: HeaderMap,
     path: OriginalUri,
 ) -> Result<impl IntoResponse> {
     let public_key = pubky.public_key().clone();
 
+    verify_signature(
+        state.db.config(),
+        &headers,
+        &public_key,
+        "DELETE",
+        path.0.path(),
+        &hash(&[]),
+    )?;
+
+    let if_match = etags(&headers, header::IF_MATCH);
+    let if_unmodified_since = headers
+        .get(header::IF_UNMODIFIED_SINCE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| HttpDate::from_str(s).ok());
+
     // TODO: should we wrap this with `tokio::task::spawn_blocking` in case it takes too long?
-    let deleted = state.db.delete_entry(&public_key, path.0.path())?;
+    let Some(deleted) = state
+        .db
+        .delete_entry_if(&public_key, path.0.path(), |entry| {
+            if let Some(if_match) = &if_match {
+                if !matches_any(if_match, &entry.etag()) {
+                    return false;
+                }
+            }
+
+            if let Some(if_unmodified_since) = if_unmodified_since {
+                if HttpDate::from(entry.timestamp().to_owned()) > if_unmodified_since {
+                    return false;
+                }
+            }
+
+            true
+        })?
+    else {
+        if is_immutable(&state, &public_key, path.0.path())? {
+            return Err(Error::new(
+                StatusCode::FORBIDDEN,
+                Some("Immutable entries can't be deleted"),
+            ));
+        }
+
+        return Err(Error::with_status(StatusCode::PRECONDITION_FAILED));
+    };
 
     if !deleted {
         // TODO: if the path ends with `/` return a `CONFLICT` error?
@@ -33,25 +84,1497 @@ pub async fn delete(
     Ok(())
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MovePrefixParams {
+    from: String,
+    to: String,
+}
+
+/// Move all entries under the `from` prefix to the `to` prefix atomically,
+/// responding with the number of moved entries.
+pub async fn move_prefix(
+    State(mut state): State<AppState>,
+    pubky: PubkyHost,
+    Query(MovePrefixParams { from, to }): Query<MovePrefixParams>,
+) -> Result<impl IntoResponse> {
+    if !from.ends_with('/') || !to.ends_with('/') {
+        return Err(Error::new(
+            StatusCode::BAD_REQUEST,
+            Some("Prefixes must end with `/`"),
+        ));
+    }
+
+    if from.starts_with(&to) || to.starts_with(&from) {
+        return Err(Error::new(
+            StatusCode::BAD_REQUEST,
+            Some("Prefixes must not overlap"),
+        ));
+    }
+
+    let moved = state
+        .db
+        .move_prefix(pubky.public_key(), &from, &to)?
+        .ok_or(Error::new(
+            StatusCode::CONFLICT,
+            Some("Immutable entries can't be moved or overwritten"),
+        ))?;
+
+    Ok(moved.to_string())
+}
+
+/// Add the decimal integer in the body to the counter entry at `path` atomically,
+/// responding with the new value, see [crate::core::database::DB::increment_entry].
+pub async fn increment(
+    State(mut state): State<AppState>,
+    pubky: PubkyHost,
+    path: OriginalUri,
+    body: Bytes,
+) -> Result<impl IntoResponse> {
+    let config = state.db.config().clone();
+
+    if path.0.path().len() > config.max_path_length {
+        return Err(path_too_long(config.max_path_length));
+    }
+
+    // Increments can't be signed, since their content is computed by the homeserver.
+    if config.require_signed_writes {
+        return Err(Error::new(
+            StatusCode::UNAUTHORIZED,
+            Some(format!("Missing {PUBKY_SIGNATURE_HEADER} header")),
+        ));
+    }
+
+    let by = std::str::from_utf8(&body)
+        .ok()
+        .and_then(|by| by.trim().parse::<i64>().ok())
+        .ok_or(Error::new(
+            StatusCode::BAD_REQUEST,
+            Some("Expected a decimal integer to increment by"),
+        ))?;
+
+    let value = state
+        .db
+        .increment_entry(pubky.public_key(), path.0.path(), by)?
+        .ok_or(Error::new(
+            StatusCode::CONFLICT,
+            Some("Entry is immutable, not a decimal integer, or would overflow"),
+        ))?;
+
+    Ok(value.to_string())
+}
+
+/// Header setting a time to live in seconds on PUT, after which the entry is treated
+/// as absent and eventually deleted.
+const PUBKY_TTL_HEADER: &str = "x-pubky-ttl";
+
+/// Header making an entry written with PUT immutable, if set to `true`.
+const IMMUTABLE_HEADER: &str = "x-immutable";
+
 pub async fn put(
     State(mut state): State<AppState>,
     pubky: PubkyHost,
+    headers: HeaderMap,
     path: OriginalUri,
+    PreferMinimal(minimal): PreferMinimal,
     body: Body,
 ) -> Result<impl IntoResponse> {
     let public_key = pubky.public_key().clone();
 
+    let config = state.db.config().clone();
+    let max_entry_size = config.max_entry_size;
+
+    if path.0.path().len() > config.max_path_length {
+        return Err(path_too_long(config.max_path_length));
+    }
+
+    let declared_content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| {
+            s.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        })
+        .filter(|s| !s.is_empty());
+
+    // Reject early, before receiving the body (and sending `100 Continue`).
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok());
+
+    if content_length.is_some_and(|length| length > max_entry_size as u64) {
+        return Err(entry_too_large(max_entry_size));
+    }
+
+    let remaining_quota = remaining_quota(&state, &public_key, path.0.path())?;
+
+    if let (Some(remaining_quota), Some(content_length)) = (remaining_quota, content_length) {
+        if content_length > remaining_quota {
+            return Err(quota_exceeded());
+        }
+    }
+
+    let ttl = match headers.get(PUBKY_TTL_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok())
+                .ok_or(Error::new(
+                    StatusCode::BAD_REQUEST,
+                    Some(format!(
+                        "Invalid {PUBKY_TTL_HEADER} header, expected seconds"
+                    )),
+                ))?,
+        ),
+        None => None,
+    };
+
+    let immutable = match headers.get(IMMUTABLE_HEADER).map(|h| h.to_str()) {
+        None => false,
+        Some(Ok(value)) if value.trim().eq_ignore_ascii_case("true") => true,
+        Some(Ok(value)) if value.trim().eq_ignore_ascii_case("false") => false,
+        Some(_) => {
+            return Err(Error::new(
+                StatusCode::BAD_REQUEST,
+                Some(format!(
+                    "Invalid {IMMUTABLE_HEADER} header, expected `true` or `false`"
+                )),
+            ))
+        }
+    };
+
+    if immutable && ttl.is_some() {
+        return Err(Error::new(
+            StatusCode::BAD_REQUEST,
+            Some(format!(
+                "Immutable entries can't expire, remove the {PUBKY_TTL_HEADER} header"
+            )),
+        ));
+    }
+
+    // Reject early, before receiving the body.
+    if is_immutable(&state, &public_key, path.0.path())? {
+        return Err(immutable_conflict());
+    }
+
+    // Pre-compressed content is stored as is, see [super::read::get].
+    let content_encoding = match headers
+        .get(header::CONTENT_ENCODING)
+        .map(|h| h.to_str().map(|s| s.trim().to_lowercase()))
+    {
+        None => None,
+        Some(Ok(encoding)) if encoding == "identity" => None,
+        Some(Ok(encoding)) if encoding == "br" => Some(encoding),
+        Some(_) => {
+            return Err(Error::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Some("Unsupported content encoding, expected `br`"),
+            ))
+        }
+    };
+
+    // Canonical JSON and redirects are buffered, to be checked (and transformed) as a whole.
+    if content_encoding.is_some()
+        && matches!(
+            declared_content_type.as_deref(),
+            Some(CANONICAL_JSON_CONTENT_TYPE | REDIRECT_CONTENT_TYPE)
+        )
+    {
+        return Err(Error::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Some("Canonical JSON and redirects can't be pre-compressed"),
+        ));
+    }
+
+    let (declared_content_type, body) = match declared_content_type {
+        Some(content_type) if content_type == CANONICAL_JSON_CONTENT_TYPE => (
+            Some("application/json".to_string()),
+            canonicalize_json(body, max_entry_size).await?,
+        ),
+        Some(content_type) if content_type == REDIRECT_CONTENT_TYPE => {
+            let body = check_redirect(body).await?;
+
+            (Some(content_type), body)
+        }
+        content_type => (content_type, body),
+    };
+
     let mut entry_writer = state.db.write_entry(&public_key, path.0.path())?;
 
+    if let Some(content_encoding) = &content_encoding {
+        entry_writer.set_content_encoding(content_encoding);
+    }
+
+    if let Some(ttl) = ttl {
+        entry_writer.set_expires_at(Timestamp::now() + ttl.saturating_mul(1_000_000));
+    }
+
+    if immutable {
+        entry_writer.set_immutable();
+    }
+
+    let mut size = 0;
+    let mut sniffed = false;
+
     let mut stream = body.into_data_stream();
     while let Some(next) = stream.next().await {
         let chunk = next?;
+
+        // Check the content type before accepting the rest of the body.
+        if !sniffed && !chunk.is_empty() {
+            sniffed = true;
+
+            // Compressed content can't be sniffed.
+            let sniffed_content_type = match content_encoding {
+                Some(_) => None,
+                None => infer::get(&chunk).map(|t| t.mime_type()),
+            };
+
+            check_content_types(
+                &config,
+                declared_content_type.as_deref(),
+                sniffed_content_type,
+            )?;
+
+            entry_writer.set_content_type(
+                declared_content_type
+                    .as_deref()
+                    .or(sniffed_content_type)
+                    .unwrap_or_default(),
+            );
+        }
+
+        size += chunk.len();
+        if size > max_entry_size {
+            return Err(entry_too_large(max_entry_size));
+        }
+        if remaining_quota.is_some_and(|remaining| size as u64 > remaining) {
+            return Err(quota_exceeded());
+        }
+
         entry_writer.write_all(&chunk)?;
     }
 
-    let _entry = entry_writer.commit()?;
+    if !sniffed {
+        check_content_types(&config, declared_content_type.as_deref(), None)?;
+
+        entry_writer.set_content_type(declared_content_type.as_deref().unwrap_or_default());
+    }
+
+    verify_signature(
+        &config,
+        &headers,
+        &public_key,
+        "PUT",
+        path.0.path(),
+        &entry_writer.content_hash(),
+    )?;
+
+    let if_match = etags(&headers, header::IF_MATCH);
+    let if_none_match = etags(&headers, header::IF_NONE_MATCH);
+    let if_unmodified_since = headers
+        .get(header::IF_UNMODIFIED_SINCE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| HttpDate::from_str(s).ok());
+
+    let mut created = false;
+
+    let entry = entry_writer.commit_if(|current| {
+        created = current.is_none();
+
+        let current_etag = current.map(Entry::etag);
+        let current_etag = current_etag.as_deref();
+
+        if let Some(if_match) = if_match {
+            if !current_etag.is_some_and(|current| matches_any(&if_match, current)) {
+                return false;
+            }
+        }
+
+        if let Some(if_none_match) = if_none_match {
+            if current_etag.is_some_and(|current| matches_any(&if_none_match, current)) {
+                return false;
+            }
+        }
+
+        if let Some(if_unmodified_since) = if_unmodified_since {
+            if current.is_some_and(|entry| {
+                HttpDate::from(entry.timestamp().to_owned()) > if_unmodified_since
+            }) {
+                return false;
+            }
+        }
+
+        true
+    })?;
+
+    let Some(entry) = entry else {
+        if is_immutable(&state, &public_key, path.0.path())? {
+            return Err(immutable_conflict());
+        }
+
+        return Err(Error::with_status(StatusCode::PRECONDITION_FAILED));
+    };
+
+    // Let clients follow up with conditional requests without reading the entry back.
+    let etag = [(header::ETAG, entry.etag())];
+
+    // An empty `201 Created` is still minimal, and tells clients the entry is new.
+    if created {
+        return Ok((StatusCode::CREATED, etag));
+    }
+
+    if minimal {
+        return Ok((StatusCode::NO_CONTENT, etag));
+    }
+
+    Ok((StatusCode::OK, etag))
+}
+
+/// Store each file of a `multipart/form-data` body as an entry under the directory `path`,
+/// named after its sanitized file name, responding with the urls of the stored entries.
+///
+/// Parts without a file name (plain form fields) are ignored.
+pub async fn post_multipart(
+    State(state): State<AppState>,
+    pubky: PubkyHost,
+    path: OriginalUri,
+    multipart: std::result::Result<Multipart, MultipartRejection>,
+) -> Result<Response> {
+    let public_key = pubky.public_key().clone();
+    let prefix = path.0.path();
+
+    // Only directories accept POST.
+    if !prefix.ends_with('/') {
+        return Ok((
+            StatusCode::METHOD_NOT_ALLOWED,
+            [(header::ALLOW, super::allowed_methods(prefix))],
+        )
+            .into_response());
+    }
+
+    let mut multipart =
+        multipart.map_err(|error| Error::new(error.status(), Some(error.body_text())))?;
+
+    let config = state.db.config().clone();
+
+    // Parts can't be individually signed.
+    if config.require_signed_writes {
+        return Err(Error::new(
+            StatusCode::UNAUTHORIZED,
+            Some(format!("Missing {PUBKY_SIGNATURE_HEADER} header")),
+        ));
+    }
+
+    // Validate every part before writing any, so that a rejected part
+    // doesn't leave the previous ones stored.
+    let mut files = vec![];
+
+    while let Some(field) = multipart.next_field().await? {
+        let Some(file_name) = field.file_name().map(sanitize_file_name) else {
+            continue;
+        };
+        let file_name = file_name.ok_or(Error::new(
+            StatusCode::BAD_REQUEST,
+            Some("Invalid file name"),
+        ))?;
+
+        let entry_path = format!("{prefix}{file_name}");
+
+        if entry_path.len() > config.max_path_length {
+            return Err(path_too_long(config.max_path_length));
+        }
+
+        let declared_content_type = field
+            .content_type()
+            .map(|s| {
+                s.split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_lowercase()
+            })
+            .filter(|s| !s.is_empty());
+
+        let bytes = field.bytes().await?;
+
+        if bytes.len() > config.max_entry_size {
+            return Err(entry_too_large(config.max_entry_size));
+        }
+
+        let sniffed_content_type = infer::get(&bytes).map(|t| t.mime_type());
+
+        check_content_types(
+            &config,
+            declared_content_type.as_deref(),
+            sniffed_content_type,
+        )?;
+
+        let content_type = declared_content_type
+            .as_deref()
+            .or(sniffed_content_type)
+            .unwrap_or_default()
+            .to_string();
+
+        files.push((entry_path, content_type, bytes));
+    }
+
+    if exceeds_quota(&state, &public_key, &files)? {
+        return Err(quota_exceeded());
+    }
+
+    // Store all parts, or none of them.
+    let mut wtxn = state.db.env.write_txn()?;
+
+    for (entry_path, content_type, bytes) in &files {
+        EntryWriter::new(&state.db, &public_key, entry_path)?
+            .set_content_type(content_type)
+            .update(bytes)?
+            .commit_in(&mut wtxn, |_| true)?
+            .ok_or_else(immutable_conflict)?;
+    }
+
+    wtxn.commit()?;
+
+    let urls = files
+        .iter()
+        .map(|(entry_path, _, _)| format!("pubky://{public_key}{entry_path}"))
+        .collect::<Vec<_>>();
+
+    Ok(urls.join("\n").into_response())
+}
+
+/// Keep only the last segment of a file name, replacing characters other than
+/// ASCII alphanumerics, `.`, `-` and `_` with `_`.
+///
+/// Returns `None` for names that are empty, `.` or `..` after sanitization.
+fn sanitize_file_name(file_name: &str) -> Option<String> {
+    let name = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+
+    match name.as_str() {
+        "" | "." | ".." => None,
+        _ => Some(name),
+    }
+}
+
+/// Returns the storage left to the user for writing an entry at `path`,
+/// if they have a quota.
+fn remaining_quota(state: &AppState, public_key: &PublicKey, path: &str) -> Result<Option<u64>> {
+    let Some(quota) = state.db.config().user_storage_quota else {
+        return Ok(None);
+    };
+
+    let rtxn = state.db.env.read_txn()?;
+
+    let used = state.db.storage_used(&rtxn, public_key)?;
+    // Overwriting an entry frees its current size.
+    let current = state
+        .db
+        .get_entry(&rtxn, public_key, path)?
+        .map(|entry| entry.content_length() as u64)
+        .unwrap_or(0);
+
+    Ok(Some(quota.saturating_sub(used.saturating_sub(current))))
+}
+
+/// Returns `true` if writing `files` (paths, content types and contents) at once would
+/// exceed the user's quota, if they have one, counting overwritten entries as freed.
+fn exceeds_quota(
+    state: &AppState,
+    public_key: &PublicKey,
+    files: &[(String, String, Bytes)],
+) -> Result<bool> {
+    let Some(quota) = state.db.config().user_storage_quota else {
+        return Ok(false);
+    };
+
+    // Later parts overwrite earlier ones at the same path.
+    let sizes = files
+        .iter()
+        .map(|(path, _, bytes)| (path.as_str(), bytes.len() as u64))
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let rtxn = state.db.env.read_txn()?;
+
+    let mut used = state.db.storage_used(&rtxn, public_key)?;
+
+    for (path, size) in sizes {
+        let current = state
+            .db
+            .get_entry(&rtxn, public_key, path)?
+            .map(|entry| entry.content_length() as u64)
+            .unwrap_or(0);
+
+        used = used.saturating_sub(current) + size;
+    }
+
+    Ok(used > quota)
+}
 
-    // TODO: return relevant headers, like Etag?
+/// Returns `true` if the current (not expired) entry at `path` is immutable.
+fn is_immutable(state: &AppState, public_key: &PublicKey, path: &str) -> Result<bool> {
+    let rtxn = state.db.env.read_txn()?;
+
+    Ok(state.db.is_immutable(&rtxn, public_key, path)?
+        && !state.db.is_expired(&rtxn, public_key, path)?)
+}
+
+/// Header carrying a hex encoded Ed25519 signature of a write by the tenant's keypair,
+/// over `<METHOD> <path> ` followed by the Blake3 hash of the body.
+const PUBKY_SIGNATURE_HEADER: &str = "x-pubky-signature";
+
+/// Verify the [PUBKY_SIGNATURE_HEADER] if present, or if required by
+/// [CoreConfig::require_signed_writes].
+fn verify_signature(
+    config: &CoreConfig,
+    headers: &HeaderMap,
+    public_key: &PublicKey,
+    method: &str,
+    path: &str,
+    body_hash: &Hash,
+) -> Result<()> {
+    let Some(value) = headers.get(PUBKY_SIGNATURE_HEADER) else {
+        if config.require_signed_writes {
+            return Err(Error::new(
+                StatusCode::UNAUTHORIZED,
+                Some(format!("Missing {PUBKY_SIGNATURE_HEADER} header")),
+            ));
+        }
+
+        return Ok(());
+    };
+
+    let signature = value
+        .to_str()
+        .ok()
+        .and_then(|s| hex::decode(s).ok())
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .ok_or(Error::new(
+            StatusCode::BAD_REQUEST,
+            Some(format!("Invalid {PUBKY_SIGNATURE_HEADER} header")),
+        ))?;
+
+    let mut signable = format!("{method} {path} ").into_bytes();
+    signable.extend_from_slice(body_hash.as_bytes());
+
+    public_key.verify(&signable, &signature).map_err(|_| {
+        Error::new(
+            StatusCode::FORBIDDEN,
+            Some("Invalid request signature".to_string()),
+        )
+    })
+}
+
+/// Content type of JSON bodies to store in canonical form, with object keys sorted
+/// and no insignificant whitespace, so equivalent JSON has the same content hash.
+///
+/// Stored as `application/json`.
+const CANONICAL_JSON_CONTENT_TYPE: &str = "application/json+canonical";
+
+/// Read a JSON body and re-serialize it canonically, see [CANONICAL_JSON_CONTENT_TYPE].
+async fn canonicalize_json(body: Body, max_entry_size: usize) -> Result<Body> {
+    let json = read_body(body, max_entry_size, entry_too_large).await?;
+
+    let value: serde_json::Value = serde_json::from_slice(&json).map_err(|error| {
+        Error::new(
+            StatusCode::BAD_REQUEST,
+            Some(format!("Invalid JSON: {error}")),
+        )
+    })?;
+
+    let mut canonical = Vec::with_capacity(json.len());
+    write_canonical_json(&value, &mut canonical);
+
+    Ok(Body::from(canonical))
+}
+
+/// Content type of entries redirecting to another entry, whose content is the `pubky://`
+/// url of the target, served as `302 Found` by [super::read::get].
+pub const REDIRECT_CONTENT_TYPE: &str = "application/x-pubky-redirect";
+
+/// Maximum length of the target url of a redirect, in bytes.
+const MAX_REDIRECT_LENGTH: usize = 2048;
+
+/// Check that the body of a redirect is a valid `pubky://` url,
+/// see [REDIRECT_CONTENT_TYPE].
+async fn check_redirect(body: Body) -> Result<Body> {
+    let invalid = || {
+        Error::new(
+            StatusCode::BAD_REQUEST,
+            Some("Redirects must contain a single pubky:// url"),
+        )
+    };
+
+    let target = read_body(body, MAX_REDIRECT_LENGTH, |_| {
+        Error::new(
+            StatusCode::BAD_REQUEST,
+            Some(format!(
+                "Redirect targets exceed the maximum length of {MAX_REDIRECT_LENGTH} bytes"
+            )),
+        )
+    })
+    .await?;
+
+    let url = std::str::from_utf8(&target)
+        .ok()
+        .and_then(|target| url::Url::parse(target).ok())
+        .ok_or_else(invalid)?;
+
+    if url.scheme() != "pubky" || url.as_str().as_bytes() != target {
+        return Err(invalid());
+    }
+
+    Ok(Body::from(target))
+}
+
+/// Read a whole body, failing with `too_large(max_size)` once it exceeds `max_size` bytes.
+async fn read_body(
+    body: Body,
+    max_size: usize,
+    too_large: impl Fn(usize) -> Error,
+) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    let mut stream = body.into_data_stream();
+    while let Some(next) = stream.next().await {
+        let chunk = next?;
+
+        if bytes.len() + chunk.len() > max_size {
+            return Err(too_large(max_size));
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+fn write_canonical_json(value: &serde_json::Value, out: &mut Vec<u8>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(a, _)| *a);
+
+            out.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key).expect("writing to a vec is infallible");
+                out.push(b':');
+                write_canonical_json(value, out);
+            }
+            out.push(b'}');
+        }
+        serde_json::Value::Array(values) => {
+            out.push(b'[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical_json(value, out);
+            }
+            out.push(b']');
+        }
+        value => serde_json::to_writer(&mut *out, value).expect("writing to a vec is infallible"),
+    }
+}
+
+fn entry_too_large(max_entry_size: usize) -> Error {
+    Error::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Some(format!(
+            "Entry exceeds the maximum size of {max_entry_size} bytes"
+        )),
+    )
+}
+
+fn path_too_long(max_path_length: usize) -> Error {
+    Error::new(
+        StatusCode::BAD_REQUEST,
+        Some(format!(
+            "Path exceeds the maximum length of {max_path_length} bytes"
+        )),
+    )
+}
+
+fn immutable_conflict() -> Error {
+    Error::new(
+        StatusCode::CONFLICT,
+        Some("Immutable entries can't be overwritten"),
+    )
+}
+
+fn quota_exceeded() -> Error {
+    Error::new(
+        StatusCode::INSUFFICIENT_STORAGE,
+        Some("User storage quota exceeded".to_string()),
+    )
+}
+
+/// Reject entries whose declared or sniffed content type is not allowed by the
+/// [CoreConfig::allowed_content_types] and [CoreConfig::denied_content_types].
+///
+/// Entries with no known content type are checked as `application/octet-stream`.
+fn check_content_types(
+    config: &CoreConfig,
+    declared: Option<&str>,
+    sniffed: Option<&str>,
+) -> Result<()> {
+    let content_types = match (declared, sniffed) {
+        (None, None) => vec!["application/octet-stream"],
+        _ => declared.into_iter().chain(sniffed).collect(),
+    };
+
+    for content_type in content_types {
+        let matches = |pattern: &String| match pattern.strip_suffix("/*") {
+            Some(prefix) => content_type.split('/').next() == Some(prefix),
+            None => pattern.eq_ignore_ascii_case(content_type),
+        };
+
+        if config.denied_content_types.iter().any(matches)
+            || config
+                .allowed_content_types
+                .as_ref()
+                .is_some_and(|allowed| !allowed.iter().any(matches))
+        {
+            return Err(Error::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Some(format!("Content type {content_type} is not allowed")),
+            ));
+        }
+    }
 
     Ok(())
 }
+
+/// Returns the list of ETags (or `*`) in a conditional header like `If-Match`.
+fn etags(headers: &HeaderMap, name: HeaderName) -> Option<Vec<String>> {
+    headers
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.split(',').map(|etag| etag.trim().to_string()).collect())
+}
+
+fn matches_any(etags: &[String], etag: &str) -> bool {
+    etags.iter().any(|e| e == "*" || e == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use bytes::Bytes;
+    use pkarr::Keypair;
+
+    use crate::core::{CoreConfig, HomeserverCore};
+
+    #[tokio::test]
+    async fn max_entry_size() {
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                max_entry_size: 4,
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/pub/foo")
+                    .method(Method::PUT)
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::from(vec![1_u8, 2, 3, 4]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/pub/bar")
+                    .method(Method::PUT)
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::from(vec![1_u8, 2, 3, 4, 5]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn multipart_all_or_nothing() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let multipart = |files: &[(&str, &str)]| {
+            let mut body = String::new();
+            for (name, content) in files {
+                body.push_str(&format!(
+                    "--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\nContent-Type: text/plain\r\n\r\n{content}\r\n"
+                ));
+            }
+            body.push_str("--boundary--\r\n");
+
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/dir/")
+                .method(Method::POST)
+                .header(header::COOKIE, &cookie)
+                .header(
+                    header::CONTENT_TYPE,
+                    "multipart/form-data; boundary=boundary",
+                )
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        // The second part has an invalid name, so the first isn't stored either.
+        let response = server
+            .call(multipart(&[("a.txt", "foo"), ("..", "bar")]))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/pub/dir/a.txt")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = server
+            .call(multipart(&[("a.txt", "foo"), ("b.txt", "bar")]))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for file in ["a.txt", "b.txt"] {
+            let response = server
+                .call(
+                    Request::builder()
+                        .header("host", public_key.to_string())
+                        .uri(format!("/pub/dir/{file}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn sanitize_file_name() {
+        use super::sanitize_file_name;
+
+        assert_eq!(
+            sanitize_file_name("photo.jpg").as_deref(),
+            Some("photo.jpg")
+        );
+        assert_eq!(
+            sanitize_file_name("my photo (1).jpg").as_deref(),
+            Some("my_photo__1_.jpg")
+        );
+        assert_eq!(
+            sanitize_file_name("../../priv/secret").as_deref(),
+            Some("secret")
+        );
+        assert_eq!(
+            sanitize_file_name("C:\\Users\\me\\notes.txt").as_deref(),
+            Some("notes.txt")
+        );
+        assert_eq!(sanitize_file_name("dir/.."), None);
+        assert_eq!(sanitize_file_name(""), None);
+    }
+
+    #[tokio::test]
+    async fn max_path_length() {
+        let max_path_length = 32;
+
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                max_path_length,
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let put = |path: String| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri(path)
+                .method(Method::PUT)
+                .header(header::COOKIE, &cookie)
+                .body(Body::from(vec![1_u8]))
+                .unwrap()
+        };
+
+        let at_limit = format!("/pub/{}", "a".repeat(max_path_length - "/pub/".len()));
+        assert_eq!(at_limit.len(), max_path_length);
+
+        let response = server.call(put(at_limit)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let too_long = format!("/pub/{}", "a".repeat(max_path_length - "/pub/".len() + 1));
+
+        let response = server.call(put(too_long)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn conditional_delete() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/pub/foo")
+                    .method(Method::PUT)
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::from(vec![1]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let delete = |condition: (header::HeaderName, String)| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/foo")
+                .method(Method::DELETE)
+                .header(header::COOKIE, &cookie)
+                .header(condition.0, condition.1)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let past = "Thu, 01 Jan 1970 00:00:00 GMT".to_string();
+
+        let response = server
+            .call(delete((header::IF_UNMODIFIED_SINCE, past)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let response = server
+            .call(delete((header::IF_MATCH, "\"other\"".to_string())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let future = httpdate::fmt_http_date(
+            std::time::SystemTime::now() + std::time::Duration::from_secs(3600),
+        );
+
+        let response = server
+            .call(delete((header::IF_UNMODIFIED_SINCE, future)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn conditional_put() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let put = |body: &[u8], condition: (header::HeaderName, String)| {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/foo")
+                .method(Method::PUT)
+                .header(header::COOKIE, &cookie)
+                .header(condition.0, condition.1)
+                .body(Body::from(body.to_vec()))
+                .unwrap()
+        };
+
+        let response = server
+            .call(put(&[1], (header::IF_NONE_MATCH, "*".to_string())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server
+            .call(put(&[2], (header::IF_NONE_MATCH, "*".to_string())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let etag = format!("\"{}\"", pubky_common::crypto::hash(&[1]));
+
+        let response = server
+            .call(put(&[3], (header::IF_MATCH, etag.clone())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server
+            .call(put(&[4], (header::IF_MATCH, etag)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let past = "Thu, 01 Jan 1970 00:00:00 GMT".to_string();
+
+        let response = server
+            .call(put(&[5], (header::IF_UNMODIFIED_SINCE, past)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let future = httpdate::fmt_http_date(
+            std::time::SystemTime::now() + std::time::Duration::from_secs(3600),
+        );
+
+        let response = server
+            .call(put(&[6], (header::IF_UNMODIFIED_SINCE, future)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn content_types() {
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                allowed_content_types: Some(vec!["image/*".into(), "text/plain".into()]),
+                denied_content_types: vec!["image/gif".into()],
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let put = |body: &[u8], content_type: Option<&str>| {
+            let mut builder = Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/foo")
+                .method(Method::PUT)
+                .header(header::COOKIE, &cookie);
+
+            if let Some(content_type) = content_type {
+                builder = builder.header(header::CONTENT_TYPE, content_type);
+            }
+
+            builder.body(Body::from(body.to_vec())).unwrap()
+        };
+
+        let png = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0];
+        let gif = b"GIF89a\0\0\0\0";
+        // ELF is only detected past the 64 bit header size.
+        let mut elf = [0_u8; 64];
+        elf[..4].copy_from_slice(&[0x7F, 0x45, 0x4C, 0x46]);
+
+        // Allowed
+        for (body, content_type) in [
+            (&png[..], None),
+            (&png[..], Some("image/png")),
+            (&b"hello"[..], Some("text/plain; charset=utf-8")),
+        ] {
+            let response = server.call(put(body, content_type)).await.unwrap();
+            assert!(response.status().is_success(), "{content_type:?}");
+        }
+
+        // Denied
+        for (body, content_type) in [
+            (&gif[..], None),
+            (&elf[..], None),
+            (&elf[..], Some("text/plain")),
+            (&b"hello"[..], Some("application/json")),
+            (&b"hello"[..], None),
+        ] {
+            let response = server.call(put(body, content_type)).await.unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "{content_type:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn quota_rejected_before_body() {
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                user_storage_quota: Some(50),
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let polled = Arc::new(AtomicBool::new(false));
+
+        let put = |path: &str, length: usize| {
+            let polled = polled.clone();
+            let stream = futures_util::stream::once(async move {
+                polled.store(true, Ordering::SeqCst);
+
+                Ok::<_, std::io::Error>(Bytes::from(vec![0; length]))
+            });
+
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri(path)
+                .method(Method::PUT)
+                .header(header::COOKIE, &cookie)
+                .header(header::CONTENT_LENGTH, length)
+                .header(header::EXPECT, "100-continue")
+                .body(Body::from_stream(stream))
+                .unwrap()
+        };
+
+        let response = server.call(put("/pub/a", 100)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        assert!(!polled.load(Ordering::SeqCst), "body should not be read");
+
+        let response = server.call(put("/pub/a", 40)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(polled.swap(false, Ordering::SeqCst));
+
+        let response = server.call(put("/pub/b", 40)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        assert!(!polled.load(Ordering::SeqCst), "body should not be read");
+
+        // Overwriting frees the current entry's size.
+        let response = server.call(put("/pub/a", 45)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn ttl() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/pub/otp")
+                    .method(Method::PUT)
+                    .header(header::COOKIE, &cookie)
+                    .header("x-pubky-ttl", "1")
+                    .body(Body::from(vec![1, 2, 3]))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let get = || {
+            Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/otp")
+                .method(Method::GET)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = server.call(get()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Let the TTL pass.
+        let key = format!("{public_key}/pub/otp");
+        let mut wtxn = server.db.env.write_txn().unwrap();
+        let expires_at = server
+            .db
+            .tables
+            .expirations
+            .get(&wtxn, &key)
+            .unwrap()
+            .unwrap();
+        server
+            .db
+            .tables
+            .expirations
+            .put(&mut wtxn, &key, &(expires_at - 1_000_000))
+            .unwrap();
+        wtxn.commit().unwrap();
+
+        let response = server.call(get()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn immutable() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let request = |method: Method, immutable: bool, body: Vec<u8>| {
+            let mut request = Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/audit.log")
+                .method(method)
+                .header(header::COOKIE, &cookie);
+
+            if immutable {
+                request = request.header("x-immutable", "true");
+            }
+
+            request.body(Body::from(body)).unwrap()
+        };
+
+        let response = server
+            .call(request(Method::PUT, true, vec![1, 2, 3]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = server
+            .call(request(Method::PUT, false, vec![4, 5, 6]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = server
+            .call(request(Method::PUT, true, vec![4, 5, 6]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = server
+            .call(request(Method::DELETE, false, vec![]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = server
+            .call(request(Method::GET, false, vec![]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), &[1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn signed_writes() {
+        let mut server = unsafe {
+            HomeserverCore::new(CoreConfig {
+                require_signed_writes: true,
+                ..CoreConfig::test()
+            })
+        }
+        .unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let sign = |method: &str, body: &[u8]| {
+            let mut signable = format!("{method} /pub/foo ").into_bytes();
+            signable.extend_from_slice(pubky_common::crypto::hash(body).as_bytes());
+
+            hex::encode(keypair.sign(&signable).to_bytes())
+        };
+
+        let request = |method: Method, signature: Option<String>| {
+            let mut request = Request::builder()
+                .header("host", public_key.to_string())
+                .uri("/pub/foo")
+                .method(method.clone())
+                .header(header::COOKIE, &cookie);
+
+            if let Some(signature) = signature {
+                request = request.header("x-pubky-signature", signature);
+            }
+
+            request
+                .body(if method == Method::PUT {
+                    Body::from(vec![1, 2, 3])
+                } else {
+                    Body::empty()
+                })
+                .unwrap()
+        };
+
+        // Unsigned
+        let response = server.call(request(Method::PUT, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Signed over a different body
+        let response = server
+            .call(request(Method::PUT, Some(sign("PUT", &[4, 5, 6]))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Signed by a different keypair
+        let mut signable = b"PUT /pub/foo ".to_vec();
+        signable.extend_from_slice(pubky_common::crypto::hash(&[1, 2, 3]).as_bytes());
+        let signature = hex::encode(Keypair::random().sign(&signable).to_bytes());
+
+        let response = server
+            .call(request(Method::PUT, Some(signature)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = server
+            .call(request(Method::PUT, Some(sign("PUT", &[1, 2, 3]))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // A PUT signature can't be replayed as a DELETE
+        let response = server
+            .call(request(Method::DELETE, Some(sign("PUT", &[]))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = server
+            .call(request(Method::DELETE, Some(sign("DELETE", &[]))))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn canonical_json() {
+        let mut server = HomeserverCore::test().unwrap();
+
+        let keypair = Keypair::random();
+        let public_key = keypair.public_key();
+        let cookie = server.create_root_user(&keypair).await.unwrap().to_string();
+
+        let mut etags = vec![];
+
+        for (path, json) in [
+            ("/pub/a.json", r#"{"b":1,"a":[true,{"d":null,"c":"x"}]}"#),
+            (
+                "/pub/b.json",
+                "{ \"a\": [ true, { \"c\": \"x\", \"d\": null } ],\n  \"b\": 1 }",
+            ),
+        ] {
+            let response = server
+                .call(
+                    Request::builder()
+                        .header("host", public_key.to_string())
+                        .uri(path)
+                        .method(Method::PUT)
+                        .header(header::COOKIE, &cookie)
+                        .header(header::CONTENT_TYPE, "application/json+canonical")
+                        .body(Body::from(json))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            let response = server
+                .call(
+                    Request::builder()
+                        .header("host", public_key.to_string())
+                        .uri(path)
+                        .method(Method::GET)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                response.headers().get(header::CONTENT_TYPE).unwrap(),
+                "application/json"
+            );
+            etags.push(response.headers().get(header::ETAG).unwrap().clone());
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body.as_ref(), br#"{"a":[true,{"c":"x","d":null}],"b":1}"#);
+        }
+
+        assert_eq!(etags[0], etags[1]);
+
+        let response = server
+            .call(
+                Request::builder()
+                    .header("host", public_key.to_string())
+                    .uri("/pub/c.json")
+                    .method(Method::PUT)
+                    .header(header::COOKIE, &cookie)
+                    .header(header::CONTENT_TYPE, "application/json+canonical")
+                    .body(Body::from("{\"a\":"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -3,6 +3,7 @@
 use std::{
     net::{SocketAddr, TcpListener},
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::Result;
@@ -13,9 +14,13 @@ use axum_server::{
 };
 use futures_util::TryFutureExt;
 use pkarr::Keypair;
+use tokio::task::JoinHandle;
 
 use super::IoConfig;
 
+/// How long to wait for in-flight requests to finish during a graceful shutdown.
+const GRACEFUL_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct HttpServers {
     /// Handle for the HTTP server
@@ -23,6 +28,9 @@ pub struct HttpServers {
     /// Handle for the HTTPS server using Pkarr TLS
     pub(crate) https_handle: Handle,
 
+    http_task: JoinHandle<Result<(), ()>>,
+    https_task: JoinHandle<Result<(), ()>>,
+
     http_address: SocketAddr,
     https_address: SocketAddr,
 }
@@ -34,7 +42,7 @@ impl HttpServers {
 
         let http_handle = Handle::new();
 
-        tokio::spawn(
+        let http_task = tokio::spawn(
             axum_server::from_tcp(http_listener)
                 .handle(http_handle.clone())
                 .serve(
@@ -51,7 +59,7 @@ impl HttpServers {
 
         let https_handle = Handle::new();
 
-        tokio::spawn(
+        let https_task = tokio::spawn(
             axum_server::from_tcp(https_listener)
                 .acceptor(RustlsAcceptor::new(RustlsConfig::from_config(Arc::new(
                     keypair.to_rpk_rustls_server_config(),
@@ -69,6 +77,9 @@ impl HttpServers {
             http_handle,
             https_handle,
 
+            http_task,
+            https_task,
+
             http_address,
             https_address,
         })
@@ -82,9 +93,15 @@ impl HttpServers {
         self.https_address
     }
 
-    /// Shutdown all HTTP servers.
-    pub fn shutdown(&self) {
-        self.http_handle.shutdown();
-        self.https_handle.shutdown();
+    /// Stop accepting new connections, and wait for in-flight requests
+    /// to finish (up to a timeout) before shutting down all HTTP servers.
+    pub async fn shutdown(self) {
+        self.http_handle
+            .graceful_shutdown(Some(GRACEFUL_SHUTDOWN_TIMEOUT));
+        self.https_handle
+            .graceful_shutdown(Some(GRACEFUL_SHUTDOWN_TIMEOUT));
+
+        let _ = self.http_task.await;
+        let _ = self.https_task.await;
     }
 }
@@ -8,16 +8,20 @@ use ::pkarr::{Keypair, PublicKey};
 use anyhow::Result;
 use http::HttpServers;
 use pkarr::PkarrServer;
+use pubky_common::capabilities::Capability;
 use tracing::info;
 
 use crate::{
     config::{Config, DEFAULT_HTTPS_PORT, DEFAULT_HTTP_PORT},
-    core::HomeserverCore,
+    core::{HomeserverCore, RateLimiterConfig, ReadThrough},
 };
 
 mod http;
 mod pkarr;
 
+/// How often expired entries are deleted from the database.
+const EXPIRED_ENTRIES_GC_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Default)]
 /// Builder for [Homeserver].
 pub struct HomeserverBuilder(Config);
@@ -58,6 +62,138 @@ impl HomeserverBuilder {
         self
     }
 
+    /// Set the default limit of a list api if no `limit` query parameter is provided.
+    pub fn default_list_limit(&mut self, limit: u16) -> &mut Self {
+        self.0.core.default_list_limit = limit;
+
+        self
+    }
+
+    /// Set the maximum limit of a list api, even if a `limit` query parameter is provided.
+    pub fn max_list_limit(&mut self, limit: u16) -> &mut Self {
+        self.0.core.max_list_limit = limit;
+
+        self
+    }
+
+    /// Set the maximum size of an entry's content in bytes.
+    ///
+    /// Writes exceeding this size are rejected with `413 Payload Too Large`.
+    pub fn max_entry_size(&mut self, max_entry_size: usize) -> &mut Self {
+        self.0.core.max_entry_size = max_entry_size;
+
+        self
+    }
+
+    /// Set the maximum length of an entry's path in bytes.
+    pub fn max_path_length(&mut self, max_path_length: usize) -> &mut Self {
+        self.0.core.max_path_length = max_path_length;
+
+        self
+    }
+
+    /// Only allow storing entries of these content types (like `image/png` or `image/*`).
+    ///
+    /// Writes of other types are rejected with `415 Unsupported Media Type`.
+    pub fn allowed_content_types(&mut self, content_types: Vec<String>) -> &mut Self {
+        self.0.core.allowed_content_types = Some(content_types);
+
+        self
+    }
+
+    /// Never allow storing entries of these content types (like `application/x-executable`).
+    ///
+    /// Writes of these types are rejected with `415 Unsupported Media Type`.
+    pub fn denied_content_types(&mut self, content_types: Vec<String>) -> &mut Self {
+        self.0.core.denied_content_types = content_types;
+
+        self
+    }
+
+    /// Set the maximum total size of entries stored by each user, in bytes.
+    ///
+    /// Writes exceeding the quota are rejected with `507 Insufficient Storage`,
+    /// before receiving the body if the request declares its `Content-Length`.
+    pub fn user_storage_quota(&mut self, quota: u64) -> &mut Self {
+        self.0.core.user_storage_quota = Some(quota);
+
+        self
+    }
+
+    /// Expire sessions after `ttl` unless they are refreshed.
+    pub fn session_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.0.core.session_ttl = Some(ttl);
+
+        self
+    }
+
+    /// Set the maximum lifetime of a session since its creation,
+    /// no matter how many times it is refreshed.
+    pub fn max_session_lifetime(&mut self, lifetime: Duration) -> &mut Self {
+        self.0.core.max_session_lifetime = Some(lifetime);
+
+        self
+    }
+
+    /// Only allow these Pubkys to signup and signin, responding with `403 Forbidden` to others.
+    pub fn allowed_pubkys(&mut self, pubkys: Vec<PublicKey>) -> &mut Self {
+        self.0.core.allowed_pubkys = Some(pubkys);
+
+        self
+    }
+
+    /// Grant sessions at most these capabilities, whatever their auth token requests,
+    /// see [crate::core::CoreConfig::max_capabilities].
+    pub fn max_capabilities(&mut self, capabilities: Vec<Capability>) -> &mut Self {
+        self.0.core.max_capabilities = Some(capabilities);
+
+        self
+    }
+
+    /// Reject writes to entries without a valid signature by the tenant's keypair,
+    /// see [crate::core::CoreConfig::require_signed_writes].
+    pub fn require_signed_writes(&mut self, require: bool) -> &mut Self {
+        self.0.core.require_signed_writes = require;
+
+        self
+    }
+
+    /// Serve entries absent locally from another Pubky's homeserver, see [ReadThrough].
+    pub fn read_through(&mut self, read_through: ReadThrough) -> &mut Self {
+        self.0.core.read_through.push(read_through);
+
+        self
+    }
+
+    /// Allow shared caches to serve `/pub/` entries for `max_age` without revalidating them.
+    pub fn public_cache_max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.0.core.public_cache_max_age = Some(max_age);
+
+        self
+    }
+
+    /// Set how long events stay in each user's event log.
+    pub fn user_event_log_max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.0.core.user_event_log_max_age = max_age;
+
+        self
+    }
+
+    /// Only allow cross-origin requests from these origins (like `https://example.com`).
+    pub fn cors_allowed_origins(&mut self, origins: Vec<String>) -> &mut Self {
+        self.0.core.cors_allowed_origins = Some(origins);
+
+        self
+    }
+
+    /// Rate limit requests by IP address, responding with `429 Too Many Requests`
+    /// to clients exceeding their quota.
+    pub fn rate_limiter(&mut self, config: RateLimiterConfig) -> &mut Self {
+        self.0.core.rate_limiter = Some(config);
+
+        self
+    }
+
     /// Run a Homeserver
     ///
     /// # Safety
@@ -71,8 +207,11 @@ impl HomeserverBuilder {
 #[derive(Debug)]
 /// Homeserver Core + I/O (http server and pkarr publishing).
 pub struct Homeserver {
+    core: HomeserverCore,
     http_servers: HttpServers,
     keypair: Keypair,
+    expired_entries_gc: tokio::task::JoinHandle<()>,
+    rate_limiter_gc: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl Homeserver {
@@ -128,9 +267,35 @@ impl Homeserver {
 
         info!("Homeserver listening on https://{}", keypair.public_key());
 
+        let expired_entries_gc = tokio::spawn({
+            let mut db = core.db.clone();
+
+            async move {
+                let mut interval = tokio::time::interval(EXPIRED_ENTRIES_GC_INTERVAL);
+
+                loop {
+                    interval.tick().await;
+
+                    match db.delete_expired_entries() {
+                        Ok(0) => {}
+                        Ok(deleted) => tracing::debug!(deleted, "Deleted expired entries"),
+                        Err(error) => tracing::warn!(?error, "Failed to delete expired entries"),
+                    }
+                }
+            }
+        });
+
+        let rate_limiter_gc = core
+            .rate_limiter
+            .clone()
+            .map(|rate_limiter| tokio::spawn(rate_limiter.run_gc()));
+
         Ok(Self {
+            core,
             http_servers,
             keypair,
+            expired_entries_gc,
+            rate_limiter_gc,
         })
     }
 
@@ -148,9 +313,19 @@ impl Homeserver {
 
     // === Public Methods ===
 
-    /// Send a shutdown signal to all open resources
-    pub fn shutdown(&self) {
-        self.http_servers.shutdown();
+    /// Gracefully shutdown the Homeserver.
+    ///
+    /// Stops accepting new connections, waits for in-flight requests to finish,
+    /// then flushes the database to disk before returning.
+    pub async fn shutdown(self) -> Result<()> {
+        self.expired_entries_gc.abort();
+        if let Some(rate_limiter_gc) = self.rate_limiter_gc {
+            rate_limiter_gc.abort();
+        }
+
+        self.http_servers.shutdown().await;
+
+        self.core.shutdown()
     }
 }
 
@@ -181,3 +356,107 @@ impl Default for IoConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pubky_common::auth::AuthToken;
+    use reqwest::{header, StatusCode};
+
+    use crate::core::CoreConfig;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn run_with_config() {
+        let testnet = mainline::Testnet::new(3).unwrap();
+
+        let mut config = Config::try_from_str(
+            r#"
+[io]
+http_port = 0
+https_port = 0
+
+[core]
+max_entry_size = 4
+cors_allowed_origins = ["https://example.com"]
+
+[core.rate_limiter]
+per_second = 60
+burst_size = 5
+            "#,
+        )
+        .unwrap();
+        config.io.bootstrap = Some(testnet.bootstrap.clone());
+        config.core.storage = CoreConfig::test().storage;
+
+        let server = unsafe { Homeserver::run(config) }.await.unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key().to_string();
+
+        let url = format!(
+            "http://localhost:{}",
+            server.http_servers.http_address().port()
+        );
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{url}/signup"))
+            .header("pubky-host", &pubky)
+            .body(AuthToken::sign(&keypair, vec![Capability::root()]).serialize())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let put = |body: &'static [u8]| {
+            client
+                .put(format!("{url}/pub/foo"))
+                .header("pubky-host", &pubky)
+                .header(header::COOKIE, &cookie)
+                .header(header::ORIGIN, "https://other.com")
+                .body(body)
+                .send()
+        };
+
+        let response = put(&[0; 5]).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = put(&[0; 4]).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(!response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        // Using up the rest of the burst of 5 requests.
+        for _ in 0..2 {
+            let response = client.get(&url).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let rate_limiter_gc = server.rate_limiter_gc.as_ref().unwrap().abort_handle();
+        assert!(!rate_limiter_gc.is_finished());
+
+        server.shutdown().await.unwrap();
+
+        // The rate limiter's garbage collection stops with the server.
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !rate_limiter_gc.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+}
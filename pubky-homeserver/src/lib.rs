@@ -9,5 +9,7 @@ mod config;
 mod core;
 mod io;
 
+pub use core::RateLimiterConfig;
+pub use core::ReadThrough;
 pub use io::Homeserver;
 pub use io::HomeserverBuilder;
@@ -107,7 +107,7 @@ test("forbidden", async (t) => {
 
   const body = (JSON.stringify({ foo: 'bar' }))
 
-  let url = `pubky://${publicKey.z32()}/priv/example.com/arbitrary`;
+  let url = `pubky://${publicKey.z32()}/other/example.com/arbitrary`;
 
   // PUT public data, by authorized client
   let response = await client.fetch(url, {
@@ -117,7 +117,7 @@ test("forbidden", async (t) => {
   });
 
   t.is(response.status, 403)
-  t.is(await response.text(), 'Writing to directories other than \'/pub/\' is forbidden')
+  t.is(await response.text(), 'Writing to directories other than \'/pub/\' or \'/priv/\' is forbidden')
 })
 
 test("list", async (t) => {
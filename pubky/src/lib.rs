@@ -22,9 +22,18 @@ pub mod native;
 #[cfg(wasm_browser)]
 mod wasm;
 
+#[cfg(not(wasm_browser))]
+pub use crate::native::api::{
+    alias::AliasProvider, public::EntryStream, republish::RepublishDaemon,
+};
 #[cfg(not(wasm_browser))]
 pub use crate::native::Client;
-pub use crate::native::{api::auth::AuthRequest, api::public::ListBuilder, ClientBuilder};
+pub use crate::native::{
+    api::auth::AuthRequest, api::event_log::LogEvent, api::public::ListBuilder,
+    api::public::ListEntry, api::public::Listing, api::public::PutOutcome, api::sync::SyncSummary,
+    ClientBuilder,
+};
+pub use crate::native::{PUBKY_VERSION, PUBKY_VERSION_HEADER};
 
 #[cfg(wasm_browser)]
 pub use native::Client as NativeClient;
@@ -40,4 +49,7 @@ pub mod errors {
 
     #[cfg(not(wasm_browser))]
     pub use native::BuildError;
+    pub use native::{
+        ErrorKind, Forbidden, HttpError, MethodNotAllowed, ResolutionError, UnsupportedMediaType,
+    };
 }
@@ -1,31 +1,84 @@
 pub mod internal {
     #[cfg(not(wasm_browser))]
     pub mod cookies;
+    #[cfg(all(test, not(wasm_browser)))]
+    pub mod mock_homeserver;
+    #[cfg(all(test, not(wasm_browser)))]
+    pub mod mock_relay;
     pub mod pkarr;
 }
 pub mod api {
+    #[cfg(not(wasm_browser))]
+    pub mod alias;
     pub mod auth;
     #[cfg(not(wasm_browser))]
+    pub mod download;
+    pub mod event_log;
+    #[cfg(not(wasm_browser))]
     pub mod http;
+    #[cfg(not(wasm_browser))]
+    pub mod offline;
     pub mod public;
+    #[cfg(not(wasm_browser))]
+    pub mod republish;
+    pub mod resolve;
+    #[cfg(not(wasm_browser))]
+    pub mod rotate;
+    pub mod sync;
 }
 
 use std::fmt::Debug;
 
 #[cfg(not(wasm_browser))]
-use std::sync::Arc;
+use std::collections::HashMap;
+#[cfg(not(wasm_browser))]
+use std::path::PathBuf;
+#[cfg(not(wasm_browser))]
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
+#[cfg(not(wasm_browser))]
+use std::time::Instant;
 
 static DEFAULT_USER_AGENT: &str = concat!("pubky.org", "@", env!("CARGO_PKG_VERSION"),);
 
+/// Header carrying the wire protocol version, sent with every request and echoed by homeservers.
+pub const PUBKY_VERSION_HEADER: &str = "x-pubky-version";
+/// The wire protocol version spoken by this client.
+pub const PUBKY_VERSION: u8 = 0;
+
 #[macro_export]
 macro_rules! handle_http_error {
+    // Also forget the cached session of the response's Pubky if it was rejected.
+    ($res:expr, $client:expr) => {
+        $client.forget_session_if_unauthorized(&$res);
+
+        $crate::handle_http_error!($res);
+    };
     ($res:expr) => {
-        if let Err(status) = $res.error_for_status_ref() {
-            return match $res.text().await {
-                Ok(text) => Err(anyhow::anyhow!("{status}. Error message: {text}")),
-                _ => Err(anyhow::anyhow!("{status}")),
-            };
+        if let Some(error) = $crate::errors::MethodNotAllowed::from_response(&$res) {
+            return Err(error.into());
+        }
+
+        if $res.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err($crate::errors::Forbidden {
+                message: $res.text().await.unwrap_or_default(),
+            }
+            .into());
+        }
+
+        if $res.status() == reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            return Err($crate::errors::UnsupportedMediaType {
+                message: $res.text().await.unwrap_or_default(),
+            }
+            .into());
+        }
+
+        if $res.status().is_client_error() || $res.status().is_server_error() {
+            return Err($crate::errors::HttpError {
+                status: $res.status(),
+                message: $res.text().await.unwrap_or_default(),
+            }
+            .into());
         }
     };
 }
@@ -34,6 +87,22 @@ macro_rules! handle_http_error {
 pub struct ClientBuilder {
     pkarr: pkarr::ClientBuilder,
     http_request_timeout: Option<Duration>,
+    #[cfg(not(wasm_browser))]
+    offline_queue: Option<PathBuf>,
+    #[cfg(not(wasm_browser))]
+    alias_provider: Option<Arc<dyn api::alias::AliasProvider>>,
+    #[cfg(not(wasm_browser))]
+    insecure_tls: bool,
+    #[cfg(not(wasm_browser))]
+    static_homeservers: HashMap<pkarr::PublicKey, url::Url>,
+    #[cfg(not(wasm_browser))]
+    doh_resolver: Option<url::Url>,
+    #[cfg(not(wasm_browser))]
+    pool_idle_timeout: Option<Duration>,
+    #[cfg(not(wasm_browser))]
+    pool_max_idle_per_host: Option<usize>,
+    #[cfg(not(wasm_browser))]
+    session_cache_ttl: Option<Duration>,
 }
 
 impl ClientBuilder {
@@ -73,6 +142,91 @@ impl ClientBuilder {
         self
     }
 
+    #[cfg(not(wasm_browser))]
+    /// Persist writes made with [Client::put_or_queue] and [Client::delete_or_queue]
+    /// in this directory while the homeserver is unreachable,
+    /// to be replayed later with [Client::flush_pending].
+    pub fn offline_queue(&mut self, dir: PathBuf) -> &mut Self {
+        self.offline_queue = Some(dir);
+
+        self
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Resolve aliases passed to [Client::signup_with_alias] and [Client::resolve_pubky]
+    /// that aren't public keys with this provider.
+    pub fn alias_provider(&mut self, provider: Arc<dyn api::alias::AliasProvider>) -> &mut Self {
+        self.alias_provider = Some(provider);
+
+        self
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Accept any TLS certificate from `https://` urls with ICANN domains,
+    /// including self-signed and expired ones.
+    ///
+    /// **For local and testnet development only**, this makes the client
+    /// vulnerable to man-in-the-middle attacks.
+    ///
+    /// Pkarr domains are still verified against their public keys.
+    pub fn insecure_tls(&mut self) -> &mut Self {
+        self.insecure_tls = true;
+
+        self
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Send requests to `homeserver`, and to Pubkys signed up to it by this client,
+    /// to `url` with a `pubky-host` header, instead of resolving them with Pkarr.
+    ///
+    /// Signing up to such a homeserver doesn't publish a Pkarr record.
+    ///
+    /// Meant for tests against local servers without a DHT.
+    pub fn static_homeserver(&mut self, homeserver: pkarr::PublicKey, url: url::Url) -> &mut Self {
+        self.static_homeservers.insert(homeserver, url);
+
+        self
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Resolve homeservers with this DNS-over-HTTPS resolver (like `https://example.com/dns-query`)
+    /// when neither the DHT nor the Pkarr relays have a record.
+    ///
+    /// Unlike Pkarr records, DoH answers are not signed, so the resolver has to be trusted.
+    pub fn doh_resolver(&mut self, url: url::Url) -> &mut Self {
+        self.doh_resolver = Some(url);
+
+        self
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Close idle HTTP connections after `timeout`, instead of Reqwest's default of 90 seconds.
+    pub fn pool_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.pool_idle_timeout = Some(timeout);
+
+        self
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Keep at most `max` idle HTTP connections open per host, instead of Reqwest's default of no limit.
+    pub fn pool_max_idle_per_host(&mut self, max: usize) -> &mut Self {
+        self.pool_max_idle_per_host = Some(max);
+
+        self
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Answer [Client::session] from the last session seen for a Pubky if it was
+    /// seen less than `ttl` ago, instead of always asking its homeserver.
+    ///
+    /// A cached session is forgotten as soon as a request of this client to its Pubky
+    /// is rejected with `401 Unauthorized` or `403 Forbidden`.
+    pub fn session_cache_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.session_cache_ttl = Some(ttl);
+
+        self
+    }
+
     /// Build [Client]
     pub fn build(&self) -> Result<Client, BuildError> {
         let pkarr = self.pkarr.build()?;
@@ -80,49 +234,152 @@ impl ClientBuilder {
         #[cfg(not(wasm_browser))]
         let cookie_store = Arc::new(internal::cookies::CookieJar::default());
 
+        #[cfg(not(wasm_browser))]
+        let http_settings = HttpSettings {
+            request_timeout: self.http_request_timeout,
+            insecure_tls: self.insecure_tls,
+            pool_idle_timeout: self.pool_idle_timeout,
+            pool_max_idle_per_host: self.pool_max_idle_per_host,
+        };
+
+        #[cfg(not(wasm_browser))]
+        let (http, icann_http) = http_settings.build(&pkarr);
+
+        // TODO: change this after Reqwest publish a release with timeout in wasm
+        #[cfg(wasm_browser)]
+        let http = reqwest::Client::builder()
+            .user_agent(DEFAULT_USER_AGENT)
+            .default_headers(default_headers())
+            .build()
+            .expect("config expected to not error");
+
+        Ok(Client {
+            pkarr,
+            http,
+
+            #[cfg(not(wasm_browser))]
+            icann_http,
+            #[cfg(not(wasm_browser))]
+            cookie_store,
+            #[cfg(not(wasm_browser))]
+            offline_queue: self
+                .offline_queue
+                .clone()
+                .map(|dir| Arc::new(api::offline::OfflineQueue::new(dir))),
+            #[cfg(not(wasm_browser))]
+            alias_provider: self.alias_provider.clone(),
+            #[cfg(not(wasm_browser))]
+            inflight_signups: Default::default(),
+            #[cfg(not(wasm_browser))]
+            static_homeservers: Arc::new(RwLock::new(self.static_homeservers.clone())),
+            #[cfg(not(wasm_browser))]
+            doh_resolver: self.doh_resolver.clone(),
+            #[cfg(not(wasm_browser))]
+            sessions: Default::default(),
+            #[cfg(not(wasm_browser))]
+            session_cache_ttl: self.session_cache_ttl,
+            #[cfg(not(wasm_browser))]
+            timeout_override: None,
+            #[cfg(not(wasm_browser))]
+            prefer_minimal: false,
+
+            #[cfg(wasm_browser)]
+            testnet: false,
+        })
+    }
+}
+
+/// Headers sent with every request.
+fn default_headers() -> reqwest::header::HeaderMap {
+    let mut default_headers = reqwest::header::HeaderMap::new();
+    default_headers.insert(PUBKY_VERSION_HEADER, u16::from(PUBKY_VERSION).into());
+
+    default_headers
+}
+
+#[cfg(not(wasm_browser))]
+/// Settings of the HTTP clients of a [Client].
+#[derive(Debug, Clone, Copy)]
+struct HttpSettings {
+    request_timeout: Option<Duration>,
+    insecure_tls: bool,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+}
+
+#[cfg(not(wasm_browser))]
+impl HttpSettings {
+    /// Build the Pkarr and ICANN HTTP clients sharing a cookie store for ICANN websites.
+    fn build(&self, pkarr: &pkarr::Client) -> (reqwest::Client, reqwest::Client) {
         // TODO: allow custom user agent, but force a Pubky user agent information
         let user_agent = DEFAULT_USER_AGENT;
 
-        #[cfg(not(wasm_browser))]
+        let cookie_store = Arc::new(internal::cookies::IcannCookieJar::default());
+
         let mut http_builder = reqwest::ClientBuilder::from(pkarr.clone())
             // TODO: use persistent cookie jar
             .cookie_provider(cookie_store.clone())
-            .user_agent(user_agent);
-
-        #[cfg(wasm_browser)]
-        let http_builder = reqwest::Client::builder().user_agent(user_agent);
+            .user_agent(user_agent)
+            .default_headers(default_headers())
+            .redirect(redirect_policy());
 
-        #[cfg(not(wasm_browser))]
         let mut icann_http_builder = reqwest::Client::builder()
             // TODO: use persistent cookie jar
             .cookie_provider(cookie_store.clone())
-            .user_agent(user_agent);
+            .user_agent(user_agent)
+            .default_headers(default_headers())
+            .redirect(redirect_policy());
 
-        // TODO: change this after Reqwest publish a release with timeout in wasm
-        #[cfg(not(wasm_browser))]
-        if let Some(timeout) = self.http_request_timeout {
+        if let Some(timeout) = self.request_timeout {
             http_builder = http_builder.timeout(timeout);
 
             icann_http_builder = icann_http_builder.timeout(timeout);
         }
 
-        Ok(Client {
-            pkarr,
-            http: http_builder.build().expect("config expected to not error"),
+        if let Some(timeout) = self.pool_idle_timeout {
+            http_builder = http_builder.pool_idle_timeout(timeout);
 
-            #[cfg(not(wasm_browser))]
-            icann_http: icann_http_builder
+            icann_http_builder = icann_http_builder.pool_idle_timeout(timeout);
+        }
+
+        if let Some(max) = self.pool_max_idle_per_host {
+            http_builder = http_builder.pool_max_idle_per_host(max);
+
+            icann_http_builder = icann_http_builder.pool_max_idle_per_host(max);
+        }
+
+        if self.insecure_tls {
+            icann_http_builder = icann_http_builder.danger_accept_invalid_certs(true);
+        }
+
+        (
+            http_builder.build().expect("config expected to not error"),
+            icann_http_builder
                 .build()
                 .expect("config expected to not error"),
-            #[cfg(not(wasm_browser))]
-            cookie_store,
-
-            #[cfg(wasm_browser)]
-            testnet: false,
-        })
+        )
     }
 }
 
+#[cfg(not(wasm_browser))]
+/// Maximum number of redirects followed by HTTP requests, like Reqwest's default.
+const MAX_REDIRECTS: usize = 10;
+
+#[cfg(not(wasm_browser))]
+/// Follow redirects, except to `pubky://` urls of redirect entries,
+/// which are returned as is, see [Client::get_following_redirects].
+fn redirect_policy() -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.url().scheme() == "pubky" {
+            attempt.stop()
+        } else if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum BuildError {
     #[error(transparent)]
@@ -130,6 +387,138 @@ pub enum BuildError {
     PkarrBuildError(#[from] pkarr::errors::BuildError),
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("405 Method Not Allowed. Allowed methods: {}", .allow.join(", "))]
+/// The homeserver does not support the request's method on this path.
+pub struct MethodNotAllowed {
+    /// Methods supported on this path, from the `Allow` header.
+    pub allow: Vec<String>,
+}
+
+impl MethodNotAllowed {
+    /// Returns [MethodNotAllowed] if the response status is `405 Method Not Allowed`.
+    pub fn from_response(response: &reqwest::Response) -> Option<Self> {
+        if response.status() != reqwest::StatusCode::METHOD_NOT_ALLOWED {
+            return None;
+        }
+
+        let allow = response
+            .headers()
+            .get(reqwest::header::ALLOW)
+            .and_then(|h| h.to_str().ok())
+            .map(|allow| {
+                allow
+                    .split(',')
+                    .map(|method| method.trim().to_string())
+                    .filter(|method| !method.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self { allow })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("403 Forbidden. Error message: {message}")]
+/// The homeserver refused the request, for example because the session lacks the
+/// required capabilities, or the Pubky is not allowed to signup to this homeserver.
+pub struct Forbidden {
+    /// Error message from the homeserver.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("415 Unsupported Media Type. Error message: {message}")]
+/// The homeserver does not allow storing entries of this content type.
+pub struct UnsupportedMediaType {
+    /// Error message from the homeserver.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{status}. Error message: {message}")]
+/// The homeserver responded with an error status, for which there is no more specific error.
+pub struct HttpError {
+    /// Status code of the response.
+    pub status: reqwest::StatusCode,
+    /// Error message from the homeserver.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Resolution error: {0}")]
+/// Resolving the homeserver of a Pubky failed, or found no homeserver record.
+pub struct ResolutionError(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The category of an error returned by a [Client] method, see [ErrorKind::of].
+pub enum ErrorKind {
+    /// Connecting to, or communicating with, the server failed.
+    Transport,
+    /// The request timed out, see [ClientBuilder::request_timeout].
+    Timeout,
+    /// Resolving a Pubky or a homeserver with Pkarr failed.
+    Resolution,
+    /// The server responded with an error status.
+    Http {
+        /// Status code of the response.
+        status: reqwest::StatusCode,
+    },
+    /// Anything else, like an invalid url or an unexpected response body.
+    Protocol,
+}
+
+impl ErrorKind {
+    /// Classify an error returned by a [Client] method.
+    pub fn of(error: &anyhow::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            return if error.is_timeout() {
+                Self::Timeout
+            } else if let Some(status) = error.status() {
+                Self::Http { status }
+            } else if error.is_decode() || error.is_builder() {
+                Self::Protocol
+            } else {
+                Self::Transport
+            };
+        }
+
+        let status = if let Some(error) = error.downcast_ref::<HttpError>() {
+            error.status
+        } else if error.is::<Forbidden>() {
+            reqwest::StatusCode::FORBIDDEN
+        } else if error.is::<MethodNotAllowed>() {
+            reqwest::StatusCode::METHOD_NOT_ALLOWED
+        } else if error.is::<UnsupportedMediaType>() {
+            reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        } else if error.is::<ResolutionError>() {
+            return Self::Resolution;
+        } else {
+            return Self::Protocol;
+        };
+
+        Self::Http { status }
+    }
+
+    /// Returns true if retrying the same request later might succeed.
+    ///
+    /// Transport, timeout and resolution errors are transient, as well as server
+    /// errors, `408 Request Timeout` and `429 Too Many Requests`, but other
+    /// client errors (like `403 Forbidden`) and protocol errors are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport | Self::Timeout | Self::Resolution => true,
+            Self::Http { status } => {
+                status.is_server_error()
+                    || *status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::Protocol => false,
+        }
+    }
+}
+
 /// A client for Pubky homeserver API, as well as generic HTTP requests to Pubky urls.
 #[derive(Clone, Debug)]
 pub struct Client {
@@ -140,6 +529,30 @@ pub struct Client {
     pub(crate) cookie_store: std::sync::Arc<internal::cookies::CookieJar>,
     #[cfg(not(wasm_browser))]
     pub(crate) icann_http: reqwest::Client,
+    #[cfg(not(wasm_browser))]
+    pub(crate) offline_queue: Option<Arc<api::offline::OfflineQueue>>,
+    #[cfg(not(wasm_browser))]
+    pub(crate) alias_provider: Option<Arc<dyn api::alias::AliasProvider>>,
+    #[cfg(not(wasm_browser))]
+    pub(crate) inflight_signups: Arc<api::auth::InflightSignups>,
+    #[cfg(not(wasm_browser))]
+    pub(crate) static_homeservers: Arc<RwLock<HashMap<pkarr::PublicKey, url::Url>>>,
+    #[cfg(not(wasm_browser))]
+    pub(crate) doh_resolver: Option<url::Url>,
+    #[cfg(not(wasm_browser))]
+    /// The last session seen for each Pubky signed in with this client, and when,
+    /// see [Client::can] and [ClientBuilder::session_cache_ttl].
+    pub(crate) sessions:
+        Arc<RwLock<HashMap<pkarr::PublicKey, (pubky_common::session::Session, Instant)>>>,
+    #[cfg(not(wasm_browser))]
+    /// See [ClientBuilder::session_cache_ttl].
+    pub(crate) session_cache_ttl: Option<Duration>,
+    #[cfg(not(wasm_browser))]
+    /// Overrides the request timeout of the HTTP clients, see [Client::with_timeout].
+    pub(crate) timeout_override: Option<Duration>,
+    #[cfg(not(wasm_browser))]
+    /// Ask for responses without a body, see [Client::minimal].
+    pub(crate) prefer_minimal: bool,
 
     #[cfg(wasm_browser)]
     pub(crate) testnet: bool,
@@ -157,4 +570,242 @@ impl Client {
     pub fn pkarr(&self) -> &pkarr::Client {
         &self.pkarr
     }
+
+    #[cfg(not(wasm_browser))]
+    /// Returns a client for acting as `pubky` only, with its own cookie store holding
+    /// just the current session of `pubky` (if any), so that signing in or out with
+    /// either client doesn't affect the other's sessions.
+    ///
+    /// HTTP connections, the Pkarr client (and its cache of resolved records) and all
+    /// other settings are shared.
+    pub fn clone_for_pubky(&self, pubky: &pkarr::PublicKey) -> Client {
+        let cookie_store = Arc::new(self.cookie_store.scoped(pubky));
+
+        let sessions = self
+            .sessions
+            .read()
+            .unwrap()
+            .get(pubky)
+            .map(|cached| (pubky.clone(), cached.clone()))
+            .into_iter()
+            .collect();
+
+        Client {
+            cookie_store,
+            inflight_signups: Default::default(),
+            sessions: Arc::new(RwLock::new(sessions)),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(all(test, not(wasm_browser)))]
+mod tests {
+    use std::{
+        collections::HashSet,
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{extract::ConnectInfo, routing::get, Router};
+    use reqwest::StatusCode;
+
+    use pubky_testnet::Testnet;
+
+    use super::{internal::mock_relay::*, Client, ClientBuilder, ErrorKind};
+
+    impl Client {
+        /// Test version of [Testnet::client_builder], building this crate's [Client]
+        /// instead of the one `pubky_testnet` depends on, to access its private items.
+        pub(crate) fn test_builder(testnet: &Testnet) -> ClientBuilder {
+            let relays = testnet.relays();
+
+            let mut builder = Client::builder();
+            builder.pkarr(|builder| {
+                builder
+                    .bootstrap(testnet.bootstrap())
+                    .relays(&relays)
+                    .expect("testnet relays should be valid urls")
+            });
+
+            builder
+        }
+    }
+
+    /// Run a server on a random local port, answering each request after a short delay,
+    /// and returns its url and the peer addresses of the connections it accepted.
+    async fn connections_server() -> (url::Url, Arc<Mutex<HashSet<SocketAddr>>>) {
+        let peers = Arc::new(Mutex::new(HashSet::new()));
+
+        let router = Router::new().route(
+            "/",
+            get({
+                let peers = peers.clone();
+
+                move |ConnectInfo(peer): ConnectInfo<SocketAddr>| async move {
+                    peers.lock().unwrap().insert(peer);
+
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+
+                    "foo"
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = url::Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+
+        (url, peers)
+    }
+
+    /// Builds a client without the default DHT and relays, for local servers.
+    fn local_client_builder(url: &url::Url) -> ClientBuilder {
+        let mut builder = Client::builder();
+        builder.pkarr(|builder| {
+            builder
+                .no_default_network()
+                .relays(std::slice::from_ref(url))
+                .unwrap()
+        });
+
+        builder
+    }
+
+    async fn error_kind(response: MockResponse) -> ErrorKind {
+        let relay = MockRelay::run(vec![response]).await;
+
+        let client = Client::builder()
+            .pkarr(|builder| builder.no_default_network().relays(&[relay.url()]).unwrap())
+            .request_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let error = client.get_text(relay.url()).await.unwrap_err();
+
+        ErrorKind::of(&error)
+    }
+
+    #[tokio::test]
+    async fn error_kinds() {
+        let timeout = error_kind(MockResponse::ok(vec![]).delay(Duration::from_secs(2))).await;
+        assert_eq!(timeout, ErrorKind::Timeout);
+        assert!(timeout.is_retryable());
+
+        let forbidden = error_kind(MockResponse::status(403)).await;
+        assert_eq!(
+            forbidden,
+            ErrorKind::Http {
+                status: StatusCode::FORBIDDEN
+            }
+        );
+        assert!(!forbidden.is_retryable());
+
+        let server_error = error_kind(MockResponse::status(500)).await;
+        assert_eq!(
+            server_error,
+            ErrorKind::Http {
+                status: StatusCode::INTERNAL_SERVER_ERROR
+            }
+        );
+        assert!(server_error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn pool_settings() {
+        let (url, peers) = connections_server().await;
+        let connections = || peers.lock().unwrap().len();
+
+        let client = local_client_builder(&url)
+            .pool_idle_timeout(Duration::from_secs(1))
+            .pool_max_idle_per_host(1)
+            .build()
+            .unwrap();
+
+        let get = || async { client.get_text(url.as_str()).await.unwrap() };
+
+        // Concurrent requests open a connection each.
+        futures_util::future::join_all((0..3).map(|_| get())).await;
+        assert_eq!(connections(), 3);
+
+        // Sequential requests reuse the idle connection.
+        get().await;
+        get().await;
+        assert_eq!(connections(), 3);
+
+        // Only one connection was kept idle.
+        futures_util::future::join_all((0..2).map(|_| get())).await;
+        assert_eq!(connections(), 4);
+
+        // Idle connections were closed in the meantime.
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        get().await;
+        assert_eq!(connections(), 5);
+    }
+
+    #[tokio::test]
+    async fn clone_for_pubky_shares_connections() {
+        let (url, peers) = connections_server().await;
+        let connections = || peers.lock().unwrap().len();
+
+        let client = local_client_builder(&url).build().unwrap();
+        let cloned = client.clone_for_pubky(&pkarr::Keypair::random().public_key());
+
+        client.get_text(url.as_str()).await.unwrap();
+        cloned.get_text(url.as_str()).await.unwrap();
+        client.get_text(url.as_str()).await.unwrap();
+
+        assert_eq!(connections(), 1);
+
+        // Unlike separately built clients.
+        let other = local_client_builder(&url).build().unwrap();
+        other.get_text(url.as_str()).await.unwrap();
+
+        assert_eq!(connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn clone_for_pubky() {
+        let testnet = pubky_testnet::Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let alice = pkarr::Keypair::random();
+        let bob = pkarr::Keypair::random();
+
+        client.signup(&alice, &server.public_key()).await.unwrap();
+        client.signup(&bob, &server.public_key()).await.unwrap();
+
+        let alice_client = client.clone_for_pubky(&alice.public_key());
+        let bob_client = client.clone_for_pubky(&bob.public_key());
+
+        assert!(alice_client.is_signed_in(&alice.public_key()));
+        assert!(!alice_client.is_signed_in(&bob.public_key()));
+        assert!(bob_client.is_signed_in(&bob.public_key()));
+
+        alice_client.signout(&alice.public_key()).await.unwrap();
+
+        assert!(!alice_client.is_signed_in(&alice.public_key()));
+        assert!(bob_client
+            .session(&bob.public_key())
+            .await
+            .unwrap()
+            .is_some());
+
+        // New sessions stay in their scope.
+        alice_client.signin(&alice).await.unwrap();
+
+        assert!(alice_client.is_signed_in(&alice.public_key()));
+        assert!(!bob_client.is_signed_in(&alice.public_key()));
+    }
 }
@@ -0,0 +1,101 @@
+//! Resolving human-readable aliases (like DNS names) to Pubkys.
+
+use std::{collections::HashMap, fmt::Debug};
+
+use futures_util::future::BoxFuture;
+use pkarr::{Keypair, PublicKey};
+use pubky_common::session::Session;
+
+use anyhow::Result;
+
+use super::super::Client;
+
+/// Resolves human-readable aliases to a [PublicKey],
+/// see [ClientBuilder::alias_provider][crate::ClientBuilder::alias_provider].
+pub trait AliasProvider: Debug + Send + Sync {
+    /// Resolve an `alias` to a [PublicKey].
+    fn resolve<'a>(&'a self, alias: &'a str) -> BoxFuture<'a, Result<PublicKey>>;
+}
+
+/// A static map of aliases, useful for tests and bundled address books.
+impl AliasProvider for HashMap<String, PublicKey> {
+    fn resolve<'a>(&'a self, alias: &'a str) -> BoxFuture<'a, Result<PublicKey>> {
+        Box::pin(async move {
+            self.get(alias)
+                .cloned()
+                .ok_or(anyhow::anyhow!("Unknown alias {alias}"))
+        })
+    }
+}
+
+impl Client {
+    /// Parse `name` as a [PublicKey], or resolve it as an alias
+    /// using the configured [AliasProvider].
+    pub async fn resolve_pubky(&self, name: &str) -> Result<PublicKey> {
+        if let Ok(public_key) = PublicKey::try_from(name) {
+            return Ok(public_key);
+        }
+
+        match &self.alias_provider {
+            Some(provider) => provider.resolve(name).await,
+            None => Err(anyhow::anyhow!(
+                "{name} is not a valid public key, and no alias provider is configured"
+            )),
+        }
+    }
+
+    /// Same as [Client::signup], but `homeserver` can be either a public key
+    /// or an alias resolved with [Client::resolve_pubky].
+    pub async fn signup_with_alias(&self, keypair: &Keypair, homeserver: &str) -> Result<Session> {
+        let homeserver = self.resolve_pubky(homeserver).await?;
+
+        self.signup(keypair, &homeserver).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use pkarr::Keypair;
+    use pubky_testnet::Testnet;
+
+    #[tokio::test]
+    async fn signup_with_alias() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let aliases = HashMap::from([("homeserver.example.com".to_string(), server.public_key())]);
+
+        let client = testnet
+            .client_builder()
+            .alias_provider(Arc::new(aliases))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            client
+                .resolve_pubky("homeserver.example.com")
+                .await
+                .unwrap(),
+            server.public_key()
+        );
+        assert_eq!(
+            client
+                .resolve_pubky(&server.public_key().to_string())
+                .await
+                .unwrap(),
+            server.public_key()
+        );
+        assert!(client.resolve_pubky("unknown.example.com").await.is_err());
+
+        let keypair = Keypair::random();
+
+        let session = client
+            .signup_with_alias(&keypair, "homeserver.example.com")
+            .await
+            .unwrap();
+
+        assert_eq!(session.pubky(), &keypair.public_key());
+    }
+}
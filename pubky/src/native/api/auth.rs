@@ -1,14 +1,23 @@
 use std::collections::HashMap;
+#[cfg(not(wasm_browser))]
+use std::sync::{Arc, Mutex};
+#[cfg(not(wasm_browser))]
+use std::time::Instant;
+
+#[cfg(not(wasm_browser))]
+use futures_util::future::{BoxFuture, FutureExt, Shared};
 
 use base64::{alphabet::URL_SAFE, engine::general_purpose::NO_PAD, Engine};
 use reqwest::{IntoUrl, Method, StatusCode};
 use url::Url;
 
 use pkarr::{Keypair, PublicKey};
+#[cfg(not(wasm_browser))]
+use pubky_common::capabilities::Action;
 use pubky_common::{
     auth::AuthToken,
     capabilities::{Capabilities, Capability},
-    crypto::{decrypt, encrypt, hash, random_bytes},
+    crypto::{decrypt, encrypt, hash, random_bytes, Hash, Hasher},
     session::Session,
 };
 
@@ -18,32 +27,177 @@ use crate::handle_http_error;
 
 use super::super::Client;
 
+/// Maximum size of an encrypted [AuthToken] accepted from an HTTP relay.
+const MAX_AUTH_RESPONSE_SIZE: usize = 16 * 1024;
+
+/// Id of the encryption scheme prefixing encrypted [AuthToken]s, using
+/// [pubky_common::crypto::encrypt], so that other schemes can be added later.
+const AUTH_TOKEN_SCHEME_V0: u8 = 0;
+
+#[cfg(not(wasm_browser))]
+/// A signup in progress, shared by concurrent calls to [Client::signup].
+type SharedSignup = Shared<BoxFuture<'static, Result<(Session, bool), Arc<anyhow::Error>>>>;
+
+#[cfg(not(wasm_browser))]
+#[derive(Default)]
+/// Signups in progress by (pubky, homeserver).
+pub(crate) struct InflightSignups(Mutex<HashMap<(PublicKey, PublicKey), SharedSignup>>);
+
+#[cfg(not(wasm_browser))]
+impl std::fmt::Debug for InflightSignups {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InflightSignups").finish_non_exhaustive()
+    }
+}
+
+#[cfg(not(wasm_browser))]
+/// Rebuild the error of a shared signup for each of its callers, keeping the
+/// homeserver errors classified by [crate::errors::ErrorKind::of].
+fn unshare_error(error: &anyhow::Error) -> anyhow::Error {
+    use crate::errors::{
+        Forbidden, HttpError, MethodNotAllowed, ResolutionError, UnsupportedMediaType,
+    };
+
+    if let Some(error) = error.downcast_ref::<Forbidden>() {
+        return error.clone().into();
+    }
+    if let Some(error) = error.downcast_ref::<HttpError>() {
+        return error.clone().into();
+    }
+    if let Some(error) = error.downcast_ref::<MethodNotAllowed>() {
+        return error.clone().into();
+    }
+    if let Some(error) = error.downcast_ref::<UnsupportedMediaType>() {
+        return error.clone().into();
+    }
+    if let Some(error) = error.downcast_ref::<ResolutionError>() {
+        return error.clone().into();
+    }
+    if let Some(status) = error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+    {
+        return HttpError {
+            status,
+            message: format!("{error:#}"),
+        }
+        .into();
+    }
+
+    anyhow::anyhow!("{error:#}")
+}
+
 impl Client {
     /// Signup to a homeserver and update Pkarr accordingly.
     ///
     /// The homeserver is a Pkarr domain name, where the TLD is a Pkarr public key
     /// for example "pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy"
+    ///
+    /// Concurrent signups of the same keypair to the same homeserver share
+    /// a single request and its result.
     pub async fn signup(&self, keypair: &Keypair, homeserver: &PublicKey) -> Result<Session> {
+        Ok(self.signup_created(keypair, homeserver).await?.0)
+    }
+
+    /// Same as [Client::signup], but also returns whether the homeserver created
+    /// a new account, or signed in to an existing one.
+    ///
+    /// Concurrent signups sharing a single request all get the same result.
+    pub async fn signup_created(
+        &self,
+        keypair: &Keypair,
+        homeserver: &PublicKey,
+    ) -> Result<(Session, bool)> {
+        #[cfg(not(wasm_browser))]
+        {
+            let key = (keypair.public_key(), homeserver.clone());
+
+            let shared = self
+                .inflight_signups
+                .0
+                .lock()
+                .expect("inflight signups lock")
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    let client = self.clone();
+                    let keypair = keypair.clone();
+                    let homeserver = homeserver.clone();
+
+                    async move {
+                        let result = client
+                            .signup_once(&keypair, &homeserver)
+                            .await
+                            .map_err(Arc::new);
+
+                        client
+                            .inflight_signups
+                            .0
+                            .lock()
+                            .expect("inflight signups lock")
+                            .remove(&key);
+
+                        result
+                    }
+                    .boxed()
+                    .shared()
+                })
+                .clone();
+
+            shared.await.map_err(|error| unshare_error(&error))
+        }
+
+        #[cfg(wasm_browser)]
+        self.signup_once(keypair, homeserver).await
+    }
+
+    async fn signup_once(
+        &self,
+        keypair: &Keypair,
+        homeserver: &PublicKey,
+    ) -> Result<(Session, bool)> {
+        let token = AuthToken::sign(keypair, vec![Capability::root()]);
+
         let response = self
             .cross_request(Method::POST, format!("https://{}/signup", homeserver))
             .await
-            .body(AuthToken::sign(keypair, vec![Capability::root()]).serialize())
+            .body(token.serialize())
             .send()
             .await?;
 
-        handle_http_error!(response);
+        handle_http_error!(response, self);
 
-        self.publish_homeserver(keypair, &homeserver.to_string())
-            .await?;
+        let created = response.status() == StatusCode::CREATED;
+
+        // Pubkys signed up to a static homeserver are mapped to it, instead of published.
+        #[cfg(not(wasm_browser))]
+        let is_static = {
+            let mut static_homeservers = self.static_homeservers.write().unwrap();
+
+            match static_homeservers.get(homeserver).cloned() {
+                Some(url) => {
+                    static_homeservers.insert(keypair.public_key(), url);
+
+                    true
+                }
+                None => false,
+            }
+        };
+        #[cfg(wasm_browser)]
+        let is_static = false;
+
+        if !is_static {
+            self.publish_homeserver(keypair, &homeserver.to_string())
+                .await?;
+        }
 
         // Store the cookie to the correct URL.
         #[cfg(not(target_arch = "wasm32"))]
         self.cookie_store
-            .store_session_after_signup(&response, &keypair.public_key());
+            .store_session(&response, &keypair.public_key());
 
-        let bytes = response.bytes().await?;
+        let session = self.session_from_response(response, &token).await?;
 
-        Ok(Session::deserialize(&bytes)?)
+        Ok((session, created))
     }
 
     /// Check the current session for a given Pubky in its homeserver.
@@ -51,6 +205,15 @@ impl Client {
     /// Returns None  if not signed in, or [reqwest::Error]
     /// if the response has any other `>=404` status code.
     pub async fn session(&self, pubky: &PublicKey) -> Result<Option<Session>> {
+        #[cfg(not(wasm_browser))]
+        if let Some(ttl) = self.session_cache_ttl {
+            if let Some((session, seen_at)) = self.sessions.read().unwrap().get(pubky) {
+                if seen_at.elapsed() < ttl {
+                    return Ok(Some(session.clone()));
+                }
+            }
+        }
+
         let response = self
             .cross_request(Method::GET, format!("pubky://{}/session", pubky))
             .await
@@ -58,17 +221,124 @@ impl Client {
             .await?;
 
         if response.status() == StatusCode::NOT_FOUND {
+            #[cfg(not(wasm_browser))]
+            self.sessions.write().unwrap().remove(pubky);
+
             return Ok(None);
         }
 
-        handle_http_error!(response);
+        handle_http_error!(response, self);
+
+        let bytes = response.bytes().await?;
+        let session = Session::deserialize(&bytes)?;
+
+        #[cfg(not(wasm_browser))]
+        self.remember_session(&session);
+
+        Ok(Some(session))
+    }
+
+    /// Extend the expiry of the current session for a given Pubky, if the homeserver
+    /// expires sessions, without signing in again.
+    ///
+    /// Homeservers may cap the total lifetime of a session, after which it
+    /// can't be refreshed anymore.
+    pub async fn refresh_session(&self, pubky: &PublicKey) -> Result<Session> {
+        let response = self
+            .cross_request(Method::PATCH, format!("pubky://{}/session", pubky))
+            .await
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(anyhow::anyhow!("No valid session for {pubky} to refresh"));
+        }
+
+        handle_http_error!(response, self);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.cookie_store.store_session(&response, pubky);
+
+        let bytes = response.bytes().await?;
+        let session = Session::deserialize(&bytes)?;
+
+        #[cfg(not(wasm_browser))]
+        self.remember_session(&session);
+
+        Ok(session)
+    }
+
+    /// Remove the capabilities within this `scope` (like `/pub/example.com/`)
+    /// from the current session of `keypair`'s Pubky on its homeserver, keeping
+    /// all other capabilities, and return the updated session.
+    ///
+    /// Fails without changing the session if a broader capability (like `/pub/`)
+    /// grants access to `scope`, with a `409 Conflict` [crate::errors::HttpError],
+    /// since it can't be narrowed to exclude `scope`; revoke that capability instead.
+    ///
+    /// Fails if `keypair` is not signed in.
+    pub async fn revoke_capability(&self, keypair: &Keypair, scope: &str) -> Result<Session> {
+        let pubky = keypair.public_key();
+
+        let mut url = Url::parse(&format!("pubky://{pubky}/session/capabilities"))?;
+        url.query_pairs_mut().append_pair("scope", scope);
+
+        let response = self.cross_request(Method::DELETE, url).await.send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(anyhow::anyhow!(
+                "No valid session for {pubky} to revoke from"
+            ));
+        }
+
+        handle_http_error!(response, self);
 
         let bytes = response.bytes().await?;
+        let session = Session::deserialize(&bytes)?;
+
+        #[cfg(not(wasm_browser))]
+        self.remember_session(&session);
+
+        Ok(session)
+    }
+
+    /// Returns the capabilities of the current session for a given Pubky,
+    /// or `None` if not signed in.
+    pub async fn capabilities(&self, pubky: &PublicKey) -> Result<Option<Capabilities>> {
+        Ok(self
+            .session(pubky)
+            .await?
+            .map(|session| Capabilities(session.capabilities().clone())))
+    }
+
+    /// Sign an [AuthToken] granting `capabilities`, for example to hand to a sub-component
+    /// of an app, refusing to grant more than the current session of `keypair`.
+    ///
+    /// Fails if `keypair` is not signed in, or if `capabilities` are not a subset of
+    /// its session's capabilities.
+    pub async fn mint_subtoken(
+        &self,
+        keypair: &Keypair,
+        capabilities: &Capabilities,
+    ) -> Result<AuthToken> {
+        let pubky = keypair.public_key();
+
+        let granted = self
+            .capabilities(&pubky)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No valid session for {pubky} to mint a token from"))?;
+
+        if !capabilities.is_subset_of(&granted) {
+            anyhow::bail!("Capabilities {capabilities} exceed the session's {granted}");
+        }
 
-        Ok(Some(Session::deserialize(&bytes)?))
+        Ok(AuthToken::sign(keypair, capabilities.clone()))
     }
 
     /// Signout from a homeserver.
+    ///
+    /// Only needs the session cookie held by this client, not the keypair,
+    /// so a delegated UI can sign out of a session it was handed.
     pub async fn signout(&self, pubky: &PublicKey) -> Result<()> {
         let response = self
             .cross_request(Method::DELETE, format!("pubky://{}/session", pubky))
@@ -76,10 +346,12 @@ impl Client {
             .send()
             .await?;
 
-        handle_http_error!(response);
+        handle_http_error!(response, self);
 
         #[cfg(not(target_arch = "wasm32"))]
         self.cookie_store.delete_session_after_signout(pubky);
+        #[cfg(not(wasm_browser))]
+        self.sessions.write().unwrap().remove(pubky);
 
         Ok(())
     }
@@ -91,57 +363,153 @@ impl Client {
         self.signin_with_authtoken(&token).await
     }
 
+    #[cfg(not(wasm_browser))]
+    /// Returns true if this client holds a session cookie for `pubky` that hasn't expired.
+    ///
+    /// Unlike [Client::session], this is a local check without any network request,
+    /// so it doesn't verify that the homeserver still considers the session valid,
+    /// for example if it was revoked or signed out by another client.
+    pub fn is_signed_in(&self, pubky: &PublicKey) -> bool {
+        self.cookie_store.has_session(pubky)
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Returns true if the last session seen by this client for `pubky` allows
+    /// `action` on `path`, for example to disable UI affordances for operations
+    /// that would be forbidden.
+    ///
+    /// This is advisory only: it is a local check against cached capabilities,
+    /// while the homeserver remains authoritative, and may have revoked, expired
+    /// or downgraded the session since. Public reads (under `/pub/`) are always allowed.
+    pub fn can(&self, pubky: &PublicKey, path: &str, action: Action) -> bool {
+        if action == Action::Read && path.starts_with("/pub/") {
+            return true;
+        }
+
+        if !self.is_signed_in(pubky) {
+            return false;
+        }
+
+        self.sessions
+            .read()
+            .unwrap()
+            .get(pubky)
+            .is_some_and(|(session, _)| {
+                Capabilities(session.capabilities().clone()).allows(path, &action)
+            })
+    }
+
+    /// Returns the session in a signup or signin `response`, and caches it for
+    /// [Client::can] and [Client::session].
+    ///
+    /// If the homeserver omitted it with `204 No Content` (see [Client::minimal]),
+    /// returns the session requested by `token` instead, without caching it,
+    /// since the homeserver may not have granted all of its capabilities.
+    async fn session_from_response(
+        &self,
+        response: reqwest::Response,
+        token: &AuthToken,
+    ) -> Result<Session> {
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(Session::new(token.pubky(), token.capabilities(), None));
+        }
+
+        let bytes = response.bytes().await?;
+        let session = Session::deserialize(&bytes)?;
+
+        #[cfg(not(wasm_browser))]
+        self.remember_session(&session);
+
+        Ok(session)
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Cache `session` for [Client::can] and [Client::session].
+    fn remember_session(&self, session: &Session) {
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(session.pubky().clone(), (session.clone(), Instant::now()));
+    }
+
+    /// Forget the cached session of the Pubky `response` came from, if the
+    /// homeserver rejected it with `401 Unauthorized` or `403 Forbidden`,
+    /// see [crate::ClientBuilder::session_cache_ttl].
+    ///
+    /// Responses of [crate::ClientBuilder::static_homeserver]s can't be traced
+    /// back to their Pubky, and are ignored.
+    pub(crate) fn forget_session_if_unauthorized(&self, response: &reqwest::Response) {
+        #[cfg(not(wasm_browser))]
+        if matches!(
+            response.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+            let pubky = response
+                .url()
+                .host_str()
+                .map(|host| host.strip_prefix("_pubky.").unwrap_or(host))
+                .and_then(|host| PublicKey::try_from(host).ok());
+
+            if let Some(pubky) = pubky {
+                self.sessions.write().unwrap().remove(&pubky);
+            }
+        }
+
+        #[cfg(wasm_browser)]
+        let _ = response;
+    }
+
+    /// Returns the current session of `keypair`'s Pubky, or [signs in][Client::signin]
+    /// if there is none.
+    pub async fn session_or_signin(&self, keypair: &Keypair) -> Result<Session> {
+        match self.session(&keypair.public_key()).await? {
+            Some(session) => Ok(session),
+            None => self.signin(keypair).await,
+        }
+    }
+
+    /// Sign an [AuthToken], encrypt it and send it to the
+    /// source of the pubkyauth request url.
     pub async fn send_auth_token<T: IntoUrl>(
         &self,
         keypair: &Keypair,
         pubkyauth_url: &T,
     ) -> Result<()> {
-        let pubkyauth_url = Url::parse(
-            pubkyauth_url
-                .as_str()
-                .replace("pubkyauth_url", "http")
-                .as_str(),
-        )?;
-
-        let query_params: HashMap<String, String> =
-            pubkyauth_url.query_pairs().into_owned().collect();
-
-        let relay = query_params
-            .get("relay")
-            .map(|r| url::Url::parse(r).expect("Relay query param to be valid URL"))
-            .expect("Missing relay query param");
-
-        let client_secret = query_params
-            .get("secret")
-            .map(|s| {
-                let engine = base64::engine::GeneralPurpose::new(&URL_SAFE, NO_PAD);
-                let bytes = engine.decode(s).expect("invalid client_secret");
-                let arr: [u8; 32] = bytes.try_into().expect("invalid client_secret");
+        self.send_auth_token_with_channel_id(keypair, pubkyauth_url)
+            .await?;
 
-                arr
-            })
-            .expect("Missing client secret");
+        Ok(())
+    }
 
-        let capabilities = query_params
-            .get("caps")
-            .map(|caps_string| {
-                caps_string
-                    .split(',')
-                    .filter_map(|cap| Capability::try_from(cap).ok())
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
+    /// Same as [Client::send_auth_token], but returns the channel id
+    /// the token was posted to, see [AuthRequest::channel_id].
+    pub async fn send_auth_token_with_channel_id<T: IntoUrl>(
+        &self,
+        keypair: &Keypair,
+        pubkyauth_url: &T,
+    ) -> Result<String> {
+        let PubkyAuthUrl {
+            relay,
+            client_secret,
+            capabilities,
+            hmac,
+        } = PubkyAuthUrl::parse(pubkyauth_url.as_str())?;
 
         let token = AuthToken::sign(keypair, capabilities);
 
-        let encrypted_token = encrypt(&token.serialize(), &client_secret);
+        let mut encrypted_token = encrypt_auth_token(&token.serialize(), &client_secret);
 
-        let engine = base64::engine::GeneralPurpose::new(&URL_SAFE, NO_PAD);
+        if hmac {
+            let mac = relay_mac(&client_secret, &encrypted_token);
+            encrypted_token.splice(0..0, mac.as_bytes().iter().copied());
+        }
 
         let mut callback_url = relay.clone();
-        let mut path_segments = callback_url.path_segments_mut().unwrap();
+        let mut path_segments = callback_url
+            .path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid relay"))?;
         path_segments.pop_if_empty();
-        let channel_id = engine.encode(hash(&client_secret).as_bytes());
+        let channel_id = channel_id(&client_secret);
         path_segments.push(&channel_id);
         drop(path_segments);
 
@@ -154,7 +522,17 @@ impl Client {
 
         handle_http_error!(response);
 
-        Ok(())
+        Ok(channel_id)
+    }
+
+    /// Returns the relay channel id of a `pubkyauth://` url, without sending anything.
+    ///
+    /// Authenticators can show it to users to match it against [AuthRequest::channel_id]
+    /// on the requester's screen.
+    pub fn auth_channel_id(pubkyauth_url: &str) -> Result<String> {
+        Ok(channel_id(
+            &PubkyAuthUrl::parse(pubkyauth_url)?.client_secret,
+        ))
     }
 
     pub(crate) async fn signin_with_authtoken(&self, token: &AuthToken) -> Result<Session> {
@@ -165,24 +543,29 @@ impl Client {
             .send()
             .await?;
 
-        handle_http_error!(response);
+        handle_http_error!(response, self);
 
-        let bytes = response.bytes().await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        self.cookie_store.store_session(&response, token.pubky());
+
+        let session = self.session_from_response(response, token).await?;
 
-        Ok(Session::deserialize(&bytes)?)
+        Ok(session)
     }
 
     pub(crate) fn create_auth_request(
         &self,
         relay: &mut Url,
         capabilities: &Capabilities,
-    ) -> Result<(Url, [u8; 32])> {
+        client_secret: [u8; 32],
+        hmac: bool,
+    ) -> Result<(Url, String)> {
         let engine = base64::engine::GeneralPurpose::new(&URL_SAFE, NO_PAD);
 
-        let client_secret: [u8; 32] = random_bytes::<32>();
+        let hmac = if hmac { "&hmac=1" } else { "" };
 
         let pubkyauth_url = Url::parse(&format!(
-            "pubkyauth:///?caps={capabilities}&secret={}&relay={relay}",
+            "pubkyauth:///?caps={capabilities}&secret={}{hmac}&relay={relay}",
             engine.encode(client_secret)
         ))?;
 
@@ -192,11 +575,11 @@ impl Client {
 
         // remove trailing slash if any.
         segments.pop_if_empty();
-        let channel_id = &engine.encode(hash(&client_secret).as_bytes());
-        segments.push(channel_id);
+        let channel_id = channel_id(&client_secret);
+        segments.push(&channel_id);
         drop(segments);
 
-        Ok((pubkyauth_url, client_secret))
+        Ok((pubkyauth_url, channel_id))
     }
 
     /// Return `pubkyauth://` url and wait for the incoming [AuthToken]
@@ -206,11 +589,49 @@ impl Client {
         &self,
         relay: T,
         capabilities: &Capabilities,
+    ) -> Result<AuthRequest> {
+        self.auth_request_inner(relay, capabilities, random_bytes::<32>(), false)
+    }
+
+    /// Resume waiting for the response to an [Client::auth_request], for example
+    /// after an app restarted, given its persisted [AuthRequest::client_secret].
+    ///
+    /// `relay` and `capabilities` must be the same as the original request's,
+    /// so the returned [AuthRequest::url] is the same too.
+    pub fn resume_auth_request<T: IntoUrl>(
+        &self,
+        relay: T,
+        client_secret: [u8; 32],
+        capabilities: &Capabilities,
+    ) -> Result<AuthRequest> {
+        self.auth_request_inner(relay, capabilities, client_secret, false)
+    }
+
+    /// Same as [Client::auth_request], but asks the authenticator to authenticate
+    /// its response with an HMAC keyed by the client secret, so that junk posted
+    /// to the relay channel by anyone else is ignored instead of failing the request.
+    ///
+    /// Authenticators that don't support HMACs can't respond to such requests.
+    pub fn auth_request_with_hmac<T: IntoUrl>(
+        &self,
+        relay: T,
+        capabilities: &Capabilities,
+    ) -> Result<AuthRequest> {
+        self.auth_request_inner(relay, capabilities, random_bytes::<32>(), true)
+    }
+
+    fn auth_request_inner<T: IntoUrl>(
+        &self,
+        relay: T,
+        capabilities: &Capabilities,
+        client_secret: [u8; 32],
+        hmac: bool,
     ) -> Result<AuthRequest> {
         // TODO: use `async_compat` to remove the dependency on Tokio runtime.
         let mut relay: Url = relay.into_url()?;
 
-        let (url, client_secret) = self.create_auth_request(&mut relay, capabilities)?;
+        let (url, channel_id) =
+            self.create_auth_request(&mut relay, capabilities, client_secret, hmac)?;
 
         let (tx, rx) = flume::bounded(1);
 
@@ -218,7 +639,7 @@ impl Client {
 
         let future = async move {
             let result = this
-                .subscribe_to_auth_response(relay, &client_secret, tx.clone())
+                .subscribe_to_auth_response(relay, &client_secret, hmac, tx.clone())
                 .await;
             let _ = tx.send(result);
         };
@@ -228,41 +649,96 @@ impl Client {
         #[cfg(wasm_browser)]
         wasm_bindgen_futures::spawn_local(future);
 
-        Ok(AuthRequest { url, rx })
+        Ok(AuthRequest {
+            url,
+            channel_id,
+            client_secret,
+            rx,
+        })
     }
+    /// Wait for an encrypted [AuthToken] on the `relay` channel.
+    ///
+    /// If `hmac` is true, messages without a valid HMAC are ignored,
+    /// and the channel is polled again.
+    ///
+    /// Tokens failing [AuthToken::verify] (tampered, expired, or posted by
+    /// another party to the channel) are ignored as well.
     pub(crate) async fn subscribe_to_auth_response(
         &self,
-        relay: Url,
+        mut relay: Url,
         client_secret: &[u8; 32],
+        hmac: bool,
         tx: flume::Sender<Result<PublicKey>>,
     ) -> anyhow::Result<PublicKey> {
-        let response = loop {
-            match self
-                .cross_request(Method::GET, relay.clone())
-                .await
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    break Ok(response);
+        let token = loop {
+            let response = loop {
+                match self
+                    .cross_request(Method::GET, relay.clone())
+                    .await
+                    .send()
+                    .await
+                {
+                    Ok(response) => {
+                        break Ok(response);
+                    }
+                    Err(error) => {
+                        // TODO: test again after Rqewest support timeout
+                        if error.is_timeout() && !tx.is_disconnected() {
+                            cross_debug!("Connection to HttpRelay timedout, reconnecting...");
+
+                            continue;
+                        }
+
+                        if error.is_redirect() {
+                            anyhow::bail!("HttpRelay redirected the auth channel too many times");
+                        }
+
+                        break Err(error);
+                    }
                 }
-                Err(error) => {
-                    // TODO: test again after Rqewest support timeout
-                    if error.is_timeout() && !tx.is_disconnected() {
-                        cross_debug!("Connection to HttpRelay timedout, reconnecting...");
+            }?;
+
+            // Redirects are followed by the HTTP client, poll the new url directly next time.
+            if response.url() != &relay {
+                relay = redirected_channel(&relay, response.url())?;
+
+                cross_debug!("HttpRelay moved the auth channel to {relay}");
+            }
+
+            handle_http_error!(response);
+
+            let body = read_auth_response(response).await?;
+
+            let encrypted_token = if hmac {
+                match verify_relay_mac(client_secret, &body) {
+                    Some(encrypted_token) => encrypted_token,
+                    None => {
+                        if tx.is_disconnected() {
+                            anyhow::bail!("Auth request was dropped");
+                        }
+
+                        cross_debug!("Ignoring auth response with an invalid HMAC");
 
                         continue;
                     }
+                }
+            } else {
+                &body
+            };
+
+            let token_bytes = decrypt_auth_token(encrypted_token, client_secret)?;
+
+            match AuthToken::verify(&token_bytes) {
+                Ok(token) => break token,
+                Err(error) => {
+                    if tx.is_disconnected() {
+                        anyhow::bail!("Auth request was dropped");
+                    }
 
-                    break Err(error);
+                    cross_debug!("Ignoring auth response with an invalid token: {error}");
                 }
             }
-        }?;
-
-        let encrypted_token = response.bytes().await?;
-        let token_bytes = decrypt(&encrypted_token, client_secret)
-            .map_err(|e| anyhow::anyhow!("Got invalid token: {e}"))?;
-        let token = AuthToken::verify(&token_bytes)?;
+        };
 
         if !token.capabilities().is_empty() {
             self.signin_with_authtoken(&token).await?;
@@ -272,9 +748,65 @@ impl Client {
     }
 }
 
+/// Read the body of a response to an auth request, up to [MAX_AUTH_RESPONSE_SIZE].
+async fn read_auth_response(response: reqwest::Response) -> Result<Vec<u8>> {
+    if response
+        .content_length()
+        .is_some_and(|length| length as usize > MAX_AUTH_RESPONSE_SIZE)
+    {
+        anyhow::bail!("Auth response is larger than {MAX_AUTH_RESPONSE_SIZE} bytes");
+    }
+
+    // Responses can't be streamed in browsers.
+    #[cfg(wasm_browser)]
+    let body = response.bytes().await?.to_vec();
+
+    #[cfg(not(wasm_browser))]
+    let body = {
+        let mut response = response;
+        let mut body = Vec::new();
+
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+
+            if body.len() > MAX_AUTH_RESPONSE_SIZE {
+                break;
+            }
+        }
+
+        body
+    };
+
+    if body.len() > MAX_AUTH_RESPONSE_SIZE {
+        anyhow::bail!("Auth response is larger than {MAX_AUTH_RESPONSE_SIZE} bytes");
+    }
+
+    Ok(body)
+}
+
+/// Returns the url the `relay` channel was redirected to,
+/// if it ends with the same channel id.
+fn redirected_channel(relay: &Url, redirected: &Url) -> Result<Url> {
+    let channel_id = |url: &Url| {
+        url.path_segments()
+            .and_then(|mut segments| segments.rfind(|segment| !segment.is_empty()))
+            .map(String::from)
+    };
+
+    if channel_id(relay) != channel_id(redirected) {
+        anyhow::bail!(
+            "HttpRelay redirected the auth channel {relay} to {redirected}, with a different channel id"
+        );
+    }
+
+    Ok(redirected.clone())
+}
+
 #[derive(Debug, Clone)]
 pub struct AuthRequest {
     url: Url,
+    channel_id: String,
+    client_secret: [u8; 32],
     pub(crate) rx: flume::Receiver<Result<PublicKey>>,
 }
 
@@ -284,6 +816,20 @@ impl AuthRequest {
         &self.url
     }
 
+    /// Returns the id of the relay channel this request is waiting on.
+    ///
+    /// Authenticators compute the same id from the [AuthRequest::url],
+    /// see [Client::auth_channel_id].
+    pub fn channel_id(&self) -> &str {
+        &self.channel_id
+    }
+
+    /// Returns the secret encrypting the response to this request,
+    /// to be persisted (as securely as a session) for [Client::resume_auth_request].
+    pub fn client_secret(&self) -> &[u8; 32] {
+        &self.client_secret
+    }
+
     // TODO: Return better errors
 
     /// Returns the result of an Auth request.
@@ -295,15 +841,127 @@ impl AuthRequest {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::time::Duration;
+/// The relay channel id derived from a `pubkyauth://` url's client secret.
+fn channel_id(client_secret: &[u8; 32]) -> String {
+    let engine = base64::engine::GeneralPurpose::new(&URL_SAFE, NO_PAD);
 
-    use pkarr::Keypair;
-    use pubky_common::capabilities::{Capabilities, Capability};
-    use pubky_testnet::Testnet;
+    engine.encode(hash(client_secret).as_bytes())
+}
+
+/// Encrypt a serialized [AuthToken] with the current scheme, prefixed with its id.
+pub(crate) fn encrypt_auth_token(token: &[u8], client_secret: &[u8; 32]) -> Vec<u8> {
+    let mut encrypted_token = vec![AUTH_TOKEN_SCHEME_V0];
+    encrypted_token.extend_from_slice(&encrypt(token, client_secret));
+
+    encrypted_token
+}
+
+/// Decrypt an encrypted [AuthToken] with the scheme of its first byte,
+/// rejecting unknown schemes.
+fn decrypt_auth_token(encrypted_token: &[u8], client_secret: &[u8; 32]) -> Result<Vec<u8>> {
+    match encrypted_token.split_first() {
+        Some((&AUTH_TOKEN_SCHEME_V0, encrypted_token)) => decrypt(encrypted_token, client_secret)
+            .map_err(|e| anyhow::anyhow!("Got invalid token: {e}")),
+        Some((scheme, _)) => anyhow::bail!("Got token with unknown encryption scheme: {scheme}"),
+        None => anyhow::bail!("Got empty token"),
+    }
+}
+
+/// HMAC over an encrypted [AuthToken] posted to a relay channel, keyed by the client secret.
+fn relay_mac(client_secret: &[u8; 32], encrypted_token: &[u8]) -> Hash {
+    Hasher::new_keyed(client_secret)
+        .update(encrypted_token)
+        .finalize()
+}
+
+/// Returns the encrypted [AuthToken] of a relay message prefixed with a valid [relay_mac].
+fn verify_relay_mac<'a>(client_secret: &[u8; 32], message: &'a [u8]) -> Option<&'a [u8]> {
+    if message.len() < 32 {
+        return None;
+    }
+
+    let (mac, encrypted_token) = message.split_at(32);
+    let mac = Hash::from_bytes(mac.try_into().ok()?);
+
+    // Constant time comparison.
+    (mac == relay_mac(client_secret, encrypted_token)).then_some(encrypted_token)
+}
+
+/// Parameters of a `pubkyauth://` url.
+struct PubkyAuthUrl {
+    relay: Url,
+    client_secret: [u8; 32],
+    capabilities: Vec<Capability>,
+    /// Whether the response should be prefixed with a [relay_mac].
+    hmac: bool,
+}
+
+impl PubkyAuthUrl {
+    fn parse(pubkyauth_url: &str) -> Result<Self> {
+        let pubkyauth_url = Url::parse(pubkyauth_url.replace("pubkyauth_url", "http").as_str())?;
+
+        let query_params: HashMap<String, String> =
+            pubkyauth_url.query_pairs().into_owned().collect();
+
+        let relay = query_params
+            .get("relay")
+            .ok_or(anyhow::anyhow!("Missing relay query param"))?;
+        let relay = Url::parse(relay)
+            .map_err(|error| anyhow::anyhow!("Invalid relay query param {relay}: {error}"))?;
+
+        let client_secret = query_params
+            .get("secret")
+            .ok_or(anyhow::anyhow!("Missing client secret"))?;
+        let client_secret: [u8; 32] = base64::engine::GeneralPurpose::new(&URL_SAFE, NO_PAD)
+            .decode(client_secret)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(anyhow::anyhow!("Invalid client secret"))?;
+
+        let capabilities = query_params
+            .get("caps")
+            .map(|caps_string| {
+                caps_string
+                    .split(',')
+                    .filter_map(|cap| Capability::try_from(cap).ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let hmac = query_params.get("hmac").is_some_and(|hmac| hmac == "1");
+
+        Ok(Self {
+            relay,
+            client_secret,
+            capabilities,
+            hmac,
+        })
+    }
+}
+
+#[cfg(all(test, not(wasm_browser)))]
+mod tests {
+    use std::time::Duration;
+
+    use pkarr::Keypair;
+    use pubky_common::{
+        auth::AuthToken,
+        capabilities::{Action, Capabilities, Capability},
+    };
+    use pubky_testnet::Testnet;
     use reqwest::StatusCode;
 
+    use super::{
+        decrypt_auth_token, encrypt_auth_token, relay_mac, AUTH_TOKEN_SCHEME_V0,
+        MAX_AUTH_RESPONSE_SIZE,
+    };
+
+    use crate::{
+        errors::ErrorKind,
+        native::internal::mock_relay::{MockRelay, MockResponse},
+        Client,
+    };
+
     #[tokio::test]
     async fn basic_authn() {
         let testnet = Testnet::run().await.unwrap();
@@ -345,6 +1003,320 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn signout_without_keypair() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let pubky = {
+            let keypair = Keypair::random();
+            client.signup(&keypair, &server.public_key()).await.unwrap();
+
+            keypair.public_key()
+        };
+
+        client.signout(&pubky).await.unwrap();
+
+        assert!(!client.is_signed_in(&pubky));
+        assert!(client.session(&pubky).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn minimal_signup() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        let session = client
+            .minimal()
+            .signup(&keypair, &server.public_key())
+            .await
+            .unwrap();
+        assert_eq!(session.pubky(), &pubky);
+        assert_eq!(session.capabilities(), &vec![Capability::root()]);
+
+        // Established from the cookie, shared with the original client.
+        assert!(client.is_signed_in(&pubky));
+
+        // The requested session isn't cached, until the granted one is fetched.
+        assert!(!client.can(&pubky, "/pub/foo.txt", Action::Write));
+        assert!(client.session(&pubky).await.unwrap().is_some());
+        assert!(client.can(&pubky, "/pub/foo.txt", Action::Write));
+
+        let response = client
+            .minimal()
+            .put(format!("pubky://{pubky}/pub/foo.txt"))
+            .body("foo")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn signup_forbidden() {
+        let testnet = Testnet::run().await.unwrap();
+
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        // Any Pubky is allowed by default, see the homeserver's `allowed_pubkys` tests
+        // for the rejection itself.
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let error = client
+            .put(format!("pubky://{}/other/file", keypair.public_key()))
+            .body(vec![])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(error.status(), StatusCode::FORBIDDEN);
+
+        let error = client
+            .put_text(
+                format!("pubky://{}/other/file", keypair.public_key()),
+                "text",
+            )
+            .await
+            .unwrap_err();
+
+        assert!(error.downcast_ref::<crate::errors::Forbidden>().is_some());
+    }
+
+    #[tokio::test]
+    async fn concurrent_signups() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+
+        let homeserver = server.public_key();
+
+        let (a, b) = tokio::join!(
+            client.signup(&keypair, &homeserver),
+            client.signup(&keypair, &homeserver)
+        );
+
+        // Every signup creates a new session with its own creation time,
+        // so equal sessions come from the same signup.
+        assert_eq!(a.unwrap(), b.unwrap());
+        assert!(client.inflight_signups.0.lock().unwrap().is_empty());
+
+        // Later signups are not deduplicated.
+        let c = client.signup(&keypair, &server.public_key()).await.unwrap();
+        assert_eq!(c.pubky(), &keypair.public_key());
+    }
+
+    #[tokio::test]
+    async fn concurrent_signups_forbidden() {
+        let relay = MockRelay::run(vec![
+            MockResponse::status(403).delay(Duration::from_millis(200))
+        ])
+        .await;
+
+        let homeserver = Keypair::random().public_key();
+
+        let client = Client::builder()
+            .pkarr(|builder| builder.no_default_network().relays(&[relay.url()]).unwrap())
+            .static_homeserver(homeserver.clone(), relay.url())
+            .build()
+            .unwrap();
+
+        let keypair = Keypair::random();
+
+        let (a, b) = tokio::join!(
+            client.signup(&keypair, &homeserver),
+            client.signup(&keypair, &homeserver)
+        );
+
+        assert_eq!(relay.requests(), 1);
+
+        for error in [a.unwrap_err(), b.unwrap_err()] {
+            assert_eq!(
+                ErrorKind::of(&error),
+                ErrorKind::Http {
+                    status: StatusCode::FORBIDDEN
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_session() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let session = client.refresh_session(&keypair.public_key()).await.unwrap();
+
+        assert_eq!(session.pubky(), &keypair.public_key());
+
+        client.signout(&keypair.public_key()).await.unwrap();
+
+        assert!(client.refresh_session(&keypair.public_key()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn capabilities() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+        client.signout(&pubky).await.unwrap();
+
+        assert_eq!(client.capabilities(&pubky).await.unwrap(), None);
+
+        let capabilities: Capabilities =
+            "/pub/pubky.app/:rw,/pub/foo.bar/file:r".try_into().unwrap();
+
+        client
+            .signin_with_authtoken(&AuthToken::sign(&keypair, capabilities.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.capabilities(&pubky).await.unwrap(),
+            Some(capabilities)
+        );
+    }
+
+    #[tokio::test]
+    async fn signup_created() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        let (session, created) = client
+            .signup_created(&keypair, &server.public_key())
+            .await
+            .unwrap();
+        assert_eq!(session.pubky(), &keypair.public_key());
+        assert!(created);
+
+        let (_, created) = client
+            .signup_created(&keypair, &server.public_key())
+            .await
+            .unwrap();
+        assert!(!created);
+    }
+
+    #[tokio::test]
+    async fn is_signed_in() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        assert!(!client.is_signed_in(&pubky));
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+        assert!(client.is_signed_in(&pubky));
+
+        client.signout(&pubky).await.unwrap();
+        assert!(!client.is_signed_in(&pubky));
+
+        client.signin(&keypair).await.unwrap();
+        assert!(client.is_signed_in(&pubky));
+
+        client.signout(&pubky).await.unwrap();
+        assert!(!client.is_signed_in(&pubky));
+
+        // Other Pubkys are not signed in.
+        assert!(!client.is_signed_in(&Keypair::random().public_key()));
+    }
+
+    #[tokio::test]
+    async fn session_or_signin() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+        client.signout(&pubky).await.unwrap();
+
+        let capabilities: Capabilities = "/pub/pubky.app/:rw".try_into().unwrap();
+
+        client
+            .signin_with_authtoken(&AuthToken::sign(&keypair, capabilities.clone()))
+            .await
+            .unwrap();
+
+        // Reuses the existing session, instead of signing in with root capabilities.
+        let session = client.session_or_signin(&keypair).await.unwrap();
+        assert_eq!(session.capabilities(), &capabilities.0);
+
+        client.signout(&pubky).await.unwrap();
+
+        let session = client.session_or_signin(&keypair).await.unwrap();
+        assert_eq!(session.capabilities(), &vec![Capability::root()]);
+
+        assert!(client.session(&pubky).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn mint_subtoken() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        let read_only: Capabilities = "/pub/pubky.app/:r".try_into().unwrap();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+        client.signout(&pubky).await.unwrap();
+
+        // Not signed in.
+        assert!(client.mint_subtoken(&keypair, &read_only).await.is_err());
+
+        client
+            .signin_with_authtoken(&AuthToken::sign(
+                &keypair,
+                Capabilities::try_from("/pub/pubky.app/:rw").unwrap(),
+            ))
+            .await
+            .unwrap();
+
+        let token = client.mint_subtoken(&keypair, &read_only).await.unwrap();
+        assert_eq!(token.pubky(), &pubky);
+        assert_eq!(token.capabilities(), &read_only.0);
+
+        let wider: Capabilities = "/pub/:rw".try_into().unwrap();
+        assert!(client.mint_subtoken(&keypair, &wider).await.is_err());
+    }
+
     #[tokio::test]
     async fn authz() {
         let testnet = Testnet::run().await.unwrap();
@@ -417,6 +1389,193 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn can() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+        client.signout(&pubky).await.unwrap();
+
+        assert!(!client.can(&pubky, "/pub/pubky.app/foo", Action::Write));
+
+        let capabilities: Capabilities =
+            "/pub/pubky.app/:rw,/pub/foo.bar/file:r".try_into().unwrap();
+
+        client
+            .signin_with_authtoken(&AuthToken::sign(&keypair, capabilities))
+            .await
+            .unwrap();
+
+        for path in ["/pub/pubky.app/foo", "/pub/pubky.app", "/pub/foo.bar/file"] {
+            let allowed = client
+                .put(format!("pubky://{pubky}{path}"))
+                .body(vec![])
+                .send()
+                .await
+                .unwrap()
+                .status()
+                .is_success();
+
+            assert_eq!(client.can(&pubky, path, Action::Write), allowed, "{path}");
+            assert!(client.can(&pubky, path, Action::Read), "{path}");
+        }
+
+        client.signout(&pubky).await.unwrap();
+
+        assert!(!client.can(&pubky, "/pub/pubky.app/foo", Action::Write));
+    }
+
+    #[tokio::test]
+    async fn revoke_capability() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let capabilities: Capabilities = "/pub/foo/:rw,/pub/bar/:rw".try_into().unwrap();
+
+        client
+            .signin_with_authtoken(&AuthToken::sign(&keypair, capabilities))
+            .await
+            .unwrap();
+
+        let session = client
+            .revoke_capability(&keypair, "/pub/foo/")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            session.capabilities(),
+            &vec![Capability {
+                scope: "/pub/bar/".to_string(),
+                actions: vec![Action::Read, Action::Write],
+            }]
+        );
+        assert_eq!(client.session(&pubky).await.unwrap(), Some(session));
+
+        let status = |path: &'static str| {
+            let client = &client;
+            let url = format!("pubky://{pubky}{path}");
+
+            async move { client.put(url).body(vec![0]).send().await.unwrap().status() }
+        };
+
+        assert_eq!(status("/pub/foo/file").await, StatusCode::FORBIDDEN);
+        assert_eq!(status("/pub/bar/file").await, StatusCode::CREATED);
+
+        // Covered by `/pub/bar/`.
+        let error = client
+            .revoke_capability(&keypair, "/pub/bar/baz/")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            ErrorKind::of(&error),
+            ErrorKind::Http {
+                status: StatusCode::CONFLICT
+            }
+        );
+        assert_eq!(status("/pub/bar/baz/file").await, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn session_cache() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = Client::test_builder(&testnet)
+            .session_cache_ttl(Duration::from_secs(60))
+            .build()
+            .unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        let session = client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        // Kill the session on the homeserver behind the client's back.
+        client
+            .clone_for_pubky(&pubky)
+            .signout(&pubky)
+            .await
+            .unwrap();
+
+        // Still answered from the cache.
+        assert_eq!(client.session(&pubky).await.unwrap(), Some(session));
+
+        let error = client
+            .upload(format!("pubky://{pubky}/pub/foo.txt"), "foo")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error
+                .downcast_ref::<crate::errors::HttpError>()
+                .unwrap()
+                .status,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Invalidated by the `401`, so fetched again.
+        assert_eq!(client.session(&pubky).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn resume_auth_request() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let http_relay = testnet.run_http_relay().await.unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        let capabilities: Capabilities = "/pub/pubky.app/:rw".try_into().unwrap();
+
+        let (url, client_secret) = {
+            let client = testnet.client_builder().build().unwrap();
+
+            let request = client
+                .auth_request(http_relay.local_link_url(), &capabilities)
+                .unwrap();
+
+            // Let the original request poll the relay before the "restart".
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            (request.url().clone(), *request.client_secret())
+        };
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let request = client
+            .resume_auth_request(http_relay.local_link_url(), client_secret, &capabilities)
+            .unwrap();
+        assert_eq!(request.url(), &url);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let authenticator = testnet.client_builder().build().unwrap();
+        authenticator
+            .signup(&keypair, &server.public_key())
+            .await
+            .unwrap();
+        authenticator.send_auth_token(&keypair, &url).await.unwrap();
+
+        assert_eq!(request.response().await.unwrap(), pubky);
+
+        let session = client.session(&pubky).await.unwrap().unwrap();
+        assert_eq!(session.capabilities(), &capabilities.0);
+    }
+
     #[tokio::test]
     async fn multiple_users() {
         let testnet = Testnet::run().await.unwrap();
@@ -534,4 +1693,299 @@ mod tests {
             StatusCode::FORBIDDEN
         );
     }
+
+    #[test]
+    fn invalid_pubkyauth_url() {
+        let secret = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+        assert!(Client::auth_channel_id(&format!(
+            "pubkyauth:///?relay=http://localhost/&secret={secret}"
+        ))
+        .is_ok());
+
+        for url in [
+            format!("pubkyauth:///?secret={secret}"),
+            format!("pubkyauth:///?relay=not%20a%20url&secret={secret}"),
+            "pubkyauth:///?relay=http://localhost/".to_string(),
+            "pubkyauth:///?relay=http://localhost/&secret=not-base64!".to_string(),
+            "pubkyauth:///?relay=http://localhost/&secret=AAAA".to_string(),
+        ] {
+            assert!(Client::auth_channel_id(&url).is_err(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn channel_id() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let http_relay = testnet.run_http_relay().await.unwrap();
+        let http_relay_url = http_relay.local_link_url();
+
+        let keypair = Keypair::random();
+
+        let capabilities: Capabilities = "/pub/pubky.app/:rw".try_into().unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let pubky_auth_request = client.auth_request(http_relay_url, &capabilities).unwrap();
+
+        // Authenticator side
+        let url = pubky_auth_request.url().as_str();
+
+        assert_eq!(
+            Client::auth_channel_id(url).unwrap(),
+            pubky_auth_request.channel_id()
+        );
+
+        let authenticator = testnet.client_builder().build().unwrap();
+        authenticator
+            .signup(&keypair, &server.public_key())
+            .await
+            .unwrap();
+
+        let channel_id = authenticator
+            .send_auth_token_with_channel_id(&keypair, &url)
+            .await
+            .unwrap();
+
+        assert_eq!(channel_id, pubky_auth_request.channel_id());
+
+        pubky_auth_request.response().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn mock_relay_timeout_reconnect() {
+        let testnet = Testnet::run().await.unwrap();
+
+        let keypair = Keypair::random();
+        let client_secret = [7; 32];
+
+        let token = AuthToken::sign(&keypair, vec![]);
+        let encrypted_token = encrypt_auth_token(&token.serialize(), &client_secret);
+
+        let relay = MockRelay::run(vec![
+            MockResponse::ok(vec![]).delay(Duration::from_millis(500)),
+            MockResponse::ok(vec![]).delay(Duration::from_millis(500)),
+            MockResponse::ok(encrypted_token),
+        ])
+        .await;
+
+        let client = Client::test_builder(&testnet)
+            .request_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let (tx, _rx) = flume::bounded(1);
+
+        let public_key = client
+            .subscribe_to_auth_response(relay.url(), &client_secret, false, tx)
+            .await
+            .unwrap();
+
+        assert_eq!(public_key, keypair.public_key());
+        assert_eq!(relay.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn mock_relay_hmac() {
+        let testnet = Testnet::run().await.unwrap();
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let client_secret = [7; 32];
+
+        let token = AuthToken::sign(&keypair, vec![]);
+        let encrypted_token = encrypt_auth_token(&token.serialize(), &client_secret);
+
+        let with_mac = |secret: &[u8; 32], encrypted_token: &[u8]| {
+            let mut message = relay_mac(secret, encrypted_token).as_bytes().to_vec();
+            message.extend_from_slice(encrypted_token);
+            message
+        };
+
+        let relay = MockRelay::run(vec![
+            // Junk, without any HMAC.
+            MockResponse::ok(vec![0; 100]),
+            // Keyed by another secret.
+            MockResponse::ok(with_mac(&[8; 32], &encrypted_token)),
+            MockResponse::ok(with_mac(&client_secret, &encrypted_token)),
+        ])
+        .await;
+
+        let (tx, _rx) = flume::bounded(1);
+
+        let public_key = client
+            .subscribe_to_auth_response(relay.url(), &client_secret, true, tx)
+            .await
+            .unwrap();
+
+        assert_eq!(public_key, keypair.public_key());
+        assert_eq!(relay.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn mock_relay_invalid_token() {
+        let testnet = Testnet::run().await.unwrap();
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let client_secret = [7; 32];
+
+        let token = AuthToken::sign(&keypair, vec![]).serialize();
+
+        let mut tampered = token.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+
+        let relay = MockRelay::run(vec![
+            MockResponse::ok(encrypt_auth_token(&tampered, &client_secret)),
+            MockResponse::ok(encrypt_auth_token(b"not a token", &client_secret)),
+            MockResponse::ok(encrypt_auth_token(&token, &client_secret)),
+        ])
+        .await;
+
+        let (tx, _rx) = flume::bounded(1);
+
+        let public_key = client
+            .subscribe_to_auth_response(relay.url(), &client_secret, false, tx)
+            .await
+            .unwrap();
+
+        assert_eq!(public_key, keypair.public_key());
+        assert_eq!(relay.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn mock_relay_redirect() {
+        let testnet = Testnet::run().await.unwrap();
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let client_secret = [7; 32];
+
+        let token = AuthToken::sign(&keypair, vec![]);
+        let encrypted_token = encrypt_auth_token(&token.serialize(), &client_secret);
+
+        let mut message = relay_mac(&client_secret, &encrypted_token)
+            .as_bytes()
+            .to_vec();
+        message.extend_from_slice(&encrypted_token);
+
+        let relay = MockRelay::run(vec![
+            MockResponse::redirect("/moved/link/"),
+            // Ignored, polling again at the new url.
+            MockResponse::ok(vec![0; 100]),
+            MockResponse::ok(message),
+        ])
+        .await;
+
+        let (tx, _rx) = flume::bounded(1);
+
+        let public_key = client
+            .subscribe_to_auth_response(relay.url(), &client_secret, true, tx)
+            .await
+            .unwrap();
+
+        assert_eq!(public_key, keypair.public_key());
+        assert_eq!(
+            relay.paths(),
+            vec!["/link/", "/moved/link/", "/moved/link/"]
+        );
+
+        // Another channel id
+        let relay = MockRelay::run(vec![MockResponse::redirect("/moved/other/")]).await;
+
+        let (tx, _rx) = flume::bounded(1);
+
+        let error = client
+            .subscribe_to_auth_response(relay.url(), &client_secret, true, tx)
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("different channel id"));
+    }
+
+    #[tokio::test]
+    async fn auth_request_with_hmac() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let http_relay = testnet.run_http_relay().await.unwrap();
+
+        let keypair = Keypair::random();
+
+        let capabilities: Capabilities = "/pub/pubky.app/:rw".try_into().unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let pubky_auth_request = client
+            .auth_request_with_hmac(http_relay.local_link_url(), &capabilities)
+            .unwrap();
+        assert!(pubky_auth_request.url().as_str().contains("hmac=1"));
+
+        let authenticator = testnet.client_builder().build().unwrap();
+
+        authenticator
+            .signup(&keypair, &server.public_key())
+            .await
+            .unwrap();
+
+        authenticator
+            .send_auth_token(&keypair, pubky_auth_request.url())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            pubky_auth_request.response().await.unwrap(),
+            keypair.public_key()
+        );
+    }
+
+    #[test]
+    fn auth_token_scheme() {
+        let client_secret = [7; 32];
+        let token = AuthToken::sign(&Keypair::random(), vec![]).serialize();
+
+        let encrypted_token = encrypt_auth_token(&token, &client_secret);
+        assert_eq!(encrypted_token[0], AUTH_TOKEN_SCHEME_V0);
+        assert_eq!(
+            decrypt_auth_token(&encrypted_token, &client_secret).unwrap(),
+            token
+        );
+
+        let mut unknown = encrypted_token.clone();
+        unknown[0] = u8::MAX;
+
+        let error = decrypt_auth_token(&unknown, &client_secret).unwrap_err();
+        assert!(error.to_string().contains("unknown encryption scheme"));
+
+        assert!(decrypt_auth_token(&[], &client_secret).is_err());
+    }
+
+    #[tokio::test]
+    async fn mock_relay_bad_responses() {
+        let testnet = Testnet::run().await.unwrap();
+        let client = Client::test_builder(&testnet).build().unwrap();
+
+        let client_secret = [7; 32];
+
+        let relay = MockRelay::run(vec![
+            MockResponse::ok(vec![0; MAX_AUTH_RESPONSE_SIZE + 1]),
+            MockResponse::ok(vec![0; 10]).content_length(MAX_AUTH_RESPONSE_SIZE * 2),
+            MockResponse::ok(vec![0; 10]).content_length(100),
+            MockResponse::status(500),
+        ])
+        .await;
+
+        for _ in 0..4 {
+            let (tx, _rx) = flume::bounded(1);
+
+            assert!(client
+                .subscribe_to_auth_response(relay.url(), &client_secret, false, tx)
+                .await
+                .is_err());
+        }
+
+        assert_eq!(relay.requests(), 4);
+    }
 }
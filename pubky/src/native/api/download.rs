@@ -0,0 +1,187 @@
+//! Downloading a tree of entries to a local directory.
+
+use std::path::{Component, Path, PathBuf};
+
+use futures_util::stream::{self, StreamExt};
+use pkarr::PublicKey;
+use reqwest::Method;
+
+use anyhow::{anyhow, Result};
+
+use crate::handle_http_error;
+
+use super::super::Client;
+
+/// Number of entries listed per request in [Client::download_all].
+const DOWNLOAD_LIST_LIMIT: u16 = 100;
+
+impl Client {
+    /// Download all entries of `pubky` under `prefix` (for example `/pub/example.com/`)
+    /// into the local directory `into_dir`, mirroring their paths relative to `prefix`.
+    ///
+    /// Runs up to `concurrency` downloads at a time.
+    ///
+    /// Fails only if listing the entries fails, otherwise returns the path of each entry
+    /// (like `/pub/example.com/foo.txt`) with the result of downloading it,
+    /// in the same order as [Client::list].
+    pub async fn download_all(
+        &self,
+        pubky: &PublicKey,
+        prefix: &str,
+        into_dir: impl AsRef<Path>,
+        concurrency: usize,
+    ) -> Result<Vec<(String, Result<()>)>> {
+        let url_prefix = format!("pubky://{pubky}");
+        let into_dir = into_dir.as_ref();
+
+        let mut urls = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut list = self
+                .list(format!("{url_prefix}{prefix}"))?
+                .limit(DOWNLOAD_LIST_LIMIT);
+
+            if let Some(cursor) = &cursor {
+                list = list.cursor(cursor);
+            }
+
+            let page = list.send().await?;
+            let full = page.len() == DOWNLOAD_LIST_LIMIT as usize;
+
+            cursor = page.last().cloned();
+            urls.extend(page);
+
+            if !full {
+                break;
+            }
+        }
+
+        let url_prefix = &url_prefix;
+
+        let results = stream::iter(urls)
+            .map(|url| async move {
+                let path = url[url_prefix.len()..].to_string();
+
+                let result: Result<()> = async {
+                    let local = local_path(into_dir, &path[prefix.len()..])?;
+
+                    let response = self.cross_request(Method::GET, &url).await.send().await?;
+
+                    handle_http_error!(response, self);
+
+                    let bytes = response.bytes().await?;
+
+                    if let Some(parent) = local.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    tokio::fs::write(&local, bytes).await?;
+
+                    Ok(())
+                }
+                .await;
+
+                (path, result)
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await;
+
+        Ok(results)
+    }
+}
+
+/// Join `relative` (an entry path relative to the downloaded prefix) to `dir`,
+/// refusing paths that would escape it.
+fn local_path(dir: &Path, relative: &str) -> Result<PathBuf> {
+    let relative = Path::new(relative);
+
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(anyhow!("Invalid entry path {relative:?}"));
+    }
+
+    Ok(dir.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use pkarr::Keypair;
+    use pubky_common::crypto::random_bytes;
+    use pubky_testnet::Testnet;
+
+    #[tokio::test]
+    async fn download_all() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let entries = [
+            ("a.txt", "a"),
+            ("b.txt", "b"),
+            ("nested/c.txt", "c"),
+            ("nested/deeper/d.txt", "d"),
+        ];
+
+        for (path, content) in entries {
+            client
+                .put(format!("pubky://{pubky}/pub/example.com/{path}"))
+                .body(content)
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+        }
+
+        // Outside the prefix.
+        client
+            .put(format!("pubky://{pubky}/pub/other.com/e.txt"))
+            .body("e")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!(
+            "pubky-download-{}",
+            random_bytes::<8>()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        ));
+
+        let results = client
+            .download_all(&pubky, "/pub/example.com/", &dir, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            results
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect::<Vec<_>>(),
+            entries
+                .iter()
+                .map(|(path, _)| format!("/pub/example.com/{path}"))
+                .collect::<Vec<_>>()
+        );
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        for (path, content) in entries {
+            assert_eq!(std::fs::read_to_string(dir.join(path)).unwrap(), content);
+        }
+        assert!(!dir.join("e.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+//! Reading the log of writes and deletes of one's own Pubky.
+
+use pkarr::PublicKey;
+use pubky_common::{crypto::Hash, timestamp::Timestamp};
+use reqwest::Method;
+
+use anyhow::{anyhow, Result};
+
+use crate::handle_http_error;
+
+use super::super::Client;
+
+/// Number of events read per request in [Client::event_log].
+const EVENT_LOG_LIMIT: u16 = 100;
+
+/// An entry written or deleted, see [Client::event_log].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEvent {
+    /// When the event happened, to pass as `since` to read later events only.
+    pub timestamp: Timestamp,
+    /// The path of the entry, like `/pub/example.com/foo.txt`.
+    pub path: String,
+    /// The hash of the written content, or `None` if the entry was deleted.
+    pub content_hash: Option<Hash>,
+}
+
+impl Client {
+    /// Returns the events (writes and deletes, public and private) of `pubky` after `since`,
+    /// or all the events the homeserver still keeps if `None`, oldest first.
+    ///
+    /// Requires a session with root capabilities for that Pubky.
+    pub async fn event_log(
+        &self,
+        pubky: &PublicKey,
+        since: Option<Timestamp>,
+    ) -> Result<Vec<LogEvent>> {
+        let mut events = Vec::new();
+        let mut since = since;
+
+        loop {
+            let mut url = format!("pubky://{pubky}/event-log?limit={EVENT_LOG_LIMIT}");
+
+            if let Some(since) = &since {
+                url.push_str(&format!("&since={since}"));
+            }
+
+            let response = self.cross_request(Method::GET, url).await.send().await?;
+
+            handle_http_error!(response, self);
+
+            let text = response.text().await?;
+
+            let page = text
+                .lines()
+                .map(parse_log_event)
+                .collect::<Result<Vec<_>>>()?;
+            let full = page.len() == EVENT_LOG_LIMIT as usize;
+
+            since = page.last().map(|event| event.timestamp).or(since);
+            events.extend(page);
+
+            if !full {
+                break;
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+/// Parse a `<timestamp> PUT <content hash> <path>` or `<timestamp> DEL <path>` line.
+fn parse_log_event(line: &str) -> Result<LogEvent> {
+    let invalid = || anyhow!("Invalid event log line: {line}");
+
+    let mut parts = line.splitn(3, ' ');
+
+    let timestamp = Timestamp::try_from(parts.next().ok_or_else(invalid)?.to_string())
+        .map_err(|_| invalid())?;
+
+    let (path, content_hash) = match (parts.next(), parts.next()) {
+        (Some("PUT"), Some(rest)) => {
+            let (content_hash, path) = rest.split_once(' ').ok_or_else(invalid)?;
+
+            (
+                path,
+                Some(Hash::from_hex(content_hash).map_err(|_| invalid())?),
+            )
+        }
+        (Some("DEL"), Some(path)) => (path, None),
+        _ => return Err(invalid()),
+    };
+
+    Ok(LogEvent {
+        timestamp,
+        path: path.to_string(),
+        content_hash,
+    })
+}
+
+#[cfg(all(test, not(wasm_browser)))]
+mod tests {
+    use pkarr::Keypair;
+    use pubky_common::crypto::hash;
+    use pubky_testnet::Testnet;
+
+    #[tokio::test]
+    async fn event_log() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let put = |path: &str, content: &'static [u8]| {
+            client
+                .put(format!("pubky://{pubky}{path}"))
+                .body(content)
+                .send()
+        };
+
+        put("/pub/a.txt", b"a").await.unwrap();
+        put("/priv/b.txt", b"b").await.unwrap();
+        put("/pub/a.txt", b"aa").await.unwrap();
+        client
+            .delete(format!("pubky://{pubky}/priv/b.txt"))
+            .send()
+            .await
+            .unwrap();
+
+        let events = client.event_log(&pubky, None).await.unwrap();
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.path.as_str(), event.content_hash))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/pub/a.txt", Some(hash(b"a"))),
+                ("/priv/b.txt", Some(hash(b"b"))),
+                ("/pub/a.txt", Some(hash(b"aa"))),
+                ("/priv/b.txt", None),
+            ]
+        );
+
+        let later = client
+            .event_log(&pubky, Some(events[1].timestamp))
+            .await
+            .unwrap();
+        assert_eq!(later, events[2..]);
+
+        // Only readable with a session of the Pubky.
+        client.signout(&pubky).await.unwrap();
+        assert!(client.event_log(&pubky, None).await.is_err());
+    }
+}
@@ -1,7 +1,11 @@
 //! HTTP methods that support `https://` with Pkarr domains, and `pubky://` URLs
 
+#[cfg(not(wasm_browser))]
+use std::time::Duration;
+
 use pkarr::PublicKey;
 use reqwest::{IntoUrl, Method, RequestBuilder};
+use url::Url;
 
 use super::super::Client;
 
@@ -14,10 +18,10 @@ impl Client {
     ///
     /// Differs from [reqwest::Client::request], in that it can make requests to:
     /// 1. HTTPs URLs with with a [pkarr::PublicKey] as Top Level Domain, by resolving
-    ///     corresponding endpoints, and verifying TLS certificates accordingly.
-    ///     (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
+    ///    corresponding endpoints, and verifying TLS certificates accordingly.
+    ///    (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
     /// 2. Pubky URLs like `pubky://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
-    ///     by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
+    ///    by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
     ///
     /// # Errors
     ///
@@ -25,27 +29,108 @@ impl Client {
     pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
         let url = url.as_str();
 
-        if url.starts_with("pubky://") {
+        let (mut request, pubky) = if let Some((pubky_host, url)) = self.static_homeserver_url(url)
+        {
+            (
+                self.icann_http
+                    .request(method, url)
+                    .header("pubky-host", pubky_host.to_string()),
+                Some(pubky_host),
+            )
+        } else if url.starts_with("pubky://") {
             let url = format!("https://_pubky.{}", url.split_at(8).1);
 
-            return self.http.request(method, url);
+            (
+                self.http.request(method, &url),
+                PublicKey::try_from(url.as_str()).ok(),
+            )
         } else if url.starts_with("https://") && PublicKey::try_from(url).is_err() {
             // TODO: remove icann_http when we can control reqwest connection
             // and or create a tls config per connection.
-            return self.icann_http.request(method, url);
+            (self.icann_http.request(method, url), None)
+        } else {
+            (
+                self.http.request(method, url),
+                PublicKey::try_from(url).ok(),
+            )
+        };
+
+        // Session cookies are attached per request, since HTTP clients are shared
+        // with clients holding other sessions, see [Client::clone_for_pubky].
+        if let Some(cookie) = pubky.and_then(|pubky| self.cookie_store.session_cookie(&pubky)) {
+            request = request.header(reqwest::header::COOKIE, cookie);
+        }
+
+        if let Some(timeout) = self.timeout_override {
+            request = request.timeout(timeout);
+        }
+
+        if self.prefer_minimal {
+            request = request.header("prefer", "return=minimal");
+        }
+
+        request
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Returns a client sending requests with `timeout`, instead of the
+    /// [crate::ClientBuilder::request_timeout] of this client, for example
+    /// for a single large upload.
+    ///
+    /// Cookies, settings and HTTP connections are shared with this client.
+    pub fn with_timeout(&self, timeout: Duration) -> Client {
+        Client {
+            timeout_override: Some(timeout),
+            ..self.clone()
         }
+    }
 
-        self.http.request(method, url)
+    #[cfg(not(wasm_browser))]
+    /// Returns a client sending requests with `Prefer: return=minimal`, asking
+    /// homeservers to respond to signups, signins and writes with `204 No Content`
+    /// instead of the session or entry, to save bandwidth.
+    ///
+    /// The session is still established from the cookie, but [Client::signup] and
+    /// [Client::signin] return the requested session instead of the homeserver's,
+    /// without caching it for [Client::can], and [Client::signup_created] can't tell
+    /// whether the account was created.
+    ///
+    /// Cookies, settings and HTTP connections are shared with this client.
+    pub fn minimal(&self) -> Client {
+        Client {
+            prefer_minimal: true,
+            ..self.clone()
+        }
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Returns the Pubky host of `url` and its url at a [crate::ClientBuilder::static_homeserver],
+    /// if it is mapped to one.
+    fn static_homeserver_url(&self, url: &str) -> Option<(PublicKey, Url)> {
+        let parsed = Url::parse(url).ok()?;
+        let host = parsed.host_str()?;
+        let pubky_host = PublicKey::try_from(host.strip_prefix("_pubky.").unwrap_or(host)).ok()?;
+
+        let mut url = self
+            .static_homeservers
+            .read()
+            .unwrap()
+            .get(&pubky_host)?
+            .clone();
+        url.set_path(parsed.path());
+        url.set_query(parsed.query());
+
+        Some((pubky_host, url))
     }
 
     /// Convenience method to make a `GET` request to a URL.
     ///
     /// Differs from [reqwest::Client::get], in that it can make requests to:
     /// 1. HTTP(s) URLs with with a [pkarr::PublicKey] as Top Level Domain, by resolving
-    ///     corresponding endpoints, and verifying TLS certificates accordingly.
-    ///     (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
+    ///    corresponding endpoints, and verifying TLS certificates accordingly.
+    ///    (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
     /// 2. Pubky URLs like `pubky://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
-    ///     by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
+    ///    by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
     ///
     /// # Errors
     ///
@@ -67,10 +152,10 @@ impl Client {
     ///
     /// Differs from [reqwest::Client::put], in that it can make requests to:
     /// 1. HTTP(s) URLs with with a [pkarr::PublicKey] as Top Level Domain, by resolving
-    ///     corresponding endpoints, and verifying TLS certificates accordingly.
-    ///     (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
+    ///    corresponding endpoints, and verifying TLS certificates accordingly.
+    ///    (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
     /// 2. Pubky URLs like `pubky://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
-    ///     by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
+    ///    by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
     ///
     /// # Errors
     ///
@@ -83,10 +168,10 @@ impl Client {
     ///
     /// Differs from [reqwest::Client::patch], in that it can make requests to:
     /// 1. HTTP(s) URLs with with a [pkarr::PublicKey] as Top Level Domain, by resolving
-    ///     corresponding endpoints, and verifying TLS certificates accordingly.
-    ///     (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
+    ///    corresponding endpoints, and verifying TLS certificates accordingly.
+    ///    (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
     /// 2. Pubky URLs like `pubky://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
-    ///     by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
+    ///    by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
     ///
     /// # Errors
     ///
@@ -99,10 +184,10 @@ impl Client {
     ///
     /// Differs from [reqwest::Client::delete], in that it can make requests to:
     /// 1. HTTP(s) URLs with with a [pkarr::PublicKey] as Top Level Domain, by resolving
-    ///     corresponding endpoints, and verifying TLS certificates accordingly.
-    ///     (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
+    ///    corresponding endpoints, and verifying TLS certificates accordingly.
+    ///    (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
     /// 2. Pubky URLs like `pubky://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
-    ///     by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
+    ///    by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
     ///
     /// # Errors
     ///
@@ -115,10 +200,10 @@ impl Client {
     ///
     /// Differs from [reqwest::Client::head], in that it can make requests to:
     /// 1. HTTP(s) URLs with with a [pkarr::PublicKey] as Top Level Domain, by resolving
-    ///     corresponding endpoints, and verifying TLS certificates accordingly.
-    ///     (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
+    ///    corresponding endpoints, and verifying TLS certificates accordingly.
+    ///    (example: `https://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`)
     /// 2. Pubky URLs like `pubky://o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
-    ///     by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
+    ///    by converting the url into `https://_pubky.o4dksfbqk85ogzdb5osziw6befigbuxmuxkuxq8434q89uj56uyy`
     ///
     /// # Errors
     ///
@@ -127,6 +212,20 @@ impl Client {
         self.request(Method::HEAD, url)
     }
 
+    /// Send a request with no body to a `pubky://` or `https://` url (including ones with
+    /// Pkarr domains), returning the response as is, whatever its status.
+    ///
+    /// An escape hatch for reading headers or statuses hidden by the other methods of
+    /// [Client], so unlike them, it doesn't turn `4xx` and `5xx` responses into errors:
+    /// handling them is the caller's responsibility.
+    pub async fn raw_request<U: IntoUrl>(
+        &self,
+        method: Method,
+        url: U,
+    ) -> anyhow::Result<reqwest::Response> {
+        Ok(self.cross_request(method, url).await.send().await?)
+    }
+
     // === Private Methods ===
 
     pub(crate) async fn cross_request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
@@ -136,8 +235,12 @@ impl Client {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use pubky_testnet::Testnet;
 
+    use crate::native::internal::mock_relay::{MockRelay, MockResponse};
+
     #[tokio::test]
     async fn http_get_pubky() {
         let testnet = Testnet::run().await.unwrap();
@@ -154,6 +257,57 @@ mod tests {
         assert_eq!(response.status(), 200)
     }
 
+    #[tokio::test]
+    async fn raw_request() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = pkarr::Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let response = client
+            .raw_request(
+                reqwest::Method::GET,
+                format!("pubky://{}/pub/missing.txt", keypair.public_key()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn with_timeout() {
+        let testnet = Testnet::run().await.unwrap();
+
+        let relay = MockRelay::run(vec![
+            MockResponse::ok(b"slow".to_vec()).delay(Duration::from_millis(500)),
+            MockResponse::ok(b"slow".to_vec()).delay(Duration::from_millis(500)),
+        ])
+        .await;
+
+        let client = testnet
+            .client_builder()
+            .request_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let response = client
+            .with_timeout(Duration::from_secs(5))
+            .get(relay.url())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "slow");
+
+        // The client default is unchanged.
+        let error = client.get(relay.url()).send().await.unwrap_err();
+        assert!(error.is_timeout());
+    }
+
     #[tokio::test]
     async fn http_get_icann() {
         let testnet = Testnet::run().await.unwrap();
@@ -168,4 +322,54 @@ mod tests {
 
         assert_eq!(response.status(), 200);
     }
+
+    #[tokio::test]
+    async fn insecure_tls() {
+        use std::{net::SocketAddr, sync::Arc};
+
+        use axum_server::tls_rustls::RustlsConfig;
+        use rustls::pki_types::PrivateKeyDer;
+
+        let testnet = Testnet::run().await.unwrap();
+
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+        let tls = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certified.cert.der().clone()],
+            PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into()),
+        )
+        .unwrap();
+
+        let listener = std::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let url = format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+
+        tokio::spawn(
+            axum_server::from_tcp_rustls(listener, RustlsConfig::from_config(Arc::new(tls))).serve(
+                axum::Router::new()
+                    .route("/", axum::routing::get(|| async { "ok" }))
+                    .into_make_service(),
+            ),
+        );
+
+        // Rejects the self-signed certificate by default.
+        let client = testnet.client_builder().build().unwrap();
+
+        assert!(client.get(&url).send().await.is_err());
+
+        let client = testnet.client_builder().insecure_tls().build().unwrap();
+
+        let response = client.get(&url).send().await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
 }
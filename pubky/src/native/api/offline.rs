@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use reqwest::{header, IntoUrl, Method, StatusCode};
+use url::Url;
+
+use pubky_common::{crypto::hash, timestamp::Timestamp};
+
+use anyhow::Result;
+
+use crate::handle_http_error;
+
+use super::super::Client;
+
+impl Client {
+    /// Write `body` to `url`, or persist it in the offline queue
+    /// (see [ClientBuilder::offline_queue][crate::ClientBuilder::offline_queue])
+    /// if the homeserver is unreachable.
+    ///
+    /// Returns `true` if the entry was written, and `false` if it was queued
+    /// for [Client::flush_pending].
+    ///
+    /// To preserve ordering, writes are always queued while there are pending writes.
+    pub async fn put_or_queue<T: IntoUrl>(&self, url: T, body: Vec<u8>) -> Result<bool> {
+        self.write_or_queue(Method::PUT, url.into_url()?, body)
+            .await
+    }
+
+    /// Delete the entry at `url`, or persist the deletion in the offline queue
+    /// if the homeserver is unreachable.
+    ///
+    /// Returns `true` if the entry was deleted, and `false` if the deletion was queued
+    /// for [Client::flush_pending].
+    pub async fn delete_or_queue<T: IntoUrl>(&self, url: T) -> Result<bool> {
+        self.write_or_queue(Method::DELETE, url.into_url()?, vec![])
+            .await
+    }
+
+    /// Replay pending writes from the offline queue, in the order they were queued.
+    ///
+    /// A queued `PUT` is skipped as a conflict if the entry was modified by someone
+    /// else after the write was queued.
+    ///
+    /// Returns the urls of the skipped writes. If the homeserver is still unreachable,
+    /// returns an error and keeps the remaining writes in the queue.
+    pub async fn flush_pending(&self) -> Result<Vec<Url>> {
+        let queue = match &self.offline_queue {
+            Some(queue) => queue,
+            None => return Ok(vec![]),
+        };
+
+        let _flushing = queue.flushing.lock().await;
+
+        let mut conflicts = vec![];
+
+        // ETags of entries written during this flush, `None` if deleted.
+        let mut written: HashMap<Url, Option<String>> = HashMap::new();
+
+        for (path, write) in queue.pending()? {
+            let mut request = self
+                .cross_request(write.method.clone(), write.url.clone())
+                .await;
+
+            request = match written.get(&write.url) {
+                Some(Some(etag)) => request.header(header::IF_MATCH, etag),
+                Some(None) => request.header(header::IF_NONE_MATCH, "*"),
+                None => request.header(header::IF_UNMODIFIED_SINCE, &write.queued_at),
+            };
+
+            if write.method == Method::PUT {
+                request = request.body(write.body.clone());
+            }
+
+            let response = request.send().await?;
+
+            if response.status() == StatusCode::PRECONDITION_FAILED {
+                conflicts.push(write.url.clone());
+            } else if write.method == Method::DELETE && response.status() == StatusCode::NOT_FOUND {
+                written.insert(write.url.clone(), None);
+            } else {
+                handle_http_error!(response, self);
+
+                let etag =
+                    (write.method == Method::PUT).then(|| format!("\"{}\"", hash(&write.body)));
+
+                written.insert(write.url.clone(), etag);
+            }
+
+            queue.remove(&path)?;
+        }
+
+        Ok(conflicts)
+    }
+
+    // === Private Methods ===
+
+    async fn write_or_queue(&self, method: Method, url: Url, body: Vec<u8>) -> Result<bool> {
+        let queue = self.offline_queue.as_ref();
+
+        if let Some(queue) = queue {
+            if !queue.is_empty()? {
+                queue.push(&PendingWrite::new(method, url, body))?;
+
+                return Ok(false);
+            }
+        }
+
+        let mut request = self.cross_request(method.clone(), url.clone()).await;
+
+        if method == Method::PUT {
+            request = request.body(body.clone());
+        }
+
+        match request.send().await {
+            Ok(response) => {
+                handle_http_error!(response, self);
+
+                Ok(true)
+            }
+            Err(error) => match queue {
+                Some(queue) if !error.is_builder() => {
+                    cross_debug!("Homeserver unreachable, queuing {method} {url}: {error}");
+
+                    queue.push(&PendingWrite::new(method, url, body))?;
+
+                    Ok(false)
+                }
+                _ => Err(error.into()),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Pending writes persisted in a directory, one file per write.
+pub(crate) struct OfflineQueue {
+    dir: PathBuf,
+    lock: Mutex<()>,
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl OfflineQueue {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn is_empty(&self) -> io::Result<bool> {
+        let _lock = self.lock.lock().expect("OfflineQueue lock");
+
+        Ok(self.paths()?.is_empty())
+    }
+
+    fn push(&self, write: &PendingWrite) -> io::Result<()> {
+        let _lock = self.lock.lock().expect("OfflineQueue lock");
+
+        fs::create_dir_all(&self.dir)?;
+
+        let next = self
+            .paths()?
+            .last()
+            .and_then(|path| path.file_name()?.to_str()?.parse::<u64>().ok())
+            .map(|sequence| sequence + 1)
+            .unwrap_or(0);
+
+        let path = self.dir.join(format!("{next:020}"));
+        let tmp = path.with_extension("tmp");
+
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&write.serialize())?;
+        file.sync_all()?;
+
+        fs::rename(tmp, path)
+    }
+
+    fn pending(&self) -> io::Result<Vec<(PathBuf, PendingWrite)>> {
+        let _lock = self.lock.lock().expect("OfflineQueue lock");
+
+        self.paths()?
+            .into_iter()
+            .map(|path| {
+                let write = PendingWrite::deserialize(&fs::read(&path)?).ok_or(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid pending write {}", path.display()),
+                ))?;
+
+                Ok((path, write))
+            })
+            .collect()
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let _lock = self.lock.lock().expect("OfflineQueue lock");
+
+        fs::remove_file(path)
+    }
+
+    /// Sorted paths of pending writes.
+    fn paths(&self) -> io::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_none())
+            .collect::<Vec<_>>();
+
+        paths.sort();
+
+        Ok(paths)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct PendingWrite {
+    method: Method,
+    url: Url,
+    /// HTTP date of when this write was queued.
+    queued_at: String,
+    body: Vec<u8>,
+}
+
+impl PendingWrite {
+    fn new(method: Method, url: Url, body: Vec<u8>) -> Self {
+        Self {
+            method,
+            url,
+            queued_at: Timestamp::now().format_http_date(),
+            body,
+        }
+    }
+
+    /// `<method> <url>\n<queued_at>\n<body>`
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = format!("{} {}\n{}\n", self.method, self.url, self.queued_at).into_bytes();
+        bytes.extend_from_slice(&self.body);
+
+        bytes
+    }
+
+    fn deserialize(bytes: &[u8]) -> Option<Self> {
+        let mut parts = bytes.splitn(3, |b| *b == b'\n');
+
+        let request_line = std::str::from_utf8(parts.next()?).ok()?;
+        let queued_at = std::str::from_utf8(parts.next()?).ok()?;
+        let body = parts.next()?;
+
+        let (method, url) = request_line.split_once(' ')?;
+
+        Some(Self {
+            method: Method::from_bytes(method.as_bytes()).ok()?,
+            url: Url::parse(url).ok()?,
+            queued_at: queued_at.to_string(),
+            body: body.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pkarr::Keypair;
+    use pubky_testnet::Testnet;
+
+    use super::*;
+
+    #[test]
+    fn pending_write_serialization() {
+        let write = PendingWrite::new(
+            Method::PUT,
+            Url::parse("pubky://foo/pub/bar.txt").unwrap(),
+            b"line 1\nline 2".to_vec(),
+        );
+
+        assert_eq!(PendingWrite::deserialize(&write.serialize()), Some(write));
+    }
+
+    #[tokio::test]
+    async fn flush_pending() {
+        let testnet = Testnet::run().await.unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pubky-offline-queue-{}", Timestamp::now()));
+
+        let client = testnet
+            .client_builder()
+            .offline_queue(dir.clone())
+            .build()
+            .unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        let a = format!("pubky://{pubky}/pub/example.com/a.txt");
+        let b = format!("pubky://{pubky}/pub/example.com/b.txt");
+        let c = format!("pubky://{pubky}/pub/example.com/c.txt");
+
+        // No homeserver is reachable for this Pubky yet.
+        assert!(!client.put_or_queue(&a, vec![1]).await.unwrap());
+        assert!(!client.put_or_queue(&b, vec![2]).await.unwrap());
+        assert!(!client.put_or_queue(&c, vec![3]).await.unwrap());
+        assert!(!client.put_or_queue(&a, vec![4]).await.unwrap());
+        assert!(!client.delete_or_queue(&c).await.unwrap());
+
+        let server = testnet.run_homeserver().await.unwrap();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let conflicts = client.flush_pending().await.unwrap();
+
+        assert!(conflicts.is_empty());
+
+        let response = client.get(&a).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(response.as_ref(), &[4]);
+
+        let response = client.get(&b).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(response.as_ref(), &[2]);
+
+        let response = client.get(&c).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Queue is empty, so writes go through directly.
+        assert!(client.put_or_queue(&b, vec![5]).await.unwrap());
+        assert!(client.flush_pending().await.unwrap().is_empty());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
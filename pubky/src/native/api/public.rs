@@ -1,133 +1,1598 @@
-use reqwest::{IntoUrl, Method};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::future::try_join_all;
+use pkarr::{Keypair, PublicKey};
+use pubky_common::{crypto::hash, timestamp::Timestamp};
+use reqwest::{header, IntoUrl, Method, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use url::Url;
 
 use anyhow::Result;
 
-use crate::handle_http_error;
+use crate::handle_http_error;
+
+use super::super::Client;
+
+/// Header setting the time to live of an entry in seconds, see [Client::put_with_ttl].
+const PUBKY_TTL_HEADER: &str = "x-pubky-ttl";
+
+/// Header carrying a signature of a write by the Pubky's keypair, see [Client::put_signed].
+const PUBKY_SIGNATURE_HEADER: &str = "x-pubky-signature";
+
+/// Content type of redirect entries, see [Client::put_redirect].
+const REDIRECT_CONTENT_TYPE: &str = "application/x-pubky-redirect";
+
+/// Page size of listings paginated by [Client::list_prefixes].
+const LIST_PAGE_LIMIT: u16 = 100;
+
+/// A JSON document tagged with the version of its schema,
+/// see [Client::get_json_versioned].
+///
+/// Serialized as `{"version": <u32>, "data": <document>}`.
+#[derive(Debug, Serialize, Deserialize)]
+struct VersionedJson<T> {
+    version: u32,
+    data: T,
+}
+
+/// Uploads larger than this send `Expect: 100-continue` in [Client::upload].
+pub const EXPECT_CONTINUE_THRESHOLD: usize = 1024 * 1024;
+
+impl Client {
+    /// Returns a [ListBuilder] to help pass options before calling [ListBuilder::send].
+    ///
+    /// `url` sets the path you want to lest within.
+    pub fn list<T: IntoUrl>(&self, url: T) -> Result<ListBuilder<'_>> {
+        Ok(ListBuilder::new(self, url))
+    }
+
+    /// Returns the Pubky URLs of the directories directly within `url_prefix`,
+    /// (ending with `/`) without any files, paginating through a shallow listing.
+    pub async fn list_prefixes<T: IntoUrl>(&self, url_prefix: T) -> Result<Vec<String>> {
+        let url = url_prefix.into_url()?;
+
+        let mut prefixes = vec![];
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut list = self.list(url.clone())?.shallow(true).limit(LIST_PAGE_LIMIT);
+
+            if let Some(cursor) = &cursor {
+                list = list.cursor(cursor);
+            }
+
+            let page = list.send().await?;
+
+            let Some(last) = page.last() else {
+                break;
+            };
+            cursor = Some(last.clone());
+
+            let full_page = page.len() == LIST_PAGE_LIMIT as usize;
+
+            prefixes.extend(page.into_iter().filter(|item| item.ends_with('/')));
+
+            if !full_page {
+                break;
+            }
+        }
+
+        Ok(prefixes)
+    }
+
+    /// Returns the entries within `url_prefix` (in a flat listing) modified after `since`,
+    /// for example to poll for changes without diffing the whole listing.
+    ///
+    /// Modification times have a precision of one microsecond, pass the latest
+    /// [ListEntry::last_modified] as `since` to only get later changes.
+    pub async fn list_since<T: IntoUrl>(
+        &self,
+        url_prefix: T,
+        since: std::time::SystemTime,
+    ) -> Result<Vec<ListEntry>> {
+        let mut url = url_prefix.into_url()?;
+
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+
+        let since = since
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let since = Timestamp::from(since).to_string();
+
+        let mut entries: Vec<ListEntry> = vec![];
+
+        loop {
+            let mut page_url = url.clone();
+
+            {
+                let mut query = page_url.query_pairs_mut();
+                query.append_pair("since", &since);
+                query.append_pair("limit", &LIST_PAGE_LIMIT.to_string());
+
+                if let Some(last) = entries.last() {
+                    query.append_pair("cursor", &last.url);
+                }
+            }
+
+            let response = self
+                .cross_request(Method::GET, page_url)
+                .await
+                .send()
+                .await?;
+
+            handle_http_error!(response, self);
+
+            let text = response.text().await?;
+
+            let page = text
+                .lines()
+                .map(ListEntry::parse)
+                .collect::<Result<Vec<_>>>()?;
+            let full_page = page.len() == LIST_PAGE_LIMIT as usize;
+
+            entries.extend(page);
+
+            if !full_page {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Write `body` to `url`, sending `Expect: 100-continue` if the body is larger
+    /// than [EXPECT_CONTINUE_THRESHOLD].
+    ///
+    /// This lets the homeserver reject the upload (quota, authorization or size limits)
+    /// from its headers alone, without receiving the body.
+    ///
+    /// `body` can be anything convertible to a [reqwest::Body], like a `&'static str`,
+    /// a `String`, a `Vec<u8>` or [Bytes]. To stream a body of known length,
+    /// use [Client::put_stream_with_len] instead.
+    ///
+    /// Returns the [PutOutcome] of the write, like the `ETag` of the written entry.
+    pub async fn upload<T: IntoUrl>(
+        &self,
+        url: T,
+        body: impl Into<reqwest::Body>,
+    ) -> Result<PutOutcome> {
+        let mut request = self.cross_request(Method::PUT, url).await;
+        let body = body.into();
+
+        if body
+            .as_bytes()
+            .is_some_and(|bytes| bytes.len() > EXPECT_CONTINUE_THRESHOLD)
+        {
+            // Reqwest doesn't wait for `100 Continue` before streaming the body,
+            // but the homeserver still responds (and closes) before reading it.
+            request = request.header(header::EXPECT, "100-continue");
+        }
+
+        let response = request.body(body).send().await?;
+
+        handle_http_error!(response, self);
+
+        Ok(PutOutcome {
+            created: response.status() == StatusCode::CREATED,
+            etag: response
+                .headers()
+                .get(header::ETAG)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from),
+        })
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Stream `body` to `url`, declaring its total `length` in bytes with a `Content-Length`
+    /// header, instead of using chunked transfer encoding.
+    ///
+    /// This lets the homeserver reject entries exceeding its size limit or the user's quota
+    /// before receiving the body. The stream has to yield exactly `length` bytes.
+    pub async fn put_stream_with_len<T, S>(&self, url: T, body: S, length: u64) -> Result<()>
+    where
+        T: IntoUrl,
+        S: futures_util::TryStream + Send + 'static,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        Bytes: From<S::Ok>,
+    {
+        let response = self
+            .cross_request(Method::PUT, url)
+            .await
+            .header(header::CONTENT_LENGTH, length)
+            .body(reqwest::Body::wrap_stream(body))
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(())
+    }
+
+    /// Write `body` to `url`, to be treated as absent (and eventually deleted)
+    /// by the homeserver after `ttl`, rounded up to whole seconds.
+    ///
+    /// Useful for ephemeral data, like one time passwords or temporary shares.
+    pub async fn put_with_ttl<T: IntoUrl>(
+        &self,
+        url: T,
+        body: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        let seconds = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+
+        let response = self
+            .cross_request(Method::PUT, url)
+            .await
+            .header(PUBKY_TTL_HEADER, seconds)
+            .body(body)
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(())
+    }
+
+    /// Atomically write `new` to `url`, only if the current content of the entry
+    /// equals `expected`, or if there is no entry at `url` if `expected` is `None`.
+    ///
+    /// Returns `true` if the entry was written, and `false` otherwise.
+    pub async fn cas<T: IntoUrl>(
+        &self,
+        url: T,
+        expected: Option<Vec<u8>>,
+        new: Vec<u8>,
+    ) -> Result<bool> {
+        let request = self.cross_request(Method::PUT, url).await.body(new);
+
+        let request = match expected {
+            Some(expected) => request.header(header::IF_MATCH, format!("\"{}\"", hash(&expected))),
+            None => request.header(header::IF_NONE_MATCH, "*"),
+        };
+
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Ok(false);
+        }
+
+        handle_http_error!(response, self);
+
+        Ok(true)
+    }
+
+    /// Write `body` to `url`, signed by the `keypair` the url belongs to.
+    ///
+    /// The homeserver verifies the signature over the method, path and body hash
+    /// on top of the session, so writes are attributable to the keypair itself.
+    /// Homeservers may require signatures for all writes.
+    pub async fn put_signed<T: IntoUrl>(
+        &self,
+        keypair: &Keypair,
+        url: T,
+        body: Vec<u8>,
+    ) -> Result<()> {
+        let url = url.into_url()?;
+        let signature = sign_write(keypair, &Method::PUT, url.path(), &body);
+
+        let response = self
+            .cross_request(Method::PUT, url)
+            .await
+            .header(PUBKY_SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(())
+    }
+
+    /// Delete the entry at `url`, signed by the `keypair` the url belongs to,
+    /// see [Client::put_signed].
+    pub async fn delete_signed<T: IntoUrl>(&self, keypair: &Keypair, url: T) -> Result<()> {
+        let url = url.into_url()?;
+        let signature = sign_write(keypair, &Method::DELETE, url.path(), &[]);
+
+        let response = self
+            .cross_request(Method::DELETE, url)
+            .await
+            .header(PUBKY_SIGNATURE_HEADER, signature)
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(())
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Delete the entry at `url` only if it wasn't modified after `since`,
+    /// for example to clean up entries that were not touched recently.
+    ///
+    /// Modification times are compared with a precision of one second.
+    ///
+    /// Returns whether the entry was deleted, and `false` if there is no entry at `url`.
+    pub async fn delete_if_unchanged_since<T: IntoUrl>(
+        &self,
+        url: T,
+        since: std::time::SystemTime,
+    ) -> Result<bool> {
+        let response = self
+            .cross_request(Method::DELETE, url)
+            .await
+            .header(header::IF_UNMODIFIED_SINCE, httpdate::fmt_http_date(since))
+            .send()
+            .await?;
+
+        match response.status() {
+            StatusCode::PRECONDITION_FAILED | StatusCode::NOT_FOUND => return Ok(false),
+            _ => {}
+        }
+
+        handle_http_error!(response, self);
+
+        Ok(true)
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Upload `files` (file names and contents) in a single `multipart/form-data` request,
+    /// like a browser `<input type=file>` form, storing each of them under the directory
+    /// `url_prefix` (like `pubky://<pubky>/pub/example.com/uploads/`).
+    ///
+    /// The homeserver sanitizes file names, keeping only their last path segment, and
+    /// replacing characters other than ASCII alphanumerics, `.`, `-` and `_` with `_`.
+    ///
+    /// Returns the urls of the stored entries.
+    pub async fn put_multipart<T: IntoUrl>(
+        &self,
+        url_prefix: T,
+        files: Vec<(String, Vec<u8>)>,
+    ) -> Result<Vec<String>> {
+        let mut form = reqwest::multipart::Form::new();
+
+        for (file_name, content) in files {
+            form = form.part(
+                "file",
+                reqwest::multipart::Part::bytes(content).file_name(file_name),
+            );
+        }
+
+        let response = self
+            .cross_request(Method::POST, url_prefix)
+            .await
+            .multipart(form)
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        let text = response.text().await?;
+
+        Ok(text.lines().map(String::from).collect())
+    }
+
+    /// Write a string to `url`, with a `text/plain; charset=utf-8` content type.
+    pub async fn put_text<T: IntoUrl>(&self, url: T, text: &str) -> Result<()> {
+        let response = self
+            .cross_request(Method::PUT, url)
+            .await
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(text.to_string())
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(())
+    }
+
+    /// Add `by` (which may be negative) to the counter entry at `url`, a decimal integer
+    /// starting at `0` if there is no entry, returning its new value.
+    ///
+    /// The homeserver reads and writes the counter in a single transaction,
+    /// so concurrent increments are never lost.
+    pub async fn increment<T: IntoUrl>(&self, url: T, by: i64) -> Result<i64> {
+        let response = self
+            .cross_request(Method::PATCH, url)
+            .await
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(by.to_string())
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(response.text().await?.trim().parse()?)
+    }
+
+    /// Write a redirect entry at `url` to the entry at the `pubky://` url `target`,
+    /// like a symbolic link, served by the homeserver as `302 Found`.
+    pub async fn put_redirect<T: IntoUrl>(&self, url: T, target: &str) -> Result<()> {
+        let response = self
+            .cross_request(Method::PUT, url)
+            .await
+            .header(header::CONTENT_TYPE, REDIRECT_CONTENT_TYPE)
+            .body(target.to_string())
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(())
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Read the entry at `url`, following redirect entries (see [Client::put_redirect])
+    /// up to `max` times.
+    ///
+    /// Returns the url of the final entry with its content, or `None` if it doesn't exist.
+    /// Fails on redirect loops, and after more than `max` redirects.
+    pub async fn get_following_redirects<T: IntoUrl>(
+        &self,
+        url: T,
+        max: usize,
+    ) -> Result<Option<(Url, Bytes)>> {
+        let mut url = url.into_url()?;
+        let mut visited = Vec::new();
+
+        loop {
+            let response = self
+                .cross_request(Method::GET, url.clone())
+                .await
+                .send()
+                .await?;
+
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+
+            if !response.status().is_redirection() {
+                handle_http_error!(response, self);
+
+                return Ok(Some((url, response.bytes().await?)));
+            }
+
+            let target = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| Url::parse(location).ok())
+                .ok_or(anyhow::anyhow!(
+                    "Redirect without a valid location at {url}"
+                ))?;
+
+            visited.push(url);
+
+            if visited.contains(&target) {
+                anyhow::bail!("Redirect loop at {target}");
+            }
+            if visited.len() > max {
+                anyhow::bail!("More than {max} redirects from {}", visited[0]);
+            }
+
+            url = target;
+        }
+    }
+
+    /// Read the entry at `url` as a string.
+    ///
+    /// Returns `None` if there is no entry at `url`, and an error
+    /// if its content is not valid UTF-8.
+    pub async fn get_text<T: IntoUrl>(&self, url: T) -> Result<Option<String>> {
+        match self.get_entry(url.into_url()?).await? {
+            Some(bytes) => Ok(Some(String::from_utf8(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Write `data` to `url` as JSON tagged with the `version` of its schema,
+    /// in the envelope `{"version": <version>, "data": <data>}`,
+    /// with an `application/json` content type.
+    pub async fn put_json_versioned<T: IntoUrl, D: Serialize>(
+        &self,
+        url: T,
+        version: u32,
+        data: &D,
+    ) -> Result<()> {
+        let body = serde_json::to_vec(&VersionedJson { version, data })?;
+
+        let response = self
+            .cross_request(Method::PUT, url)
+            .await
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        Ok(())
+    }
+
+    /// Read a JSON document written by [Client::put_json_versioned],
+    /// returning the version of its schema and its data.
+    ///
+    /// To migrate documents of older versions, read them as [serde_json::Value]
+    /// and deserialize by version, or use an untagged enum of all versions.
+    ///
+    /// Returns `None` if there is no entry at `url`, and an error if it is not
+    /// a versioned JSON envelope, or `data` doesn't deserialize as `D`.
+    pub async fn get_json_versioned<T: IntoUrl, D: DeserializeOwned>(
+        &self,
+        url: T,
+    ) -> Result<Option<(u32, D)>> {
+        match self.get_entry(url.into_url()?).await? {
+            Some(bytes) => {
+                let envelope: VersionedJson<D> = serde_json::from_slice(&bytes)?;
+
+                Ok(Some((envelope.version, envelope.data)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Read the entry at `url`, creating it with `default` first if it doesn't exist.
+    ///
+    /// The entry is created with `If-None-Match: *`, so if another client creates it
+    /// concurrently, only one creation wins and both callers return the same content.
+    pub async fn get_or_create<T: IntoUrl>(
+        &self,
+        url: T,
+        default: impl FnOnce() -> Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let url = url.into_url()?;
+
+        if let Some(content) = self.get_entry(url.clone()).await? {
+            return Ok(content);
+        }
+
+        let content = default();
+
+        if self.cas(url.clone(), None, content.clone()).await? {
+            return Ok(content);
+        }
+
+        // Lost the race, return what the winner created.
+        self.get_entry(url.clone()).await?.ok_or(anyhow::anyhow!(
+            "Entry at {url} was deleted while being created"
+        ))
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Request the entry at `url`, returning its metadata as soon as the response headers
+    /// arrive, and the body as a [EntryStream] to be consumed afterwards.
+    ///
+    /// Returns `None` if there is no entry at `url`.
+    pub async fn get_metadata_and_body<T: IntoUrl>(&self, url: T) -> Result<Option<EntryStream>> {
+        let response = self.cross_request(Method::GET, url).await.send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        handle_http_error!(response, self);
+
+        let get_header = |name: header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .map(String::from)
+        };
+
+        Ok(Some(EntryStream {
+            content_length: get_header(header::CONTENT_LENGTH).and_then(|s| s.parse().ok()),
+            content_type: get_header(header::CONTENT_TYPE),
+            etag: get_header(header::ETAG),
+            last_modified: get_header(header::LAST_MODIFIED),
+            response,
+        }))
+    }
+
+    /// Read the first `n` bytes of the entry at `url`, using a range request.
+    ///
+    /// Returns fewer bytes if the entry is shorter than `n`.
+    pub async fn get_prefix<T: IntoUrl>(&self, url: T, n: usize) -> Result<Vec<u8>> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+
+        let response = self
+            .cross_request(Method::GET, url)
+            .await
+            .header(header::RANGE, format!("bytes=0-{}", n - 1))
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        let mut bytes = response.bytes().await?.to_vec();
+
+        // Servers are allowed to ignore the range and send the full entry.
+        bytes.truncate(n);
+
+        Ok(bytes)
+    }
+
+    /// Download the entry at `url` in `parts` parallel range requests,
+    /// and reassemble them into the full content.
+    ///
+    /// Uses the `Content-Length` from a `HEAD` request to plan the ranges,
+    /// and returns `None` if there is no entry at `url`.
+    pub async fn get_range_all<T: IntoUrl>(&self, url: T, parts: usize) -> Result<Option<Bytes>> {
+        let url = url.into_url()?;
+
+        let response = self
+            .cross_request(Method::HEAD, url.clone())
+            .await
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        handle_http_error!(response, self);
+
+        let length: usize = response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| s.parse().ok())
+            .ok_or(anyhow::anyhow!("Missing Content-Length header"))?;
+
+        if length == 0 {
+            return Ok(Some(Bytes::new()));
+        }
+
+        let part_size = length.div_ceil(parts.max(1));
+
+        let requests = (0..length).step_by(part_size).map(|start| {
+            let end = (start + part_size).min(length) - 1;
+            let url = url.clone();
+
+            async move {
+                let response = self
+                    .cross_request(Method::GET, url)
+                    .await
+                    .header(header::RANGE, format!("bytes={start}-{end}"))
+                    .send()
+                    .await?;
+
+                handle_http_error!(response, self);
+
+                if response.status() != StatusCode::PARTIAL_CONTENT {
+                    anyhow::bail!("Server does not support range requests");
+                }
+
+                let bytes = response.bytes().await?;
+
+                if bytes.len() != end - start + 1 {
+                    anyhow::bail!("Entry changed while downloading ranges");
+                }
+
+                Ok(bytes)
+            }
+        });
+
+        let mut content = Vec::with_capacity(length);
+
+        for part in try_join_all(requests).await? {
+            content.extend_from_slice(&part);
+        }
+
+        Ok(Some(content.into()))
+    }
+
+    /// Negotiate the wire protocol version with a homeserver.
+    ///
+    /// Returns the version echoed by the homeserver in the `X-Pubky-Version` header,
+    /// or an error if the homeserver doesn't support [PUBKY_VERSION](crate::PUBKY_VERSION)
+    /// (`426 Upgrade Required`).
+    pub async fn negotiate_version(&self, homeserver: &PublicKey) -> Result<u8> {
+        let response = self
+            .cross_request(Method::GET, format!("https://{homeserver}"))
+            .await
+            .send()
+            .await?;
+
+        let echoed = response
+            .headers()
+            .get(crate::PUBKY_VERSION_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| s.parse::<u8>().ok());
+
+        if response.status() == StatusCode::UPGRADE_REQUIRED {
+            return Err(anyhow::anyhow!(
+                "Homeserver doesn't support Pubky version {}, expected version {}",
+                crate::PUBKY_VERSION,
+                echoed.map(|v| v.to_string()).unwrap_or("unknown".into())
+            ));
+        }
+
+        handle_http_error!(response);
+
+        // Homeservers predating version negotiation speak version 0.
+        Ok(echoed.unwrap_or(0))
+    }
+
+    // === Private Methods ===
+
+    /// Returns the content of the entry at `url`, or `None` if there is no entry.
+    async fn get_entry(&self, url: Url) -> Result<Option<Vec<u8>>> {
+        let response = self.cross_request(Method::GET, url).await.send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        handle_http_error!(response, self);
+
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// Move all entries under `from_prefix` to `to_prefix` in a single
+    /// homeserver transaction, for example to rename an app's namespace
+    /// from `pubky://<pubky>/pub/old.app/` to `pubky://<pubky>/pub/new.app/`.
+    ///
+    /// Both prefixes must be directories (ending with `/`) of the same Pubky,
+    /// and not overlap. Returns the number of moved entries.
+    pub async fn move_prefix<F: IntoUrl, T: IntoUrl>(
+        &self,
+        from_prefix: F,
+        to_prefix: T,
+    ) -> Result<u64> {
+        let from = from_prefix.into_url()?;
+        let to = to_prefix.into_url()?;
+
+        if from.host_str() != to.host_str() {
+            anyhow::bail!("Can't move entries between different Pubkys: {from} and {to}");
+        }
+
+        let mut url = from.clone();
+        url.set_path("/move");
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("from", from.path())
+            .append_pair("to", to.path());
+
+        let response = self.cross_request(Method::POST, url).await.send().await?;
+
+        handle_http_error!(response, self);
+
+        Ok(response.text().await?.parse()?)
+    }
+
+    /// Returns the storage used by each top-level directory in `/pub/`
+    /// of a Pubky (for example `/pub/pubky.app/`), in bytes.
+    ///
+    /// Requires a session with root capabilities for that Pubky.
+    pub async fn storage_breakdown(&self, pubky: &PublicKey) -> Result<Vec<(String, u64)>> {
+        let response = self
+            .cross_request(Method::GET, format!("pubky://{pubky}/storage"))
+            .await
+            .send()
+            .await?;
+
+        handle_http_error!(response, self);
+
+        let text = response.text().await?;
+
+        text.lines()
+            .map(|line| {
+                let (directory, size) = line
+                    .rsplit_once(' ')
+                    .ok_or(anyhow::anyhow!("Invalid storage breakdown line: {line}"))?;
+
+                Ok((directory.to_string(), size.parse()?))
+            })
+            .collect()
+    }
+}
+
+/// Hex encoded signature over `<METHOD> <path> ` followed by the hash of the `body`.
+fn sign_write(keypair: &Keypair, method: &Method, path: &str, body: &[u8]) -> String {
+    let mut signable = format!("{method} {path} ").into_bytes();
+    signable.extend_from_slice(hash(body).as_bytes());
+
+    keypair
+        .sign(&signable)
+        .to_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// The result of a write, see [Client::upload].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PutOutcome {
+    /// Whether the write created a new entry, instead of overwriting an existing one.
+    pub created: bool,
+    /// ETag of the written entry, for conditional follow-up requests
+    /// (like `If-Match`) without reading the entry back.
+    pub etag: Option<String>,
+}
+
+#[cfg(not(wasm_browser))]
+/// Metadata of an entry, and its body streamed from the same response.
+#[derive(Debug)]
+pub struct EntryStream {
+    /// Size of the entry in bytes.
+    pub content_length: Option<u64>,
+    /// Content type of the entry.
+    pub content_type: Option<String>,
+    /// ETag of the entry.
+    pub etag: Option<String>,
+    /// HTTP date of the last modification of the entry.
+    pub last_modified: Option<String>,
+    response: reqwest::Response,
+}
+
+#[cfg(not(wasm_browser))]
+impl EntryStream {
+    /// Returns the next chunk of the body, or `None` once the body is fully consumed.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        Ok(self.response.chunk().await?)
+    }
+
+    /// Consume the rest of the body.
+    pub async fn bytes(self) -> Result<Bytes> {
+        Ok(self.response.bytes().await?)
+    }
+}
+
+/// An entry modified after a given time, see [Client::list_since].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    /// The Pubky URL of the entry.
+    pub url: String,
+    /// When the entry was last written.
+    pub last_modified: std::time::SystemTime,
+}
+
+impl ListEntry {
+    /// Parse a `<timestamp> <url>` line.
+    fn parse(line: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid listing line: {line}");
+
+        let (timestamp, url) = line.split_once(' ').ok_or_else(invalid)?;
+        let timestamp = Timestamp::try_from(timestamp.to_string()).map_err(|_| invalid())?;
+
+        Ok(Self {
+            url: url.to_string(),
+            last_modified: std::time::UNIX_EPOCH + Duration::from_micros(timestamp.as_u64()),
+        })
+    }
+}
+
+/// The result of a conditional listing, see [ListBuilder::send_if_none_match].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listing {
+    /// The listing changed since the given `ETag`.
+    Modified {
+        /// Pubky URLs of the listed files.
+        urls: Vec<String>,
+        /// `ETag` of this listing, to pass on the next poll.
+        etag: Option<String>,
+    },
+    /// The listing still matches the given `ETag`.
+    NotModified,
+}
+
+/// Helper struct to edit Pubky homeserver's list API options before sending them.
+#[derive(Debug)]
+pub struct ListBuilder<'a> {
+    url: String,
+    reverse: bool,
+    limit: Option<u16>,
+    cursor: Option<&'a str>,
+    client: &'a Client,
+    shallow: bool,
+}
+
+impl<'a> ListBuilder<'a> {
+    /// Create a new List request builder
+    pub(crate) fn new<T: IntoUrl>(client: &'a Client, url: T) -> Self {
+        Self {
+            client,
+            url: url.as_str().to_string(),
+            limit: None,
+            cursor: None,
+            reverse: false,
+            shallow: false,
+        }
+    }
+
+    /// Set the `reverse` option.
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Set the `limit` value.
+    pub fn limit(mut self, limit: u16) -> Self {
+        self.limit = limit.into();
+        self
+    }
+
+    /// Set the `cursor` value.
+    ///
+    /// Either a full `pubky://` Url (from previous list response),
+    /// or a path (to a file or directory) relative to the `url`
+    ///
+    /// In a [ListBuilder::shallow] listing, a directory cursor (ending with `/`)
+    /// skips everything within that directory, so passing the last returned
+    /// Url continues with the next file or directory.
+    pub fn cursor(mut self, cursor: &'a str) -> Self {
+        self.cursor = cursor.into();
+        self
+    }
+
+    /// Set the `shallow` option, to list files and directories directly
+    /// within the `url`, instead of a flat list of all nested files.
+    pub fn shallow(mut self, shallow: bool) -> Self {
+        self.shallow = shallow;
+        self
+    }
+
+    /// Send the list request.
+    ///
+    /// Returns a list of Pubky URLs of the files in the path of the `url`
+    /// respecting [ListBuilder::reverse], [ListBuilder::limit] and [ListBuilder::cursor]
+    /// options.
+    pub async fn send(self) -> Result<Vec<String>> {
+        match self.send_if_none_match(None).await? {
+            Listing::Modified { urls, .. } => Ok(urls),
+            Listing::NotModified => Ok(vec![]),
+        }
+    }
+
+    /// Send the list request, unless it still matches the `etag` of a previous
+    /// [Listing::Modified], in which case [Listing::NotModified] is returned.
+    ///
+    /// Useful to cheaply poll a directory for changes, starting with `None`.
+    pub async fn send_if_none_match(self, etag: Option<&str>) -> Result<Listing> {
+        let mut url = url::Url::parse(&self.url)?;
+
+        if !url.path().ends_with('/') {
+            let path = url.path().to_string();
+            let mut parts = path.split('/').collect::<Vec<&str>>();
+            parts.pop();
+
+            let path = format!("{}/", parts.join("/"));
+
+            url.set_path(&path)
+        }
+
+        let mut query = url.query_pairs_mut();
+
+        if self.reverse {
+            query.append_key_only("reverse");
+        }
+
+        if self.shallow {
+            query.append_key_only("shallow");
+        }
+
+        if let Some(limit) = self.limit {
+            query.append_pair("limit", &limit.to_string());
+        }
+
+        if let Some(cursor) = self.cursor {
+            query.append_pair("cursor", cursor);
+        }
+
+        drop(query);
+
+        let mut request = self.client.cross_request(Method::GET, url).await;
+
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Listing::NotModified);
+        }
+
+        handle_http_error!(response, self.client);
+
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
+        // TODO: bail on too large files.
+        let bytes = response.bytes().await?;
+
+        Ok(Listing::Modified {
+            urls: String::from_utf8_lossy(&bytes)
+                .lines()
+                .map(String::from)
+                .collect(),
+            etag,
+        })
+    }
+}
+
+#[cfg(all(test, not(wasm_browser)))]
+mod tests {
+    use super::Listing;
+    use bytes::Bytes;
+    use pkarr::Keypair;
+    use pubky_testnet::Testnet;
+    use reqwest::{header, Method, StatusCode};
+
+    #[tokio::test]
+    async fn negotiate_version() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let version = client
+            .negotiate_version(&server.public_key())
+            .await
+            .unwrap();
+
+        assert_eq!(version, crate::PUBKY_VERSION);
+
+        // Mismatching version sent explicitly.
+        let response = client
+            .get(format!("https://{}", server.public_key()))
+            .header(crate::PUBKY_VERSION_HEADER, "255")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+    }
+
+    #[tokio::test]
+    async fn get_or_create() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let keypair = Keypair::random();
+
+        let a = testnet.client_builder().build().unwrap();
+        let b = testnet.client_builder().build().unwrap();
+
+        a.signup(&keypair, &server.public_key()).await.unwrap();
+        b.signin(&keypair).await.unwrap();
+
+        let url = format!("pubky://{}/pub/example.com/config", keypair.public_key());
+
+        let (from_a, from_b) = tokio::join!(
+            a.get_or_create(&url, || b"a".to_vec()),
+            b.get_or_create(&url, || b"b".to_vec())
+        );
+
+        let from_a = from_a.unwrap();
+        let from_b = from_b.unwrap();
+
+        assert_eq!(from_a, from_b);
+        assert!(from_a == b"a" || from_a == b"b");
+
+        let stored = a.get(&url).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(stored.as_ref(), from_a.as_slice());
+
+        // Existing entries are not overwritten.
+        let existing = a.get_or_create(&url, || b"c".to_vec()).await.unwrap();
+        assert_eq!(existing, from_a);
+    }
+
+    #[tokio::test]
+    async fn put_with_ttl() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/example.com/otp", keypair.public_key());
+
+        client
+            .put_with_ttl(&url, vec![1, 2, 3], std::time::Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn delete_if_unchanged_since() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let old = format!("pubky://{pubky}/pub/example.com/old");
+        let recent = format!("pubky://{pubky}/pub/example.com/recent");
+
+        client.put_text(&old, "old").await.unwrap();
+
+        // HTTP dates have a precision of one second.
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let since = std::time::SystemTime::now();
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        client.put_text(&recent, "recent").await.unwrap();
+
+        assert!(!client
+            .delete_if_unchanged_since(&recent, since)
+            .await
+            .unwrap());
+        assert!(client.get_text(&recent).await.unwrap().is_some());
+
+        assert!(client.delete_if_unchanged_since(&old, since).await.unwrap());
+        assert!(client.get_text(&old).await.unwrap().is_none());
+
+        // Already deleted.
+        assert!(!client.delete_if_unchanged_since(&old, since).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn put_multipart() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let urls = client
+            .put_multipart(
+                format!("pubky://{pubky}/pub/example.com/uploads/"),
+                vec![
+                    ("notes.txt".to_string(), b"notes".to_vec()),
+                    ("../my photo.jpg".to_string(), vec![0xff, 0xd8, 0xff, 0xe0]),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let expected = vec![
+            format!("pubky://{pubky}/pub/example.com/uploads/my_photo.jpg"),
+            format!("pubky://{pubky}/pub/example.com/uploads/notes.txt"),
+        ];
+
+        let mut sorted = urls.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+
+        let list = client
+            .list(format!("pubky://{pubky}/pub/example.com/uploads/"))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(list, expected);
+
+        let response = client.get(&expected[1]).send().await.unwrap();
+        assert_eq!(response.bytes().await.unwrap(), "notes");
+
+        let response = client.get(&expected[0]).send().await.unwrap();
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "image/jpeg"
+        );
+    }
+
+    #[tokio::test]
+    async fn json_versioned() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct ProfileV1 {
+            name: String,
+        }
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct ProfileV2 {
+            first_name: String,
+            last_name: String,
+        }
+
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let v1 = format!("pubky://{}/pub/example.com/v1.json", keypair.public_key());
+        let v2 = format!("pubky://{}/pub/example.com/v2.json", keypair.public_key());
+
+        // Written by an older version of the app.
+        client
+            .put(&v1)
+            .body(r#"{"version":1,"data":{"name":"Alice Smith"}}"#)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let profile = ProfileV2 {
+            first_name: "Bob".to_string(),
+            last_name: "Jones".to_string(),
+        };
+        client.put_json_versioned(&v2, 2, &profile).await.unwrap();
+
+        // Migrate by version.
+        let read = |(version, data): (u32, serde_json::Value)| match version {
+            1 => {
+                let v1: ProfileV1 = serde_json::from_value(data).unwrap();
+                let (first_name, last_name) = v1.name.split_once(' ').unwrap();
+
+                ProfileV2 {
+                    first_name: first_name.to_string(),
+                    last_name: last_name.to_string(),
+                }
+            }
+            2 => serde_json::from_value(data).unwrap(),
+            _ => panic!("unknown version"),
+        };
+
+        let migrated = read(client.get_json_versioned(&v1).await.unwrap().unwrap());
+        assert_eq!(
+            migrated,
+            ProfileV2 {
+                first_name: "Alice".to_string(),
+                last_name: "Smith".to_string(),
+            }
+        );
+
+        let current = read(client.get_json_versioned(&v2).await.unwrap().unwrap());
+        assert_eq!(current, profile);
+
+        assert_eq!(
+            client
+                .get_json_versioned::<_, ProfileV2>(&v2)
+                .await
+                .unwrap(),
+            Some((2, profile))
+        );
+
+        // Not an envelope.
+        client.put_text(&v1, "{}").await.unwrap();
+        assert!(client
+            .get_json_versioned::<_, serde_json::Value>(&v1)
+            .await
+            .is_err());
+
+        let missing = format!("pubky://{}/pub/example.com/missing", keypair.public_key());
+        assert!(client
+            .get_json_versioned::<_, serde_json::Value>(&missing)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn put_get_text() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/example.com/note.txt", keypair.public_key());
+
+        let text = "héllo wörld 👋 こんにちは";
+
+        client.put_text(&url, text).await.unwrap();
+
+        assert_eq!(client.get_text(&url).await.unwrap().as_deref(), Some(text));
+
+        let response = client.get(&url).send().await.unwrap();
+        assert!(response
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+
+        // Missing entry
+        let missing = format!(
+            "pubky://{}/pub/example.com/missing.txt",
+            keypair.public_key()
+        );
+        assert_eq!(client.get_text(&missing).await.unwrap(), None);
+
+        // Invalid UTF-8
+        let binary = format!("pubky://{}/pub/example.com/binary", keypair.public_key());
+        client
+            .put(&binary)
+            .body(vec![0xff, 0xfe, 0xfd])
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        assert!(client.get_text(&binary).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn list_if_none_match() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = crate::Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let pubky = keypair.public_key();
+        let dir = format!("pubky://{pubky}/pub/example.com/");
+
+        client
+            .put(format!("{dir}a.txt"))
+            .body(vec![0])
+            .send()
+            .await
+            .unwrap();
+
+        let Listing::Modified { urls, etag } = client
+            .list(&dir)
+            .unwrap()
+            .send_if_none_match(None)
+            .await
+            .unwrap()
+        else {
+            panic!("expected a modified listing");
+        };
+        assert_eq!(urls, vec![format!("{dir}a.txt")]);
+        let etag = etag.unwrap();
+
+        assert_eq!(
+            client
+                .list(&dir)
+                .unwrap()
+                .send_if_none_match(Some(&etag))
+                .await
+                .unwrap(),
+            Listing::NotModified
+        );
+
+        client
+            .put(format!("{dir}b.txt"))
+            .body(vec![0])
+            .send()
+            .await
+            .unwrap();
+
+        let Listing::Modified { urls, etag: next } = client
+            .list(&dir)
+            .unwrap()
+            .send_if_none_match(Some(&etag))
+            .await
+            .unwrap()
+        else {
+            panic!("expected a modified listing");
+        };
+        assert_eq!(urls.len(), 2);
+        assert_ne!(next.unwrap(), etag);
+    }
 
-use super::super::Client;
+    #[tokio::test]
+    async fn upload() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
 
-impl Client {
-    /// Returns a [ListBuilder] to help pass options before calling [ListBuilder::send].
-    ///
-    /// `url` sets the path you want to lest within.
-    pub fn list<T: IntoUrl>(&self, url: T) -> Result<ListBuilder> {
-        Ok(ListBuilder::new(self, url))
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/large.bin", keypair.public_key());
+
+        let body = vec![7; super::EXPECT_CONTINUE_THRESHOLD + 1];
+
+        client.upload(&url, body.clone()).await.unwrap();
+
+        let response = client
+            .get(&url)
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+
+        assert_eq!(response.as_ref(), body.as_slice());
     }
-}
 
-/// Helper struct to edit Pubky homeserver's list API options before sending them.
-#[derive(Debug)]
-pub struct ListBuilder<'a> {
-    url: String,
-    reverse: bool,
-    limit: Option<u16>,
-    cursor: Option<&'a str>,
-    client: &'a Client,
-    shallow: bool,
-}
+    #[tokio::test]
+    async fn upload_into_body() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
 
-impl<'a> ListBuilder<'a> {
-    /// Create a new List request builder
-    pub(crate) fn new<T: IntoUrl>(client: &'a Client, url: T) -> Self {
-        Self {
-            client,
-            url: url.as_str().to_string(),
-            limit: None,
-            cursor: None,
-            reverse: false,
-            shallow: false,
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = |name: &str| format!("pubky://{}/pub/{name}", keypair.public_key());
+
+        let outcome = client.upload(url("str"), "from a str").await.unwrap();
+        assert!(outcome.created);
+
+        let head = client.head(url("str")).send().await.unwrap();
+        assert_eq!(
+            outcome.etag.as_deref(),
+            head.headers()
+                .get(header::ETAG)
+                .map(|etag| etag.to_str().unwrap())
+        );
+
+        let outcome = client.upload(url("str"), "from a str").await.unwrap();
+        assert!(!outcome.created);
+
+        client
+            .upload(url("vec"), b"from a vec".to_vec())
+            .await
+            .unwrap();
+        client
+            .upload(url("bytes"), Bytes::from_static(b"from bytes"))
+            .await
+            .unwrap();
+
+        for (name, expected) in [
+            ("str", "from a str"),
+            ("vec", "from a vec"),
+            ("bytes", "from bytes"),
+        ] {
+            let body = client
+                .get(url(name))
+                .send()
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+
+            assert_eq!(body.as_ref(), expected.as_bytes());
         }
     }
 
-    /// Set the `reverse` option.
-    pub fn reverse(mut self, reverse: bool) -> Self {
-        self.reverse = reverse;
-        self
-    }
+    #[tokio::test]
+    async fn put_stream_with_len() {
+        use std::sync::{Arc, Mutex};
 
-    /// Set the `limit` value.
-    pub fn limit(mut self, limit: u16) -> Self {
-        self.limit = limit.into();
-        self
-    }
+        use axum::{extract::State, http::HeaderMap, routing::put, Router};
 
-    /// Set the `cursor` value.
-    ///
-    /// Either a full `pubky://` Url (from previous list response),
-    /// or a path (to a file or directory) relative to the `url`
-    pub fn cursor(mut self, cursor: &'a str) -> Self {
-        self.cursor = cursor.into();
-        self
-    }
+        #[derive(Clone, Default)]
+        struct Received(Arc<Mutex<Option<(HeaderMap, Bytes)>>>);
 
-    pub fn shallow(mut self, shallow: bool) -> Self {
-        self.shallow = shallow;
-        self
-    }
+        let received = Received::default();
 
-    /// Send the list request.
-    ///
-    /// Returns a list of Pubky URLs of the files in the path of the `url`
-    /// respecting [ListBuilder::reverse], [ListBuilder::limit] and [ListBuilder::cursor]
-    /// options.
-    pub async fn send(self) -> Result<Vec<String>> {
-        let mut url = url::Url::parse(&self.url)?;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
 
-        if !url.path().ends_with('/') {
-            let path = url.path().to_string();
-            let mut parts = path.split('/').collect::<Vec<&str>>();
-            parts.pop();
+        let router = Router::new()
+            .route(
+                "/upload",
+                put(
+                    |State(received): State<Received>, headers: HeaderMap, body: Bytes| async move {
+                        *received.0.lock().unwrap() = Some((headers, body));
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
 
-            let path = format!("{}/", parts.join("/"));
+        let testnet = Testnet::run().await.unwrap();
+        let client = testnet.client_builder().build().unwrap();
 
-            url.set_path(&path)
-        }
+        let chunks = vec![
+            Ok::<_, std::io::Error>(Bytes::from("hello ")),
+            Ok(Bytes::from("streamed ")),
+            Ok(Bytes::from("world")),
+        ];
 
-        let mut query = url.query_pairs_mut();
+        client
+            .put_stream_with_len(&url, futures_util::stream::iter(chunks), 20)
+            .await
+            .unwrap();
 
-        if self.reverse {
-            query.append_key_only("reverse");
-        }
+        let (headers, body) = received.0.lock().unwrap().take().unwrap();
 
-        if self.shallow {
-            query.append_key_only("shallow");
-        }
+        assert_eq!(headers.get(header::CONTENT_LENGTH).unwrap(), "20");
+        assert!(headers.get(header::TRANSFER_ENCODING).is_none());
+        assert_eq!(body.as_ref(), b"hello streamed world");
+    }
 
-        if let Some(limit) = self.limit {
-            query.append_pair("limit", &limit.to_string());
-        }
+    #[tokio::test]
+    async fn get_following_redirects() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
 
-        if let Some(cursor) = self.cursor {
-            query.append_pair("cursor", cursor);
-        }
+        let client = testnet.client_builder().build().unwrap();
 
-        drop(query);
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
 
-        let response = self
-            .client
-            .cross_request(Method::GET, url)
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = |path: &str| format!("pubky://{pubky}/pub/example.com/{path}");
+
+        client.put_text(url("target.txt"), "target").await.unwrap();
+        client
+            .put_redirect(url("b"), &url("target.txt"))
             .await
-            .send()
-            .await?;
+            .unwrap();
+        client.put_redirect(url("a"), &url("b")).await.unwrap();
 
-        handle_http_error!(response);
+        let (final_url, body) = client
+            .get_following_redirects(url("a"), 5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(final_url.as_str(), url("target.txt"));
+        assert_eq!(body.as_ref(), b"target");
 
-        // TODO: bail on too large files.
-        let bytes = response.bytes().await?;
+        // Cap
+        assert!(client.get_following_redirects(url("a"), 1).await.is_err());
 
-        Ok(String::from_utf8_lossy(&bytes)
-            .lines()
-            .map(String::from)
-            .collect())
-    }
-}
+        // Dangling
+        client
+            .put_redirect(url("dangling"), &url("missing"))
+            .await
+            .unwrap();
+        assert!(client
+            .get_following_redirects(url("dangling"), 5)
+            .await
+            .unwrap()
+            .is_none());
 
-#[cfg(test)]
-mod tests {
-    use bytes::Bytes;
-    use pkarr::Keypair;
-    use pubky_testnet::Testnet;
-    use reqwest::{Method, StatusCode};
+        // Loop
+        client.put_redirect(url("x"), &url("y")).await.unwrap();
+        client.put_redirect(url("y"), &url("x")).await.unwrap();
+
+        let error = client
+            .get_following_redirects(url("x"), 5)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("loop"));
+
+        // Plain reads return the redirect itself.
+        let response = client.get(url("a")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+    }
 
     #[tokio::test]
     async fn put_get_delete() {
@@ -169,6 +1634,124 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn move_prefix() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let paths = ["a.txt", "b.txt", "nested/c.txt"];
+
+        for path in paths {
+            client
+                .put(format!("pubky://{pubky}/pub/old.app/{path}"))
+                .body(path.to_string())
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+        }
+        // Only on the exact prefix.
+        client
+            .put(format!("pubky://{pubky}/pub/old.apps/d.txt"))
+            .body(vec![0])
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let moved = client
+            .move_prefix(
+                format!("pubky://{pubky}/pub/old.app/"),
+                format!("pubky://{pubky}/pub/new.app/"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(moved, 3);
+
+        for path in paths {
+            let response = client
+                .get(format!("pubky://{pubky}/pub/old.app/{path}"))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let text = client
+                .get_text(format!("pubky://{pubky}/pub/new.app/{path}"))
+                .await
+                .unwrap();
+            assert_eq!(text.as_deref(), Some(path));
+        }
+
+        let list = client
+            .list(format!("pubky://{pubky}/pub/"))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            list,
+            vec![
+                format!("pubky://{pubky}/pub/new.app/a.txt"),
+                format!("pubky://{pubky}/pub/new.app/b.txt"),
+                format!("pubky://{pubky}/pub/new.app/nested/c.txt"),
+                format!("pubky://{pubky}/pub/old.apps/d.txt"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn put_delete_signed() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = crate::Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/signed.txt", keypair.public_key());
+
+        client
+            .put_signed(&keypair, &url, vec![0, 1, 2])
+            .await
+            .unwrap();
+
+        let response = client
+            .get(&url)
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(response, bytes::Bytes::from(vec![0, 1, 2]));
+
+        // Signed by another keypair
+        let error = client
+            .put_signed(&Keypair::random(), &url, vec![3])
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<crate::errors::Forbidden>().is_some());
+
+        client.delete_signed(&keypair, &url).await.unwrap();
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn unauthorized_put_delete() {
         let testnet = Testnet::run().await.unwrap();
@@ -647,6 +2230,125 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn list_prefixes() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let pubky = keypair.public_key();
+
+        for path in [
+            "a.com/a.txt",
+            "a.com/nested/b.txt",
+            "example.com",
+            "example.com/c.txt",
+            "file",
+            "z.com/d.txt",
+        ] {
+            client
+                .put(format!("pubky://{pubky}/pub/{path}"))
+                .body(vec![0])
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+        }
+
+        let prefixes = client
+            .list_prefixes(format!("pubky://{pubky}/pub/"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            prefixes,
+            vec![
+                format!("pubky://{pubky}/pub/a.com/"),
+                format!("pubky://{pubky}/pub/example.com/"),
+                format!("pubky://{pubky}/pub/z.com/"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn list_since() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let pubky = keypair.public_key();
+
+        let put = |path: &str| {
+            let url = format!("pubky://{pubky}/pub/example.com/{path}");
+            let client = client.clone();
+
+            async move {
+                client
+                    .put(url)
+                    .body(vec![0])
+                    .send()
+                    .await
+                    .unwrap()
+                    .error_for_status()
+                    .unwrap();
+            }
+        };
+
+        put("a.txt").await;
+        put("nested/b.txt").await;
+        put("c.txt").await;
+
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let since = std::time::SystemTime::now();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        put("d.txt").await;
+        put("nested/e.txt").await;
+        // Overwritten after `since`.
+        put("a.txt").await;
+
+        let entries = client
+            .list_since(format!("pubky://{pubky}/pub/example.com/"), since)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.url.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                format!("pubky://{pubky}/pub/example.com/a.txt"),
+                format!("pubky://{pubky}/pub/example.com/d.txt"),
+                format!("pubky://{pubky}/pub/example.com/nested/e.txt"),
+            ]
+        );
+        assert!(entries.iter().all(|entry| entry.last_modified > since));
+
+        let latest = entries
+            .iter()
+            .map(|entry| entry.last_modified)
+            .max()
+            .unwrap();
+
+        assert!(client
+            .list_since(format!("pubky://{pubky}/pub/example.com/"), latest)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn list_events() {
         let testnet = Testnet::run().await.unwrap();
@@ -862,6 +2564,81 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn storage_breakdown() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let pubky = keypair.public_key();
+
+        let files = vec![
+            (format!("pubky://{pubky}/pub/pubky.app/a.txt"), 3),
+            (format!("pubky://{pubky}/pub/pubky.app/nested/b.txt"), 5),
+            (format!("pubky://{pubky}/pub/example.com/c.txt"), 7),
+            (format!("pubky://{pubky}/pub/file"), 1),
+        ];
+
+        for (url, size) in files {
+            client
+                .put(url)
+                .body(vec![0; size])
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+        }
+
+        let breakdown = client.storage_breakdown(&pubky).await.unwrap();
+
+        assert_eq!(
+            breakdown,
+            vec![
+                ("/pub/".to_string(), 1),
+                ("/pub/example.com/".to_string(), 7),
+                ("/pub/pubky.app/".to_string(), 8),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn cas() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/counter", keypair.public_key());
+        let url = url.as_str();
+
+        assert!(client.cas(url, None, vec![0]).await.unwrap());
+        assert!(!client.cas(url, None, vec![1]).await.unwrap());
+        assert!(client.cas(url, Some(vec![0]), vec![1]).await.unwrap());
+
+        // Contention
+        let (a, b) = tokio::join!(
+            client.cas(url, Some(vec![1]), vec![2]),
+            client.cas(url, Some(vec![1]), vec![3]),
+        );
+        let (a, b) = (a.unwrap(), b.unwrap());
+
+        assert!(a ^ b, "exactly one swap should succeed");
+
+        let response = client.get(url).send().await.unwrap().bytes().await.unwrap();
+
+        assert_eq!(response, Bytes::from(if a { vec![2] } else { vec![3] }));
+    }
+
     #[tokio::test]
     async fn stream() {
         // TODO: test better streaming API
@@ -891,4 +2668,251 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn method_not_allowed() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/foo.txt", keypair.public_key());
+
+        let response = client.post(url).body(vec![0]).send().await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let mut allow = crate::errors::MethodNotAllowed::from_response(&response)
+            .unwrap()
+            .allow;
+        allow.sort();
+
+        assert_eq!(allow, vec!["DELETE", "GET", "HEAD", "PATCH", "PUT"]);
+    }
+
+    #[tokio::test]
+    async fn increment() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = crate::Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/likes", keypair.public_key());
+
+        let values =
+            futures_util::future::try_join_all((0..100).map(|_| client.increment(&url, 1)))
+                .await
+                .unwrap();
+
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert_eq!(sorted, (1..=100).collect::<Vec<_>>());
+
+        assert_eq!(client.get_text(&url).await.unwrap().as_deref(), Some("100"));
+
+        assert_eq!(client.increment(&url, -10).await.unwrap(), 90);
+
+        // Not a counter.
+        let text = format!("pubky://{}/pub/text.txt", keypair.public_key());
+        client.put_text(&text, "hello").await.unwrap();
+
+        let error = client.increment(&text, 1).await.unwrap_err();
+        assert_eq!(
+            crate::errors::ErrorKind::of(&error),
+            crate::errors::ErrorKind::Http {
+                status: StatusCode::CONFLICT
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn head_and_get_etags() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let large = (0..3 * 1024 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        for (name, content_type, content) in [
+            ("empty", "application/octet-stream", vec![]),
+            ("text.txt", "text/plain; charset=utf-8", b"hello".to_vec()),
+            ("doc.json", "application/json", b"{\"a\":1}".to_vec()),
+            ("large.bin", "application/octet-stream", large),
+        ] {
+            let url = format!("pubky://{}/pub/{name}", keypair.public_key());
+
+            client
+                .put(&url)
+                .header(header::CONTENT_TYPE, content_type)
+                .body(content.clone())
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+
+            let head = client.head(&url).send().await.unwrap();
+            let head_etag = head
+                .headers()
+                .get(header::ETAG)
+                .and_then(|h| h.to_str().ok())
+                .map(String::from);
+
+            let get_etag = client
+                .get_metadata_and_body(&url)
+                .await
+                .unwrap()
+                .unwrap()
+                .etag;
+
+            assert_eq!(head_etag, get_etag, "{name}");
+            assert_eq!(
+                head_etag,
+                Some(format!("\"{}\"", pubky_common::crypto::hash(&content))),
+                "{name}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn get_metadata_and_body() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/large.bin", keypair.public_key());
+        let url = url.as_str();
+
+        let data = (0..4 * 1024 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        client
+            .put(url)
+            .body(data.clone())
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let mut stream = client.get_metadata_and_body(url).await.unwrap().unwrap();
+
+        assert_eq!(stream.content_length, Some(data.len() as u64));
+        assert_eq!(
+            stream.etag,
+            Some(format!("\"{}\"", pubky_common::crypto::hash(&data)))
+        );
+        assert!(stream.last_modified.is_some());
+
+        let first = stream.chunk().await.unwrap().unwrap();
+
+        // Metadata was available before the body was fully consumed.
+        assert!(first.len() < data.len());
+
+        let rest = stream.bytes().await.unwrap();
+
+        assert_eq!([first, rest].concat(), data);
+
+        let missing = format!("pubky://{}/pub/missing.bin", keypair.public_key());
+
+        assert!(client
+            .get_metadata_and_body(missing)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn get_prefix() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/foo.bin", keypair.public_key());
+        let url = url.as_str();
+
+        let data = (0..100_u8).collect::<Vec<_>>();
+
+        client
+            .put(url)
+            .body(data.clone())
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let prefix = client.get_prefix(url, 16).await.unwrap();
+
+        assert_eq!(prefix, &data[..16]);
+
+        let prefix = client.get_prefix(url, 1000).await.unwrap();
+
+        assert_eq!(prefix, data);
+    }
+
+    #[tokio::test]
+    async fn get_range_all() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let url = format!("pubky://{}/pub/large.bin", keypair.public_key());
+        let url = url.as_str();
+
+        let data = (0..1024 * 1024 + 7)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        client
+            .put(url)
+            .body(data.clone())
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let full = client.get(url).send().await.unwrap().bytes().await.unwrap();
+
+        let assembled = client.get_range_all(url, 4).await.unwrap().unwrap();
+
+        assert_eq!(assembled, full);
+        assert_eq!(assembled, Bytes::from(data));
+
+        let missing = format!("pubky://{}/pub/missing.bin", keypair.public_key());
+
+        assert_eq!(client.get_range_all(missing, 4).await.unwrap(), None);
+    }
 }
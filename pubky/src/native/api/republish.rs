@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use pkarr::Keypair;
+use pubky_common::crypto::random_bytes;
+use tokio::task::JoinSet;
+
+use super::super::Client;
+
+impl Client {
+    /// Spawn a task republishing the homeserver record of each keypair every `interval`,
+    /// minus a random jitter of up to 10%, so many agents don't republish at once.
+    ///
+    /// The `interval` should be shorter than the record's TTL (1 hour).
+    ///
+    /// Republishing stops when the returned [RepublishDaemon] is dropped.
+    pub fn start_republish_daemon(
+        &self,
+        keypairs: Vec<Keypair>,
+        interval: Duration,
+    ) -> RepublishDaemon {
+        let mut tasks = JoinSet::new();
+
+        for keypair in keypairs {
+            let client = self.clone();
+
+            tasks.spawn(async move {
+                loop {
+                    tokio::time::sleep(jittered(interval)).await;
+
+                    if let Err(error) = client.republish_homeserver(&keypair).await {
+                        tracing::warn!(
+                            pubky = %keypair.public_key(),
+                            ?error,
+                            "Failed to republish homeserver record"
+                        );
+                    }
+                }
+            });
+        }
+
+        RepublishDaemon { tasks }
+    }
+}
+
+/// Handle to a republish task started with [Client::start_republish_daemon].
+///
+/// Dropping it stops republishing.
+#[derive(Debug)]
+pub struct RepublishDaemon {
+    tasks: JoinSet<()>,
+}
+
+impl RepublishDaemon {
+    /// Stop republishing, waiting for the republish tasks to be cancelled.
+    pub async fn stop(mut self) {
+        self.tasks.shutdown().await;
+    }
+}
+
+fn jittered(interval: Duration) -> Duration {
+    let random = u64::from_le_bytes(random_bytes::<8>());
+
+    interval - interval.mul_f64((random % 1000) as f64 / 10_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pkarr::{Keypair, PublicKey, Timestamp};
+    use pubky_testnet::Testnet;
+
+    /// Resolve the most recent record of `pubky` from the DHT alone, with a fresh
+    /// client so neither a relay nor a cache can answer with an older packet.
+    async fn dht_timestamp(testnet: &Testnet, pubky: &PublicKey) -> Timestamp {
+        let pkarr = pkarr::Client::builder()
+            .no_relays()
+            .bootstrap(testnet.bootstrap())
+            .build()
+            .unwrap();
+
+        pkarr
+            .resolve_most_recent(pubky)
+            .await
+            .expect("homeserver record should be published to the DHT")
+            .timestamp()
+    }
+
+    #[tokio::test]
+    async fn republish_daemon() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = crate::Client::test_builder(&testnet).build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        let interval = Duration::from_millis(100);
+
+        let published = dht_timestamp(&testnet, &pubky).await;
+
+        let daemon = client.start_republish_daemon(vec![keypair], interval);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(30);
+        while dht_timestamp(&testnet, &pubky).await <= published {
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "homeserver record was not republished"
+            );
+
+            tokio::time::sleep(interval).await;
+        }
+
+        daemon.stop().await;
+
+        let stopped = dht_timestamp(&testnet, &pubky).await;
+
+        // A running daemon would republish several times meanwhile; waiting can only
+        // make this check miss a republish, never fail spuriously.
+        tokio::time::sleep(interval * 5).await;
+
+        assert_eq!(dht_timestamp(&testnet, &pubky).await, stopped);
+    }
+}
@@ -0,0 +1,158 @@
+use futures_util::stream::{self, StreamExt};
+use pkarr::PublicKey;
+use url::Url;
+
+use anyhow::Result;
+
+use super::super::Client;
+
+/// Maximum number of concurrent resolutions in [Client::resolve_all].
+pub const MAX_CONCURRENT_RESOLUTIONS: usize = 16;
+
+impl Client {
+    /// Resolve the homeservers of many pubkys concurrently, for example the authors of a feed.
+    ///
+    /// Runs up to [MAX_CONCURRENT_RESOLUTIONS] resolutions at a time, and reuses
+    /// cached records that are still fresh.
+    ///
+    /// Returns the homeserver url (`https://<homeserver public key>`) of each pubky,
+    /// in the same order as `pubkys`.
+    pub async fn resolve_all(&self, pubkys: &[PublicKey]) -> Vec<Result<(PublicKey, Url)>> {
+        stream::iter(pubkys)
+            .map(|pubky| async move {
+                let host = self.resolve_homeserver(pubky).await?;
+                let url = Url::parse(&format!("https://{host}"))?;
+
+                Ok((pubky.clone(), url))
+            })
+            .buffered(MAX_CONCURRENT_RESOLUTIONS)
+            .collect()
+            .await
+    }
+}
+
+#[cfg(all(test, not(wasm_browser)))]
+mod tests {
+    use pkarr::Keypair;
+    use pubky_testnet::Testnet;
+
+    use crate::Client;
+
+    #[tokio::test]
+    async fn resolve_all() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        // Publishing and resolving all these records through the testnet relay
+        // would exceed its rate limit, so only use the DHT.
+        let mut builder = Client::builder();
+        builder.pkarr(|builder| builder.bootstrap(testnet.bootstrap()).no_relays());
+        let client = builder.build().unwrap();
+
+        let keypairs = (0..5).map(|_| Keypair::random()).collect::<Vec<_>>();
+
+        for keypair in &keypairs {
+            client.signup(keypair, &server.public_key()).await.unwrap();
+        }
+
+        let unknown = Keypair::random().public_key();
+
+        let mut pubkys = keypairs.iter().map(|k| k.public_key()).collect::<Vec<_>>();
+        pubkys.push(unknown);
+
+        let results = client.resolve_all(&pubkys).await;
+
+        assert_eq!(results.len(), pubkys.len());
+
+        for (pubky, result) in pubkys.iter().zip(&results).take(keypairs.len()) {
+            let (resolved, url) = result.as_ref().unwrap();
+
+            assert_eq!(resolved, pubky);
+            assert_eq!(
+                url.host_str(),
+                Some(server.public_key().to_string().as_str())
+            );
+        }
+
+        assert!(results.last().unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_over_doh() {
+        use std::collections::HashMap;
+
+        use axum::{
+            extract::{Query, State},
+            http::header,
+            response::IntoResponse,
+            routing::get,
+            Router,
+        };
+        use base64::{alphabet::URL_SAFE, engine::general_purpose::NO_PAD, Engine};
+        use pkarr::dns::{
+            rdata::{RData, HTTPS, SVCB},
+            Name, Packet, ResourceRecord, CLASS,
+        };
+        use url::Url;
+
+        use crate::Client;
+
+        /// Answers every query with an HTTPS record targeting the homeserver.
+        async fn doh(
+            State(homeserver): State<String>,
+            Query(params): Query<HashMap<String, String>>,
+        ) -> impl IntoResponse {
+            let engine = base64::engine::GeneralPurpose::new(&URL_SAFE, NO_PAD);
+
+            let bytes = engine.decode(&params["dns"]).unwrap();
+            let query = Packet::parse(&bytes).unwrap();
+
+            let mut reply = Packet::new_reply(query.id());
+            reply.answers.push(ResourceRecord::new(
+                query.questions[0].qname.clone(),
+                CLASS::IN,
+                60,
+                RData::HTTPS(HTTPS(SVCB::new(0, Name::new(&homeserver).unwrap()))),
+            ));
+
+            (
+                [(header::CONTENT_TYPE, "application/dns-message")],
+                reply.build_bytes_vec().unwrap(),
+            )
+        }
+
+        let homeserver = Keypair::random().public_key();
+        let pubky = Keypair::random().public_key();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+
+        let router = Router::new()
+            .route("/dns-query", get(doh))
+            .with_state(homeserver.to_string());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        // No DHT, and a "relay" that never has the record.
+        let mut builder = Client::builder();
+        builder.pkarr(|builder| {
+            builder
+                .no_default_network()
+                .relays(std::slice::from_ref(&url))
+                .unwrap()
+        });
+
+        let client = builder.build().unwrap();
+        assert!(client.resolve_all(std::slice::from_ref(&pubky)).await[0].is_err());
+
+        let client = builder
+            .doh_resolver(url.join("/dns-query").unwrap())
+            .build()
+            .unwrap();
+
+        let results = client.resolve_all(std::slice::from_ref(&pubky)).await;
+        let (resolved, url) = results[0].as_ref().unwrap();
+
+        assert_eq!(resolved, &pubky);
+        assert_eq!(url.host_str(), Some(homeserver.to_string().as_str()));
+    }
+}
@@ -0,0 +1,215 @@
+//! Rotating a Pubky identity to a new keypair.
+
+use pkarr::{Keypair, PublicKey};
+use reqwest::{header, Method, StatusCode};
+use url::Url;
+
+use anyhow::Result;
+
+use crate::handle_http_error;
+
+use super::super::Client;
+
+/// Path of the redirect entry forwarding a rotated identity to its replacement,
+/// see [Client::rotate_identity].
+pub const FORWARD_PATH: &str = "/pub/.well-known/pubky-forward";
+
+/// Number of entries listed per request in [Client::rotate_identity].
+const ROTATE_LIST_LIMIT: u16 = 100;
+
+impl Client {
+    /// Rotate the identity `old` to the keypair `new`.
+    ///
+    /// Signs up `new` at `homeserver`, copies every `/pub/` entry of `old` to the same
+    /// path under `new`, then writes a redirect entry at [FORWARD_PATH] under `old`
+    /// pointing to `new`, see [Client::forwarded_to].
+    ///
+    /// Entries of `old` are left in place, and its homeserver has to be reachable.
+    pub async fn rotate_identity(
+        &self,
+        old: &Keypair,
+        new: &Keypair,
+        homeserver: &PublicKey,
+    ) -> Result<()> {
+        self.session_or_signin(old).await?;
+        self.signup(new, homeserver).await?;
+
+        let old_prefix = format!("pubky://{}", old.public_key());
+        let new_prefix = format!("pubky://{}", new.public_key());
+
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut list = self
+                .list(format!("{old_prefix}/pub/"))?
+                .limit(ROTATE_LIST_LIMIT);
+
+            if let Some(cursor) = &cursor {
+                list = list.cursor(cursor);
+            }
+
+            let urls = list.send().await?;
+
+            for url in &urls {
+                let path = &url[old_prefix.len()..];
+
+                if path == FORWARD_PATH {
+                    continue;
+                }
+
+                let response = self.cross_request(Method::GET, url).await.send().await?;
+
+                // Deleted since it was listed.
+                if response.status() == StatusCode::NOT_FOUND {
+                    continue;
+                }
+
+                let target = format!("{new_prefix}{path}");
+
+                // Redirect entries are copied as redirects.
+                if response.status().is_redirection() {
+                    if let Some(location) = response
+                        .headers()
+                        .get(header::LOCATION)
+                        .and_then(|location| location.to_str().ok())
+                    {
+                        self.put_redirect(&target, location).await?;
+                    }
+                    continue;
+                }
+
+                handle_http_error!(response, self);
+
+                let mut request = self.cross_request(Method::PUT, &target).await;
+
+                if let Some(content_type) = response.headers().get(header::CONTENT_TYPE) {
+                    request = request.header(header::CONTENT_TYPE, content_type.clone());
+                }
+
+                let response = request.body(response.bytes().await?).send().await?;
+
+                handle_http_error!(response, self);
+            }
+
+            match urls.last() {
+                Some(last) if urls.len() == ROTATE_LIST_LIMIT as usize => {
+                    cursor = Some(last.clone());
+                }
+                _ => break,
+            }
+        }
+
+        self.put_redirect(
+            format!("{old_prefix}{FORWARD_PATH}"),
+            &format!("{new_prefix}/"),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the Pubky that `pubky` was rotated to by [Client::rotate_identity],
+    /// or `None` if it has no forwarding record.
+    pub async fn forwarded_to(&self, pubky: &PublicKey) -> Result<Option<PublicKey>> {
+        let response = self
+            .cross_request(Method::GET, format!("pubky://{pubky}{FORWARD_PATH}"))
+            .await
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !response.status().is_redirection() {
+            handle_http_error!(response, self);
+
+            anyhow::bail!("Invalid forwarding record of {pubky}");
+        }
+
+        let target = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| Url::parse(location).ok())
+            .and_then(|url| url.host_str().map(String::from))
+            .ok_or(anyhow::anyhow!("Invalid forwarding record of {pubky}"))?;
+
+        Ok(Some(PublicKey::try_from(target.as_str())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pkarr::Keypair;
+    use pubky_testnet::Testnet;
+    use reqwest::header;
+
+    #[tokio::test]
+    async fn rotate_identity() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let old = Keypair::random();
+        let new = Keypair::random();
+
+        client.signup(&old, &server.public_key()).await.unwrap();
+
+        let old_pubky = old.public_key();
+        let new_pubky = new.public_key();
+
+        client
+            .put(format!("pubky://{old_pubky}/pub/example.com/foo.txt"))
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body("foo")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        client
+            .put(format!("pubky://{old_pubky}/pub/example.com/bar/baz"))
+            .body(vec![1_u8, 2, 3])
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        assert_eq!(client.forwarded_to(&old_pubky).await.unwrap(), None);
+
+        client
+            .rotate_identity(&old, &new, &server.public_key())
+            .await
+            .unwrap();
+
+        let response = client
+            .get(format!("pubky://{new_pubky}/pub/example.com/foo.txt"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain"
+        );
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"foo");
+
+        let body = client
+            .get(format!("pubky://{new_pubky}/pub/example.com/bar/baz"))
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), [1, 2, 3]);
+
+        assert_eq!(
+            client.forwarded_to(&old_pubky).await.unwrap(),
+            Some(new_pubky.clone())
+        );
+        assert_eq!(client.forwarded_to(&new_pubky).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Mirroring the public data of another Pubky.
+
+use std::collections::HashMap;
+
+use pkarr::PublicKey;
+use reqwest::{header, Method, StatusCode};
+
+use anyhow::Result;
+
+use crate::handle_http_error;
+
+use super::super::Client;
+
+/// Number of entries listed per request in [Client::diff_sync].
+const SYNC_LIST_LIMIT: u16 = 100;
+
+/// Summary of a [Client::diff_sync].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncSummary {
+    /// Paths of new or changed entries that were fetched.
+    pub fetched: Vec<String>,
+    /// Paths of entries that no longer exist on the remote.
+    pub deleted: Vec<String>,
+}
+
+impl Client {
+    /// Mirror the entries of `remote` under `prefix` (for example `/pub/example.com/`),
+    /// fetching only new or changed entries.
+    ///
+    /// `state` maps the paths of already mirrored entries to their ETags, and is updated
+    /// as entries are fetched or deleted. Start with an empty map for a full mirror,
+    /// and pass the same map again to re-sync.
+    ///
+    /// Each fetched entry is passed to `into` with its path and content.
+    pub async fn diff_sync(
+        &self,
+        remote: &PublicKey,
+        prefix: &str,
+        state: &mut HashMap<String, String>,
+        mut into: impl FnMut(&str, &[u8]),
+    ) -> Result<SyncSummary> {
+        let url_prefix = format!("pubky://{remote}");
+
+        let mut summary = SyncSummary::default();
+        let mut seen = Vec::new();
+
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut list = self
+                .list(format!("{url_prefix}{prefix}"))?
+                .limit(SYNC_LIST_LIMIT);
+
+            if let Some(cursor) = &cursor {
+                list = list.cursor(cursor);
+            }
+
+            let urls = list.send().await?;
+
+            for url in &urls {
+                let path = url[url_prefix.len()..].to_string();
+
+                let mut request = self.cross_request(Method::GET, url).await;
+
+                if let Some(etag) = state.get(&path) {
+                    request = request.header(header::IF_NONE_MATCH, etag);
+                }
+
+                let response = request.send().await?;
+
+                match response.status() {
+                    StatusCode::NOT_MODIFIED => {}
+                    // Deleted since it was listed.
+                    StatusCode::NOT_FOUND => continue,
+                    _ => {
+                        handle_http_error!(response, self);
+
+                        let etag = response
+                            .headers()
+                            .get(header::ETAG)
+                            .and_then(|h| h.to_str().ok())
+                            .map(String::from);
+
+                        let bytes = response.bytes().await?;
+
+                        into(&path, &bytes);
+
+                        match etag {
+                            Some(etag) => state.insert(path.clone(), etag),
+                            None => state.remove(&path),
+                        };
+
+                        summary.fetched.push(path.clone());
+                    }
+                }
+
+                seen.push(path);
+            }
+
+            match urls.last() {
+                Some(last) if urls.len() == SYNC_LIST_LIMIT as usize => {
+                    cursor = Some(last.clone());
+                }
+                _ => break,
+            }
+        }
+
+        let mut deleted = state
+            .keys()
+            .filter(|path| path.starts_with(prefix) && !seen.contains(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        deleted.sort();
+
+        for path in &deleted {
+            state.remove(path);
+        }
+
+        summary.deleted = deleted;
+
+        Ok(summary)
+    }
+}
+
+#[cfg(all(test, not(wasm_browser)))]
+mod tests {
+    use std::collections::HashMap;
+
+    use pkarr::Keypair;
+    use pubky_testnet::Testnet;
+
+    #[tokio::test]
+    async fn diff_sync() {
+        let testnet = Testnet::run().await.unwrap();
+        let server = testnet.run_homeserver().await.unwrap();
+
+        let client = testnet.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        client.signup(&keypair, &server.public_key()).await.unwrap();
+
+        for (name, content) in [("a", "1"), ("b", "2"), ("c", "3")] {
+            client
+                .put(format!("pubky://{pubky}/pub/example.com/{name}"))
+                .body(content)
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+        }
+
+        let mirror = testnet.client_builder().build().unwrap();
+
+        let mut state = HashMap::new();
+        let mut mirrored = HashMap::new();
+
+        let summary = mirror
+            .diff_sync(&pubky, "/pub/", &mut state, |path, bytes| {
+                mirrored.insert(path.to_string(), bytes.to_vec());
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            summary.fetched,
+            vec![
+                "/pub/example.com/a",
+                "/pub/example.com/b",
+                "/pub/example.com/c"
+            ]
+        );
+        assert_eq!(mirrored.len(), 3);
+        assert_eq!(mirrored["/pub/example.com/b"], b"2");
+
+        client
+            .put(format!("pubky://{pubky}/pub/example.com/b"))
+            .body("updated")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        client
+            .delete(format!("pubky://{pubky}/pub/example.com/c"))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let mut fetched = vec![];
+
+        let summary = mirror
+            .diff_sync(&pubky, "/pub/", &mut state, |path, bytes| {
+                fetched.push((path.to_string(), bytes.to_vec()));
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fetched,
+            vec![("/pub/example.com/b".to_string(), b"updated".to_vec())]
+        );
+        assert_eq!(summary.fetched, vec!["/pub/example.com/b"]);
+        assert_eq!(summary.deleted, vec!["/pub/example.com/c"]);
+        assert_eq!(state.len(), 2);
+    }
+}
@@ -1,64 +1,132 @@
-use std::{collections::HashMap, sync::RwLock};
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, SystemTime},
+};
 
 use pkarr::PublicKey;
 use reqwest::{cookie::CookieStore, header::HeaderValue, Response};
 
+/// A session cookie stored after signup or signin.
+#[derive(Debug, Clone)]
+struct PubkySession {
+    secret: String,
+    /// From the cookie's `Max-Age`, if any.
+    expires_at: Option<SystemTime>,
+}
+
+impl PubkySession {
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= SystemTime::now())
+    }
+}
+
+/// Session cookies of the Pubkys signed in with a [crate::Client], attached to
+/// each request to a Pubky by [crate::Client::request].
+///
+/// Kept apart from the HTTP clients' [IcannCookieJar], so that clients sharing
+/// HTTP connections (see [crate::Client::clone_for_pubky]) don't share sessions.
 #[derive(Default, Debug)]
 pub struct CookieJar {
-    pubky_sessions: RwLock<HashMap<String, String>>,
-    normal_jar: RwLock<cookie_store::CookieStore>,
+    pubky_sessions: RwLock<HashMap<PublicKey, PubkySession>>,
 }
 
 impl CookieJar {
-    pub(crate) fn store_session_after_signup(&self, response: &Response, pubky: &PublicKey) {
-        for (header_name, header_value) in response.headers() {
-            let cookie_name = &pubky.to_string();
+    /// Store the session cookie of `pubky` set by a signup, signin or session refresh `response`.
+    pub(crate) fn store_session(&self, response: &Response, pubky: &PublicKey) {
+        let cookie_name = &pubky.to_string();
 
-            if header_name == "set-cookie"
-                && header_value.as_ref().starts_with(cookie_name.as_bytes())
+        for header_value in response.headers().get_all(reqwest::header::SET_COOKIE) {
+            if let Ok(Ok(cookie)) =
+                std::str::from_utf8(header_value.as_bytes()).map(cookie::Cookie::parse)
             {
-                if let Ok(Ok(cookie)) =
-                    std::str::from_utf8(header_value.as_bytes()).map(cookie::Cookie::parse)
-                {
-                    if cookie.name() == cookie_name {
-                        let domain = format!("_pubky.{pubky}");
-                        tracing::debug!(?cookie, "Storing coookie after signup");
-
-                        self.pubky_sessions
-                            .write()
-                            .unwrap()
-                            .insert(domain, cookie.value().to_string());
-                    }
-                };
+                if cookie.name() == cookie_name {
+                    tracing::debug!(?cookie, "Storing session cookie");
+
+                    let expires_at = cookie.max_age().map(|max_age| {
+                        SystemTime::now()
+                            + Duration::from_secs(max_age.whole_seconds().max(0) as u64)
+                    });
+
+                    self.pubky_sessions.write().unwrap().insert(
+                        pubky.clone(),
+                        PubkySession {
+                            secret: cookie.value().to_string(),
+                            expires_at,
+                        },
+                    );
+                }
             }
         }
     }
 
     pub(crate) fn delete_session_after_signout(&self, pubky: &PublicKey) {
+        self.pubky_sessions.write().unwrap().remove(pubky);
+    }
+
+    /// Returns true if there is a session cookie for this Pubky that hasn't expired,
+    /// without checking whether the homeserver still considers that session valid.
+    pub(crate) fn has_session(&self, pubky: &PublicKey) -> bool {
+        self.session(pubky).is_some()
+    }
+
+    /// Returns a new jar holding only the session cookie of this Pubky, if any.
+    pub(crate) fn scoped(&self, pubky: &PublicKey) -> Self {
+        let jar = Self::default();
+
+        if let Some(session) = self.session(pubky) {
+            jar.pubky_sessions
+                .write()
+                .unwrap()
+                .insert(pubky.clone(), session);
+        }
+
+        jar
+    }
+
+    /// Returns the `Cookie` header value of the session of this Pubky, if any.
+    pub(crate) fn session_cookie(&self, pubky: &PublicKey) -> Option<HeaderValue> {
+        self.session(pubky)
+            .and_then(|session| HeaderValue::try_from(format!("{pubky}={}", session.secret)).ok())
+    }
+
+    /// Returns the session cookie of this Pubky if it hasn't expired.
+    fn session(&self, pubky: &PublicKey) -> Option<PubkySession> {
         self.pubky_sessions
-            .write()
+            .read()
             .unwrap()
-            .remove(&format!("_pubky.{pubky}"));
+            .get(pubky)
+            .filter(|session| !session.is_expired())
+            .cloned()
     }
 }
 
-impl CookieStore for CookieJar {
+/// Cookie store of the HTTP clients, shared by all clients cloned from the same
+/// [crate::Client], for cookies of ICANN websites.
+///
+/// Session cookies (named after a Pubky) are ignored, and held by each client's
+/// [CookieJar] instead.
+#[derive(Default, Debug)]
+pub struct IcannCookieJar {
+    jar: RwLock<cookie_store::CookieStore>,
+}
+
+impl CookieStore for IcannCookieJar {
     fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &url::Url) {
         let iter = cookie_headers.filter_map(|val| {
             val.to_str()
                 .ok()
                 .and_then(|s| cookie::Cookie::parse(s.to_owned()).ok())
+                .filter(|cookie| PublicKey::try_from(cookie.name()).is_err())
         });
 
-        self.normal_jar
-            .write()
-            .unwrap()
-            .store_response_cookies(iter, url);
+        self.jar.write().unwrap().store_response_cookies(iter, url);
     }
 
     fn cookies(&self, url: &url::Url) -> Option<HeaderValue> {
         let s = self
-            .normal_jar
+            .jar
             .read()
             .unwrap()
             .get_request_values(url)
@@ -67,15 +135,7 @@ impl CookieStore for CookieJar {
             .join("; ");
 
         if s.is_empty() {
-            let host = url.host_str().unwrap_or("");
-
-            if let Ok(public_key) = PublicKey::try_from(host) {
-                let cookie_name = public_key.to_string();
-
-                return self.pubky_sessions.read().unwrap().get(host).map(|secret| {
-                    HeaderValue::try_from(format!("{cookie_name}={secret}")).unwrap()
-                });
-            }
+            return None;
         }
 
         HeaderValue::from_maybe_shared(bytes::Bytes::from(s)).ok()
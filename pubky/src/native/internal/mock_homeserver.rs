@@ -0,0 +1,356 @@
+//! An in-memory stand-in for a homeserver, to test client logic without
+//! running a DHT, a Pkarr relay and a full homeserver.
+//!
+//! Supports signup, sessions, and writing, reading, deleting and (flat) listing entries,
+//! but not capabilities, conditional requests, or `reverse` and `shallow` listings.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use pkarr::{Keypair, PublicKey};
+use pubky_common::{auth::AuthVerifier, crypto::random_bytes, session::Session};
+use tokio::net::TcpListener;
+use url::Url;
+
+use crate::ClientBuilder;
+
+#[derive(Debug, Default)]
+struct Store {
+    /// `<pubky><path>` => content
+    entries: BTreeMap<String, Vec<u8>>,
+    /// session secret => session
+    sessions: HashMap<String, Session>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MockState {
+    store: Arc<Mutex<Store>>,
+    verifier: AuthVerifier,
+}
+
+/// A homeserver keeping everything in memory, reachable by clients from
+/// [MockHomeserver::client_builder] through a [ClientBuilder::static_homeserver].
+#[derive(Debug)]
+pub struct MockHomeserver {
+    keypair: Keypair,
+    address: SocketAddr,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockHomeserver {
+    /// Run a mock homeserver on a random local port.
+    pub async fn run() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock homeserver");
+        let address = listener.local_addr().expect("mock homeserver address");
+
+        let router = Router::new()
+            .route("/signup", post(signup))
+            .route("/session", get(session).delete(signout))
+            .route("/pub/", get(get_entry))
+            .route(
+                "/pub/{*path}",
+                get(get_entry).put(put_entry).delete(delete_entry),
+            )
+            .with_state(MockState::default());
+
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+
+        Self {
+            keypair: Keypair::random(),
+            address,
+            task,
+        }
+    }
+
+    /// Returns the public key of this homeserver, to pass to [crate::Client::signup].
+    pub fn public_key(&self) -> PublicKey {
+        self.keypair.public_key()
+    }
+
+    /// Returns the `http://` url this homeserver listens on.
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}", self.address)).expect("valid url")
+    }
+
+    /// Returns a [ClientBuilder] sending requests for this homeserver,
+    /// and Pubkys signed up to it, to [MockHomeserver::url].
+    pub fn client_builder(&self) -> ClientBuilder {
+        let mut builder = crate::Client::builder();
+
+        // Pkarr requires a network, even if it is never used.
+        builder
+            .pkarr(|builder| {
+                builder
+                    .no_default_network()
+                    .relays(&[self.url()])
+                    .expect("valid relay url")
+            })
+            .static_homeserver(self.public_key(), self.url());
+
+        builder
+    }
+}
+
+impl Drop for MockHomeserver {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn signup(State(state): State<MockState>, body: Bytes) -> Response {
+    let token = match state.verifier.verify(&body) {
+        Ok(token) => token,
+        Err(error) => return (StatusCode::BAD_REQUEST, error.to_string()).into_response(),
+    };
+
+    let secret = random_bytes::<16>()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    let session = Session::new(token.pubky(), token.capabilities(), None);
+    let body = session.serialize();
+
+    state
+        .store
+        .lock()
+        .unwrap()
+        .sessions
+        .insert(secret.clone(), session);
+
+    (
+        [(
+            header::SET_COOKIE,
+            format!("{}={secret}; Path=/; HttpOnly", token.pubky()),
+        )],
+        body,
+    )
+        .into_response()
+}
+
+async fn session(State(state): State<MockState>, headers: HeaderMap) -> Response {
+    let store = state.store.lock().unwrap();
+
+    match current_session(&store, &headers) {
+        Some(session) => session.serialize().into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn signout(State(state): State<MockState>, headers: HeaderMap) -> Response {
+    if let Some(secret) = pubky_host(&headers).and_then(|pubky| session_secret(&headers, &pubky)) {
+        state.store.lock().unwrap().sessions.remove(&secret);
+    }
+
+    StatusCode::OK.into_response()
+}
+
+async fn get_entry(
+    State(state): State<MockState>,
+    headers: HeaderMap,
+    uri: Uri,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let Some(pubky) = pubky_host(&headers) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let store = state.store.lock().unwrap();
+    let key = format!("{pubky}{}", uri.path());
+
+    if !key.ends_with('/') {
+        return match store.entries.get(&key) {
+            Some(content) => content.clone().into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        };
+    }
+
+    let limit = params
+        .get("limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(usize::MAX);
+
+    let start = match params.get("cursor") {
+        Some(cursor) => match cursor.strip_prefix("pubky://") {
+            Some(cursor) => Bound::Excluded(cursor.to_string()),
+            None => Bound::Excluded(format!("{key}{}", cursor.trim_start_matches('/'))),
+        },
+        None => Bound::Included(key.clone()),
+    };
+
+    store
+        .entries
+        .range((start, Bound::Unbounded))
+        .map(|(key, _)| key)
+        .take_while(|path| path.starts_with(&key))
+        .take(limit)
+        .map(|path| format!("pubky://{path}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .into_response()
+}
+
+async fn put_entry(
+    State(state): State<MockState>,
+    headers: HeaderMap,
+    uri: Uri,
+    body: Bytes,
+) -> Response {
+    let mut store = state.store.lock().unwrap();
+
+    let Some(pubky) = authorized(&store, &headers) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    match store
+        .entries
+        .insert(format!("{pubky}{}", uri.path()), body.to_vec())
+    {
+        Some(_) => StatusCode::OK,
+        None => StatusCode::CREATED,
+    }
+    .into_response()
+}
+
+async fn delete_entry(State(state): State<MockState>, headers: HeaderMap, uri: Uri) -> Response {
+    let mut store = state.store.lock().unwrap();
+
+    let Some(pubky) = authorized(&store, &headers) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    match store.entries.remove(&format!("{pubky}{}", uri.path())) {
+        Some(_) => StatusCode::OK,
+        None => StatusCode::NOT_FOUND,
+    }
+    .into_response()
+}
+
+/// The tenant of a request, from the `pubky-host` header set by static homeserver requests.
+fn pubky_host(headers: &HeaderMap) -> Option<PublicKey> {
+    PublicKey::try_from(headers.get("pubky-host")?.to_str().ok()?).ok()
+}
+
+fn session_secret(headers: &HeaderMap, pubky: &PublicKey) -> Option<String> {
+    let name = pubky.to_string();
+
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| match cookie.trim().split_once('=') {
+            Some((key, secret)) if key == name => Some(secret.to_string()),
+            _ => None,
+        })
+}
+
+fn current_session<'a>(store: &'a Store, headers: &HeaderMap) -> Option<&'a Session> {
+    let pubky = pubky_host(headers)?;
+
+    store
+        .sessions
+        .get(&session_secret(headers, &pubky)?)
+        .filter(|session| session.pubky() == &pubky)
+}
+
+/// Returns the tenant of a request if it has a session for it.
+fn authorized(store: &Store, headers: &HeaderMap) -> Option<PublicKey> {
+    current_session(store, headers).map(|session| session.pubky().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use pkarr::Keypair;
+
+    use super::MockHomeserver;
+
+    #[tokio::test]
+    async fn signup_put_get_list() {
+        let server = MockHomeserver::run().await;
+
+        let client = server.client_builder().build().unwrap();
+
+        let keypair = Keypair::random();
+        let pubky = keypair.public_key();
+
+        let session = client.signup(&keypair, &server.public_key()).await.unwrap();
+        assert_eq!(session.pubky(), &pubky);
+
+        assert_eq!(
+            client.session(&pubky).await.unwrap().unwrap().pubky(),
+            &pubky
+        );
+
+        for path in ["a.txt", "b.txt", "nested/c.txt"] {
+            client
+                .put(format!("pubky://{pubky}/pub/example.com/{path}"))
+                .body(path.to_string())
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+        }
+
+        let text = client
+            .get_text(format!("pubky://{pubky}/pub/example.com/b.txt"))
+            .await
+            .unwrap();
+        assert_eq!(text.as_deref(), Some("b.txt"));
+
+        let list = client
+            .list(format!("pubky://{pubky}/pub/example.com/"))
+            .unwrap()
+            .limit(2)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            list,
+            vec![
+                format!("pubky://{pubky}/pub/example.com/a.txt"),
+                format!("pubky://{pubky}/pub/example.com/b.txt"),
+            ]
+        );
+
+        let list = client
+            .list(format!("pubky://{pubky}/pub/example.com/"))
+            .unwrap()
+            .cursor(&list[1])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            list,
+            vec![format!("pubky://{pubky}/pub/example.com/nested/c.txt")]
+        );
+
+        // Writes require a session.
+        client.signout(&pubky).await.unwrap();
+
+        let response = client
+            .put(format!("pubky://{pubky}/pub/example.com/d.txt"))
+            .body(vec![0])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+    }
+}
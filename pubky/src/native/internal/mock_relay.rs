@@ -0,0 +1,179 @@
+//! A scriptable stand-in for an HTTP relay, to drive failure paths of the auth flow
+//! (slow responses, wrong-length bodies, server errors, redirects) in tests deterministically.
+
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use url::Url;
+
+/// A scripted response of a [MockRelay].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    delay: Duration,
+    status: u16,
+    body: Vec<u8>,
+    content_length: Option<usize>,
+    location: Option<String>,
+}
+
+impl MockResponse {
+    /// Respond immediately with `200 OK` and this body.
+    pub fn ok(body: Vec<u8>) -> Self {
+        Self {
+            delay: Duration::ZERO,
+            status: 200,
+            body,
+            content_length: None,
+            location: None,
+        }
+    }
+
+    /// Respond immediately with `302 Found` redirecting to this path.
+    pub fn redirect(path: &str) -> Self {
+        Self {
+            location: Some(path.to_string()),
+            ..Self::status(302)
+        }
+    }
+
+    /// Respond immediately with this status and an empty body.
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            ..Self::ok(vec![])
+        }
+    }
+
+    /// Wait for `delay` before responding.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+
+        self
+    }
+
+    /// Announce a `Content-Length` different from the actual body length.
+    pub fn content_length(mut self, content_length: usize) -> Self {
+        self.content_length = Some(content_length);
+
+        self
+    }
+}
+
+/// A relay that answers every request with the next scripted [MockResponse],
+/// and `404 Not Found` once the script is exhausted.
+#[derive(Debug)]
+pub struct MockRelay {
+    address: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockRelay {
+    /// Run a relay on a random local port with a script of responses.
+    pub async fn run(responses: Vec<MockResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock relay");
+        let address = listener.local_addr().expect("mock relay address");
+
+        let responses = Arc::new(Mutex::new(VecDeque::from(responses)));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let task = tokio::spawn({
+            let requests = requests.clone();
+
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let responses = responses.clone();
+                    let requests = requests.clone();
+
+                    tokio::spawn(async move {
+                        handle(stream, responses, requests).await;
+                    });
+                }
+            }
+        });
+
+        Self {
+            address,
+            requests,
+            task,
+        }
+    }
+
+    /// Returns the url to pass as a relay to [crate::Client::auth_request].
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}/link/", self.address)).expect("valid url")
+    }
+
+    /// Returns the number of requests received so far.
+    pub fn requests(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Returns the paths of the requests received so far.
+    pub fn paths(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockRelay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    responses: Arc<Mutex<VecDeque<MockResponse>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+) {
+    // Read the request head, bodies are ignored.
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => head.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    // `GET <path> HTTP/1.1`
+    let path = String::from_utf8_lossy(&head)
+        .split(' ')
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+    requests.lock().unwrap().push(path);
+
+    let response = responses
+        .lock()
+        .unwrap()
+        .pop_front()
+        .unwrap_or(MockResponse::status(404));
+
+    tokio::time::sleep(response.delay).await;
+
+    let location = response
+        .location
+        .map(|location| format!("Location: {location}\r\n"))
+        .unwrap_or_default();
+
+    let head = format!(
+        "HTTP/1.1 {} Mock\r\n{location}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_length.unwrap_or(response.body.len())
+    );
+
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}
@@ -1,8 +1,23 @@
-use pkarr::{dns::rdata::SVCB, Keypair, SignedPacket};
+use pkarr::{
+    dns::rdata::{RData, HTTPS, SVCB},
+    Keypair, PublicKey, SignedPacket,
+};
+
+#[cfg(not(wasm_browser))]
+use base64::{alphabet::URL_SAFE, engine::general_purpose::NO_PAD, Engine};
+#[cfg(not(wasm_browser))]
+use pkarr::dns::{Name, Packet, Question, CLASS, TYPE};
+#[cfg(not(wasm_browser))]
+use reqwest::header;
+#[cfg(not(wasm_browser))]
+use url::Url;
 
 use anyhow::Result;
 
-use super::super::Client;
+#[cfg(not(wasm_browser))]
+use crate::handle_http_error;
+
+use super::super::{Client, ResolutionError};
 
 impl Client {
     /// Publish the HTTPS record for `_pubky.<public_key>`.
@@ -33,4 +48,111 @@ impl Client {
 
         Ok(())
     }
+
+    /// Republish the existing HTTPS record for `_pubky.<public_key>` with a fresh timestamp.
+    #[cfg(not(wasm_browser))]
+    pub(crate) async fn republish_homeserver(&self, keypair: &Keypair) -> Result<()> {
+        let existing = self
+            .pkarr
+            .resolve_most_recent(&keypair.public_key())
+            .await
+            .ok_or(ResolutionError(
+                "No homeserver record to republish".to_string(),
+            ))?;
+
+        let host = homeserver_host(&existing).ok_or(ResolutionError(
+            "No homeserver record to republish".to_string(),
+        ))?;
+
+        self.publish_homeserver(keypair, &host).await
+    }
+
+    /// Resolve the homeserver host of `pubky`, using the cached record if it is still fresh.
+    ///
+    /// Falls back to the [crate::ClientBuilder::doh_resolver], if any,
+    /// when no record is found with Pkarr.
+    pub(crate) async fn resolve_homeserver(&self, pubky: &PublicKey) -> Result<String> {
+        let Some(signed_packet) = self.pkarr.resolve(pubky).await else {
+            #[cfg(not(wasm_browser))]
+            if let Some(doh_resolver) = &self.doh_resolver {
+                return self.resolve_homeserver_over_doh(doh_resolver, pubky).await;
+            }
+
+            return Err(ResolutionError(format!("Could not resolve {pubky}")).into());
+        };
+
+        let host = homeserver_host(&signed_packet).ok_or(ResolutionError(format!(
+            "No homeserver record found for {pubky}"
+        )))?;
+
+        Ok(host)
+    }
+
+    #[cfg(not(wasm_browser))]
+    /// Resolve the homeserver host of `pubky` from the `_pubky` HTTPS record answered by
+    /// a DNS-over-HTTPS resolver, with an [RFC 8484](https://www.rfc-editor.org/rfc/rfc8484)
+    /// `GET` request.
+    async fn resolve_homeserver_over_doh(
+        &self,
+        doh_resolver: &Url,
+        pubky: &PublicKey,
+    ) -> Result<String> {
+        let name = format!("_pubky.{pubky}");
+
+        let mut query = Packet::new_query(0);
+        query.questions.push(Question::new(
+            Name::new(&name)?,
+            TYPE::HTTPS.into(),
+            CLASS::IN.into(),
+            false,
+        ));
+
+        let engine = base64::engine::GeneralPurpose::new(&URL_SAFE, NO_PAD);
+
+        let mut url = doh_resolver.clone();
+        url.query_pairs_mut()
+            .append_pair("dns", &engine.encode(query.build_bytes_vec()?));
+
+        let response = self
+            .icann_http
+            .get(url)
+            .header(header::ACCEPT, DNS_MESSAGE_CONTENT_TYPE)
+            .send()
+            .await?;
+
+        handle_http_error!(response);
+
+        let bytes = response.bytes().await?;
+        let reply = Packet::parse(&bytes)?;
+
+        let name = Name::new(&name)?;
+
+        let host = reply
+            .answers
+            .iter()
+            .filter(|record| record.name == name)
+            .find_map(|record| match &record.rdata {
+                RData::HTTPS(HTTPS(svcb)) => Some(svcb.target.to_string()),
+                _ => None,
+            })
+            .ok_or(ResolutionError(format!(
+                "No homeserver record found for {pubky} over DNS-over-HTTPS"
+            )))?;
+
+        Ok(host)
+    }
+}
+
+#[cfg(not(wasm_browser))]
+/// Media type of DNS wire format messages, in DNS-over-HTTPS requests and responses.
+const DNS_MESSAGE_CONTENT_TYPE: &str = "application/dns-message";
+
+/// Returns the target of the `_pubky` HTTPS record.
+fn homeserver_host(signed_packet: &SignedPacket) -> Option<String> {
+    signed_packet
+        .resource_records("_pubky")
+        .find_map(|record| match &record.rdata {
+            RData::HTTPS(HTTPS(svcb)) => Some(svcb.target.to_string()),
+            _ => None,
+        })
 }
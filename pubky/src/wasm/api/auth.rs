@@ -116,6 +116,13 @@ impl AuthRequest {
         self.0.url().as_str().to_string()
     }
 
+    /// Returns the id of the relay channel this request is waiting on,
+    /// which authenticators can show to users to confirm the target.
+    #[wasm_bindgen(js_name = "channelId")]
+    pub fn channel_id(&self) -> String {
+        self.0.channel_id().to_string()
+    }
+
     /// Wait for the user to send an authentication or authorization proof.
     ///
     /// If successful, you should expect an instance of [PublicKey]
@@ -130,3 +137,69 @@ impl AuthRequest {
             .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen::prelude::*;
+    use wasm_bindgen_test::*;
+
+    use pubky_common::{auth::AuthToken, capabilities::Capabilities};
+
+    use crate::native::api::auth::encrypt_auth_token;
+
+    use super::super::super::Client;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Replace the global `fetch` with a mock relay responding to any request with `body`,
+    /// returning the replaced `fetch` and the urls of the requests it receives.
+    fn mock_relay(body: &[u8]) -> (JsValue, js_sys::Array) {
+        let global = js_sys::global();
+        let original = js_sys::Reflect::get(&global, &"fetch".into()).unwrap();
+
+        let requests = js_sys::Array::new();
+        let fetch = js_sys::Function::new_with_args(
+            "requests, body",
+            "return async (request) => {
+                requests.push(request.url);
+                const response = new Response(body);
+                Object.defineProperty(response, 'url', { value: request.url });
+                return response;
+            }",
+        )
+        .call2(&JsValue::NULL, &requests, &js_sys::Uint8Array::from(body))
+        .unwrap();
+
+        js_sys::Reflect::set(&global, &"fetch".into(), &fetch).unwrap();
+
+        (original, requests)
+    }
+
+    #[wasm_bindgen_test]
+    async fn auth_request() {
+        let client = Client::testnet();
+
+        let request = client
+            .auth_request("https://relay.example.com/link/", "")
+            .unwrap();
+
+        assert!(request.url().starts_with("pubkyauth:///"));
+
+        let keypair = pkarr::Keypair::random();
+        let token = AuthToken::sign(&keypair, Capabilities::default());
+        let encrypted_token = encrypt_auth_token(&token.serialize(), request.0.client_secret());
+
+        // The relay is polled from `spawn_local`, so only once this test yields.
+        let (original, requests) = mock_relay(&encrypted_token);
+
+        let public_key = request.response().await;
+
+        js_sys::Reflect::set(&js_sys::global(), &"fetch".into(), &original).unwrap();
+
+        assert_eq!(public_key.unwrap().z32(), keypair.public_key().to_string());
+        assert_eq!(
+            requests.get(0).as_string().unwrap(),
+            format!("https://relay.example.com/link/{}", request.channel_id())
+        );
+    }
+}